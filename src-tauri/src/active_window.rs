@@ -0,0 +1,369 @@
+//! Active Window Module
+//! Identifies the application owning the focused window at copy time.
+//!
+//! On X11 this reads `_NET_ACTIVE_WINDOW`, `WM_CLASS`, `_NET_WM_PID` and the window
+//! title. Wayland has no generic protocol for this, so the compositor's IPC is used
+//! where available: sway (`swaymsg`), Hyprland (`hyprctl`) and KWin (`kdotool`).
+
+use crate::session;
+use serde_json::Value;
+use std::process::Command;
+
+/// Information about the focused window
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActiveWindow {
+    /// Application class (X11 `WM_CLASS` class, Wayland app_id)
+    pub app_class: Option<String>,
+    /// X11 `WM_CLASS` instance name
+    pub app_instance: Option<String>,
+    /// Window title
+    pub title: Option<String>,
+    /// Process id of the window owner
+    pub pid: Option<u32>,
+}
+
+impl ActiveWindow {
+    /// Executable name of the owning process (from `/proc/<pid>/comm`)
+    pub fn process_name(&self) -> Option<String> {
+        let pid = self.pid?;
+        std::fs::read_to_string(format!("/proc/{}/comm", pid))
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Whether the class, instance or process name matches one of `names` (see `app_matches`)
+    pub fn matches_any(&self, names: &[String]) -> bool {
+        if names.is_empty() {
+            return false;
+        }
+        let matches =
+            |id: Option<&str>| id.is_some_and(|id| names.iter().any(|name| app_matches(id, name)));
+        // The process name needs a /proc read, so it is checked last
+        matches(self.app_class.as_deref())
+            || matches(self.app_instance.as_deref())
+            || matches(self.process_name().as_deref())
+    }
+}
+
+/// Whether an application id (class, app_id, process name) is the app `name`, ignoring
+/// case. The name must match the whole id or its last reverse-DNS part, so "keepassxc"
+/// matches "org.keepassxc.KeePassXC" but "code" does not match "Xcode". A `*` in the
+/// name matches any run of characters ("*codium*").
+pub fn app_matches(id: &str, name: &str) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+    let id = id.to_lowercase();
+    let name = name.to_lowercase();
+    let last_part = id.rsplit('.').next().unwrap_or_default();
+    glob_matches(&id, &name) || glob_matches(last_part, &name)
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of characters
+fn glob_matches(text: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard: the whole text must match
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Returns the focused window, or None if it cannot be determined in this session
+pub fn get_active_window() -> Option<ActiveWindow> {
+    if !session::is_wayland() {
+        return x11::active_window();
+    }
+
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        hyprland_active_window()
+    } else if std::env::var_os("SWAYSOCK").is_some() {
+        sway_active_window()
+    } else if is_kde() {
+        kwin_active_window()
+    } else {
+        None
+    }
+}
+
+fn is_kde() -> bool {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .map(|desktop| desktop.to_uppercase().contains("KDE"))
+        .unwrap_or(false)
+}
+
+/// Runs a command and returns its trimmed stdout if it succeeded
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn json_string(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+}
+
+fn json_pid(value: &Value) -> Option<u32> {
+    value
+        .get("pid")
+        .and_then(Value::as_u64)
+        .and_then(|pid| u32::try_from(pid).ok())
+        .filter(|&pid| pid > 0)
+}
+
+// --- Wayland Compositors ---
+
+fn hyprland_active_window() -> Option<ActiveWindow> {
+    let output = command_output("hyprctl", &["activewindow", "-j"])?;
+    let window: Value = serde_json::from_str(&output).ok()?;
+    Some(ActiveWindow {
+        app_class: json_string(&window, "class"),
+        app_instance: json_string(&window, "initialClass"),
+        title: json_string(&window, "title"),
+        pid: json_pid(&window),
+    })
+}
+
+fn sway_active_window() -> Option<ActiveWindow> {
+    let output = command_output("swaymsg", &["-t", "get_tree", "-r"])?;
+    let tree: Value = serde_json::from_str(&output).ok()?;
+    let node = find_focused_node(&tree)?;
+
+    // Native Wayland clients have an app_id; XWayland clients report X11 properties
+    let properties = node.get("window_properties");
+    Some(ActiveWindow {
+        app_class: json_string(node, "app_id")
+            .or_else(|| properties.and_then(|p| json_string(p, "class"))),
+        app_instance: properties.and_then(|p| json_string(p, "instance")),
+        title: json_string(node, "name"),
+        pid: json_pid(node),
+    })
+}
+
+fn find_focused_node(node: &Value) -> Option<&Value> {
+    if node.get("focused").and_then(Value::as_bool) == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(*key).and_then(Value::as_array))
+        .flatten()
+        .find_map(find_focused_node)
+}
+
+fn kwin_active_window() -> Option<ActiveWindow> {
+    let id = command_output("kdotool", &["getactivewindow"])?;
+    if id.is_empty() {
+        return None;
+    }
+    let query =
+        |command: &str| command_output("kdotool", &[command, &id]).filter(|s| !s.is_empty());
+    Some(ActiveWindow {
+        app_class: query("getwindowclassname"),
+        app_instance: None,
+        title: query("getwindowname"),
+        pid: query("getwindowpid").and_then(|pid| pid.parse().ok()),
+    })
+}
+
+// --- X11 ---
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::ActiveWindow;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, Window};
+
+    pub fn active_window() -> Option<ActiveWindow> {
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let intern = |name: &str| -> Option<u32> {
+            Some(
+                conn.intern_atom(false, name.as_bytes())
+                    .ok()?
+                    .reply()
+                    .ok()?
+                    .atom,
+            )
+        };
+        let net_active_window = intern("_NET_ACTIVE_WINDOW")?;
+        let net_wm_pid = intern("_NET_WM_PID")?;
+        let net_wm_name = intern("_NET_WM_NAME")?;
+        let utf8_string = intern("UTF8_STRING")?;
+
+        // Prefer the EWMH active window; the input focus may be a child without properties
+        let window = get_property(&conn, root, net_active_window, AtomEnum::WINDOW.into())
+            .and_then(|value| first_u32(&value))
+            .filter(|&window| window != 0)
+            .or_else(crate::focus_manager::get_focused_window)?;
+
+        let (app_instance, app_class) = get_property(
+            &conn,
+            window,
+            AtomEnum::WM_CLASS.into(),
+            AtomEnum::STRING.into(),
+        )
+        .map(|value| parse_wm_class(&value))
+        .unwrap_or_default();
+        let title = get_property(&conn, window, net_wm_name, utf8_string)
+            .or_else(|| {
+                get_property(
+                    &conn,
+                    window,
+                    AtomEnum::WM_NAME.into(),
+                    AtomEnum::STRING.into(),
+                )
+            })
+            .map(|value| String::from_utf8_lossy(&value).into_owned())
+            .filter(|title| !title.is_empty());
+        let pid = get_property(&conn, window, net_wm_pid, AtomEnum::CARDINAL.into())
+            .and_then(|value| first_u32(&value))
+            .filter(|&pid| pid > 0);
+
+        Some(ActiveWindow {
+            app_class,
+            app_instance,
+            title,
+            pid,
+        })
+    }
+
+    fn get_property(
+        conn: &impl Connection,
+        window: Window,
+        property: u32,
+        type_: u32,
+    ) -> Option<Vec<u8>> {
+        let reply = conn
+            .get_property(false, window, property, type_, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        (!reply.value.is_empty()).then_some(reply.value)
+    }
+
+    fn first_u32(value: &[u8]) -> Option<u32> {
+        let bytes: [u8; 4] = value.get(..4)?.try_into().ok()?;
+        Some(u32::from_ne_bytes(bytes))
+    }
+
+    /// WM_CLASS is "instance\0class\0"
+    pub(super) fn parse_wm_class(value: &[u8]) -> (Option<String>, Option<String>) {
+        let mut parts = value
+            .split(|&b| b == 0)
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .map(|part| (!part.is_empty()).then_some(part));
+        let instance = parts.next().flatten();
+        let class = parts.next().flatten();
+        (instance, class)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod x11 {
+    use super::ActiveWindow;
+
+    pub fn active_window() -> Option<ActiveWindow> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_any_is_case_insensitive() {
+        let window = ActiveWindow {
+            app_class: Some("KeePassXC".to_string()),
+            app_instance: Some("keepassxc".to_string()),
+            ..Default::default()
+        };
+        assert!(window.matches_any(&["keepassxc".to_string()]));
+        assert!(!window.matches_any(&["firefox".to_string()]));
+        assert!(!window.matches_any(&[]));
+    }
+
+    #[test]
+    fn test_app_matches_whole_names_or_globs() {
+        assert!(app_matches("code", "Code"));
+        assert!(app_matches("com.visualstudio.Code", "code"));
+        assert!(!app_matches("Xcode", "code"));
+        assert!(!app_matches("vscodium-helper", "code"));
+        assert!(!app_matches("org.keepassxc.KeePassXC", "keepass"));
+
+        assert!(app_matches("vscodium-helper", "*codium*"));
+        assert!(app_matches("Xcode", "*code"));
+        assert!(app_matches("org.keepassxc.KeePassXC", "keepass*"));
+        assert!(app_matches("KeePassXC", "k*p*c"));
+        assert!(!app_matches("KeePassXC", "k*z*c"));
+        // "ab" must not be matched twice by "a*b" style overlaps
+        assert!(!app_matches("ab", "ab*b"));
+    }
+
+    #[test]
+    fn test_default_exclusions_match_real_classes() {
+        let defaults: Vec<String> = ["keepassxc", "bitwarden", "1password"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        for class in ["org.keepassxc.KeePassXC", "Bitwarden", "1Password"] {
+            let window = ActiveWindow {
+                app_class: Some(class.to_string()),
+                ..Default::default()
+            };
+            assert!(window.matches_any(&defaults), "{} is not excluded", class);
+        }
+
+        let window = ActiveWindow {
+            app_class: Some("org.mozilla.firefox".to_string()),
+            ..Default::default()
+        };
+        assert!(!window.matches_any(&defaults));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_wm_class() {
+        let (instance, class) = x11::parse_wm_class(b"navigator\0firefox\0");
+        assert_eq!(instance.as_deref(), Some("navigator"));
+        assert_eq!(class.as_deref(), Some("firefox"));
+    }
+
+    #[test]
+    fn test_find_focused_sway_node() {
+        let tree: Value = serde_json::from_str(
+            r#"{"focused": false, "nodes": [
+                {"focused": false, "nodes": [], "floating_nodes": [
+                    {"focused": true, "app_id": "org.keepassxc.KeePassXC", "pid": 42, "name": "db"}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        let node = find_focused_node(&tree).unwrap();
+        assert_eq!(
+            json_string(node, "app_id").as_deref(),
+            Some("org.keepassxc.KeePassXC")
+        );
+        assert_eq!(json_pid(node), Some(42));
+    }
+}
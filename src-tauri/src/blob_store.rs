@@ -0,0 +1,411 @@
+//! Blob Store Module
+//! Content-addressed storage for image payloads and other clipboard representations.
+//!
+//! Payloads of any format are written once as a file named after their key, without
+//! an extension, and history items only keep the key. Several items may share one blob;
+//! the manager deletes a blob when the last item pointing at it goes away. When history encryption is
+//! enabled the file content is encrypted and the name is a keyed HMAC of the content,
+//! so names cannot be checked against known payloads.
+
+use crate::history_crypto::HistoryCipher;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const IMAGE_BLOB_DIR: &str = "images";
+/// Extension of blobs written by older versions, which named every payload `<key>.png`
+const LEGACY_EXTENSION: &str = "png";
+/// Suffix of blobs re-encoded by `stage_rewrite` that are not swapped in yet
+const STAGED_SUFFIX: &str = "staged";
+
+/// Directory of immutable blobs addressed by a digest of their content
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    dir: PathBuf,
+    /// Cipher for blobs written from now on; encrypted blobs are always detected on read
+    cipher: Option<Arc<HistoryCipher>>,
+}
+
+impl BlobStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, cipher: None }
+    }
+
+    pub fn set_cipher(&mut self, cipher: Option<Arc<HistoryCipher>>) {
+        self.cipher = cipher;
+    }
+
+    /// Blob key for some content written with `cipher`: its SHA-256 digest as lowercase
+    /// hex, or its HMAC under the history key when encrypted.
+    /// Blobs written by older versions use 16-digit keys and stay readable.
+    pub fn key_for(bytes: &[u8], cipher: Option<&HistoryCipher>) -> String {
+        let digest: [u8; 32] = match cipher {
+            Some(cipher) => cipher.blob_digest(bytes),
+            None => Sha256::digest(bytes).into(),
+        };
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Resolves the file path for a key, rejecting anything that is not a plain hex name
+    fn path_for(&self, key: &str) -> Result<PathBuf, String> {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid blob key '{}'", key));
+        }
+        Ok(self.dir.join(key))
+    }
+
+    /// Renames blobs written by older versions as `<key>.png` to their plain key.
+    /// Returns the number of files renamed.
+    pub fn rename_legacy_files(&self) -> Result<usize, String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(format!("Failed to read blob directory: {}", e)),
+        };
+
+        let mut renamed = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().map_or(true, |ext| ext != LEGACY_EXTENSION) {
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let Ok(new_path) = self.path_for(key) else {
+                continue;
+            };
+            // The same key means the same content, so an existing copy can stay
+            let result = if new_path.exists() {
+                fs::remove_file(&path)
+            } else {
+                fs::rename(&path, &new_path)
+            };
+            result.map_err(|e| format!("Failed to rename blob {:?}: {}", path, e))?;
+            renamed += 1;
+        }
+        Ok(renamed)
+    }
+
+    /// Stores the bytes under their content key and returns it. Existing blobs are
+    /// left untouched since identical keys mean identical content.
+    pub fn put(&self, bytes: &[u8]) -> Result<String, String> {
+        let key = Self::key_for(bytes, self.cipher.as_deref());
+        if !self.path_for(&key)?.exists() {
+            self.write(&key, bytes, self.cipher.as_deref())?;
+        }
+        Ok(key)
+    }
+
+    /// Re-encodes the given blobs with another cipher (`None` = plaintext), under their
+    /// key for that cipher (see `StagedBlobs::renamed`). The originals stay in place until
+    /// `StagedBlobs::commit`; on error nothing is left behind. Missing blobs are skipped.
+    pub fn stage_rewrite<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a str>,
+        cipher: Option<&HistoryCipher>,
+    ) -> Result<StagedBlobs, String> {
+        let mut staged = StagedBlobs {
+            files: Vec::new(),
+            created: Vec::new(),
+            superseded: Vec::new(),
+            renamed: HashMap::new(),
+        };
+        for key in keys {
+            let path = self.path_for(key)?;
+            if !path.exists() {
+                continue;
+            }
+            if let Err(e) = self.stage_blob(key, path, cipher, &mut staged) {
+                staged.discard();
+                return Err(e);
+            }
+        }
+        Ok(staged)
+    }
+
+    fn stage_blob(
+        &self,
+        key: &str,
+        path: PathBuf,
+        cipher: Option<&HistoryCipher>,
+        staged: &mut StagedBlobs,
+    ) -> Result<(), String> {
+        let bytes = self.read(key)?;
+        let new_key = Self::key_for(&bytes, cipher);
+        if new_key == key {
+            // Same name: the re-encoded file replaces the original on commit
+            staged
+                .files
+                .push(self.write_temp(key, &bytes, cipher, STAGED_SUFFIX)?);
+            return Ok(());
+        }
+        // A new name: write the blob there now, the original is removed on commit
+        let new_path = self.path_for(&new_key)?;
+        if !new_path.exists() {
+            self.write(&new_key, &bytes, cipher)?;
+            staged.created.push(new_path);
+        }
+        staged.superseded.push(path);
+        staged.renamed.insert(key.to_string(), new_key);
+        Ok(())
+    }
+
+    fn write(&self, key: &str, bytes: &[u8], cipher: Option<&HistoryCipher>) -> Result<(), String> {
+        // Write to a temp file first so a crash never leaves a truncated blob behind
+        let (tmp_path, path) = self.write_temp(key, bytes, cipher, "tmp")?;
+        fs::rename(&tmp_path, &path).map_err(|e| format!("Failed to finalize blob: {}", e))
+    }
+
+    /// Writes the blob for `key` next to its final path, returning both paths
+    fn write_temp(
+        &self,
+        key: &str,
+        bytes: &[u8],
+        cipher: Option<&HistoryCipher>,
+        suffix: &str,
+    ) -> Result<(PathBuf, PathBuf), String> {
+        let path = self.path_for(key)?;
+        let encrypted;
+        let bytes = match cipher {
+            Some(cipher) => {
+                encrypted = cipher.encrypt(bytes)?;
+                &encrypted
+            }
+            None => bytes,
+        };
+
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create blob directory: {}", e))?;
+
+        let tmp_path = path.with_extension(suffix);
+        fs::write(&tmp_path, bytes).map_err(|e| format!("Failed to write blob: {}", e))?;
+        Ok((tmp_path, path))
+    }
+
+    pub fn read(&self, key: &str) -> Result<Vec<u8>, String> {
+        let path = self.path_for(key)?;
+        let bytes = fs::read(&path).map_err(|e| format!("Failed to read blob '{}': {}", key, e))?;
+        if !HistoryCipher::is_encrypted(&bytes) {
+            return Ok(bytes);
+        }
+        match &self.cipher {
+            Some(cipher) => cipher.decrypt(&bytes),
+            None => Err(format!(
+                "Blob '{}' is encrypted but no key is available",
+                key
+            )),
+        }
+    }
+
+    /// Size of the stored file (including encryption overhead)
+    pub fn size(&self, key: &str) -> Result<u64, String> {
+        let path = self.path_for(key)?;
+        fs::metadata(&path)
+            .map(|metadata| metadata.len())
+            .map_err(|e| format!("Failed to read blob '{}': {}", key, e))
+    }
+
+    pub fn remove(&self, key: &str) -> Result<(), String> {
+        let path = self.path_for(key)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to remove blob '{}': {}", key, e)),
+        }
+    }
+
+    /// Deletes every blob (and leftover temp file) whose key is not in `keep`.
+    /// Returns the number of files removed.
+    pub fn retain(&self, keep: &HashSet<&str>) -> usize {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let key = file_name
+                .to_str()
+                .and_then(|name| name.split('.').next())
+                .unwrap_or_default();
+
+            // Referenced blobs that still have their legacy name are kept too
+            let is_blob = path.extension().map_or(true, |ext| ext == LEGACY_EXTENSION);
+            if is_blob && keep.contains(key) {
+                continue;
+            }
+
+            if fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+
+/// Blobs re-encoded by `BlobStore::stage_rewrite`, waiting to replace the originals
+#[must_use]
+pub struct StagedBlobs {
+    /// (re-encoded temp file, blob path) of blobs that keep their name
+    files: Vec<(PathBuf, PathBuf)>,
+    /// Re-encoded blobs written under a new name
+    created: Vec<PathBuf>,
+    /// Originals of the renamed blobs
+    superseded: Vec<PathBuf>,
+    /// Old key -> new key of every renamed blob
+    renamed: HashMap<String, String>,
+}
+
+impl StagedBlobs {
+    /// Keys that change with the new cipher; items must point to the new keys
+    /// before they are stored.
+    pub fn renamed(&self) -> &HashMap<String, String> {
+        &self.renamed
+    }
+
+    /// Replaces the original blobs with the re-encoded ones
+    pub fn commit(self) -> Result<(), String> {
+        for (staged, path) in &self.files {
+            fs::rename(staged, path).map_err(|e| format!("Failed to finalize blob: {}", e))?;
+        }
+        for path in &self.superseded {
+            // A leftover original is an orphan and gets swept on the next start
+            if let Err(e) = fs::remove_file(path) {
+                eprintln!("[BlobStore] Failed to remove {:?}: {}", path, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the re-encoded blobs, keeping the originals
+    pub fn discard(self) {
+        for staged in self
+            .files
+            .iter()
+            .map(|(staged, _)| staged)
+            .chain(&self.created)
+        {
+            let _ = fs::remove_file(staged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+
+    fn temp_store() -> (BlobStore, TempDir) {
+        let dir = test_support::temp_dir("blobs");
+        (BlobStore::new(dir.to_path_buf()), dir)
+    }
+
+    #[test]
+    fn test_put_read_remove() {
+        let (store, _dir) = temp_store();
+        let key = store.put(b"png-bytes").unwrap();
+        assert_eq!(store.read(&key).unwrap(), b"png-bytes");
+        // Storing the same content again reuses the blob
+        assert_eq!(store.put(b"png-bytes").unwrap(), key);
+
+        store.remove(&key).unwrap();
+        assert!(store.read(&key).is_err());
+        // Removing a missing blob is not an error
+        assert!(store.remove(&key).is_ok());
+    }
+
+    #[test]
+    fn test_encrypted_blobs() {
+        let (mut store, _dir) = temp_store();
+        let cipher = Arc::new(HistoryCipher::from_key(&HistoryCipher::generate_key()));
+        store.set_cipher(Some(cipher.clone()));
+        let key = store.put(b"png-bytes").unwrap();
+        let raw = fs::read(store.path_for(&key).unwrap()).unwrap();
+        assert!(HistoryCipher::is_encrypted(&raw));
+        assert_eq!(store.read(&key).unwrap(), b"png-bytes");
+        // The name does not reveal the content digest
+        let plain_key = BlobStore::key_for(b"png-bytes", None);
+        assert_ne!(key, plain_key);
+
+        // A discarded migration leaves the blob as it was
+        store.stage_rewrite([key.as_str()], None).unwrap().discard();
+        assert_eq!(fs::read(store.path_for(&key).unwrap()).unwrap(), raw);
+        assert!(!store.path_for(&plain_key).unwrap().exists());
+
+        // Migrate back to plaintext, which renames the blob
+        let staged = store.stage_rewrite([key.as_str()], None).unwrap();
+        assert_eq!(staged.renamed().get(&key), Some(&plain_key));
+        staged.commit().unwrap();
+        store.set_cipher(None);
+        assert!(!store.path_for(&key).unwrap().exists());
+        assert_eq!(
+            fs::read(store.path_for(&plain_key).unwrap()).unwrap(),
+            b"png-bytes"
+        );
+
+        // Another key gives another name
+        let other = HistoryCipher::from_key(&HistoryCipher::generate_key());
+        assert_ne!(BlobStore::key_for(b"png-bytes", Some(&other)), key);
+    }
+
+    #[test]
+    fn test_rejects_path_traversal() {
+        let (store, _dir) = temp_store();
+        assert!(store.read("../escape").is_err());
+        assert!(store.remove("../escape").is_err());
+        assert!(store.read("").is_err());
+    }
+
+    #[test]
+    fn test_retain_removes_orphans() {
+        let (store, _dir) = temp_store();
+        let kept = store.put(b"a").unwrap();
+        let orphan = store.put(b"b").unwrap();
+
+        let keep: HashSet<&str> = [kept.as_str()].into_iter().collect();
+        assert_eq!(store.retain(&keep), 1);
+        assert!(store.read(&kept).is_ok());
+        assert!(store.read(&orphan).is_err());
+    }
+
+    #[test]
+    fn test_keys_are_content_digests() {
+        let (store, _dir) = temp_store();
+        let a = store.put(b"first image").unwrap();
+        let b = store.put(b"second image").unwrap();
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+        assert_eq!(store.read(&a).unwrap(), b"first image");
+        assert_eq!(store.read(&b).unwrap(), b"second image");
+
+        // Blobs keyed by the old 64-bit hash are still found
+        let legacy = format!("{:016x}", 42u64);
+        fs::write(store.path_for(&legacy).unwrap(), b"old image").unwrap();
+        assert_eq!(store.read(&legacy).unwrap(), b"old image");
+    }
+
+    #[test]
+    fn test_legacy_png_names_are_renamed() {
+        let (store, dir) = temp_store();
+        let key = BlobStore::key_for(b"rtf payload", None);
+        let legacy_path = dir.join(format!("{}.png", key));
+        fs::write(&legacy_path, b"rtf payload").unwrap();
+        // A leftover temp file of an interrupted write is not a blob
+        fs::write(dir.join(format!("{}.png.tmp", key)), b"rtf").unwrap();
+
+        // Until renamed, a referenced legacy file survives the orphan sweep
+        let keep: HashSet<&str> = [key.as_str()].into_iter().collect();
+        assert_eq!(store.retain(&keep), 1);
+        assert!(legacy_path.exists());
+
+        assert_eq!(store.rename_legacy_files().unwrap(), 1);
+        assert!(!legacy_path.exists());
+        assert_eq!(store.read(&key).unwrap(), b"rtf payload");
+        assert_eq!(store.rename_legacy_files().unwrap(), 0);
+    }
+}
@@ -1,15 +1,30 @@
 //! Clipboard Manager Module
 //! Handles clipboard monitoring, history storage, and paste injection
 
+use crate::active_window::{self, ActiveWindow};
+use crate::blob_store::{BlobStore, IMAGE_BLOB_DIR};
+use crate::clipboard_targets;
+use crate::copied_files::{self, CopiedFiles, FileOperation};
+use crate::history_crypto::{self, EncryptionConfig, HistoryCipher};
+use crate::history_retention::RetentionPolicy;
+use crate::history_search::{SearchCache, SearchQuery, SearchResults};
+use crate::history_store::{self, HistoryStore, MemoryHistoryStore, StoreChange};
+use crate::image_policy::{self, EncodedImage, ImagePolicy, THUMBNAIL_MAX_SIZE};
+use crate::selection_owner::{self, SelectionTarget};
+use crate::sensitive_paste::{
+    self, ClipboardSave, PastedContent, PendingRevert, SensitivePasteAction, SensitivePastePolicy,
+    TRANSIENT_RESTORE_DELAY,
+};
 use arboard::{Clipboard, ImageData};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
-use chrono::{DateTime, Utc};
-use image::{DynamicImage, ImageFormat};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
-use std::io::Cursor;
-use std::path::PathBuf;
+use std::path::Path;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use uuid::Uuid;
@@ -18,8 +33,17 @@ use uuid::Uuid;
 
 pub const DEFAULT_MAX_HISTORY_SIZE: usize = 50;
 const PREVIEW_TEXT_MAX_LEN: usize = 100;
+/// Items per page sent to the frontend when no limit is given, and the largest page allowed
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+/// Text shipped with a list summary; the rest is loaded on demand
+const SUMMARY_TEXT_MAX_LEN: usize = 1000;
 const GIF_CACHE_MARKER: &str = "win11-clipboard-history/gifs/";
-const FILE_URI_PREFIX: &str = "file://";
+const TEXT_MIME: &str = "text/plain;charset=utf-8";
+const HTML_MIME: &str = "text/html";
+/// Time after a revert emptied the clipboard during which owner loss is not answered,
+/// since the emptied clipboard is reported the same way
+const CLEARED_OWNER_LOSS_GRACE_SECS: i64 = 2;
 
 // --- Helper Functions ---
 
@@ -55,6 +79,27 @@ pub fn calculate_hash<T: Hash>(t: &T) -> u64 {
     s.finish()
 }
 
+/// Bytes an item takes in storage: its text or blobs, plus the other formats
+fn item_bytes(blobs: &BlobStore, item: &ClipboardItem) -> u64 {
+    let content = match &item.content {
+        ClipboardContent::Text(text) => text.len() as u64,
+        ClipboardContent::RichText { plain, html } => (plain.len() + html.len()) as u64,
+        ClipboardContent::Files { uris, .. } => uris.iter().map(|uri| uri.len() as u64).sum(),
+        ClipboardContent::Image { .. } => item
+            .blob_key()
+            .into_iter()
+            .chain(item.thumbnail_key())
+            .map(|key| blobs.size(key).unwrap_or(0))
+            .sum(),
+    };
+    content
+        + item
+            .formats
+            .iter()
+            .map(|format| format.size as u64)
+            .sum::<u64>()
+}
+
 /// Helper to get a fresh clipboard instance.
 fn get_system_clipboard() -> Result<Clipboard, String> {
     Clipboard::new().map_err(|e| e.to_string())
@@ -70,14 +115,180 @@ pub enum ClipboardContent {
     Text(String),
     /// Rich text with HTML formatting (plain text + optional HTML)
     RichText { plain: String, html: String },
-    /// Image as base64 encoded PNG
+    /// Image stored as a PNG (or JPEG, see `ImagePolicy`) blob in the `BlobStore`, referenced by key
     Image {
-        base64: String,
+        #[serde(default)]
+        blob: String,
         width: u32,
         height: u32,
+        /// Hash of the RGBA pixels, used to recognise the same image when copied again
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hash: Option<u64>,
+        /// Blob store key of the list thumbnail. `None` for images that are small
+        /// enough to be shown as they are, and for items recorded before thumbnails.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thumbnail: Option<String>,
+        /// Inline base64 PNG written by versions before blob storage.
+        /// Moved into the blob store on load and never written back.
+        #[serde(default, rename = "base64", skip_serializing)]
+        legacy_base64: Option<String>,
+    },
+    /// Files and folders copied in a file manager, as URIs
+    Files {
+        uris: Vec<String>,
+        #[serde(default)]
+        operation: FileOperation,
     },
 }
 
+impl ClipboardContent {
+    /// Short name of the content type ("text", "rich_text", "image" or "files")
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ClipboardContent::Text(_) => "text",
+            ClipboardContent::RichText { .. } => "rich_text",
+            ClipboardContent::Image { .. } => "image",
+            ClipboardContent::Files { .. } => "files",
+        }
+    }
+
+    /// Whether the main content already covers `mime_type`, so it is not stored
+    /// (or offered) a second time as another format
+    pub fn represents(&self, mime_type: &str) -> bool {
+        Self::type_represents(self.type_name(), mime_type)
+    }
+
+    /// `represents` for content of the given `type_name`, so that formats covered by
+    /// the main content are not even read from the clipboard
+    pub fn type_represents(type_name: &str, mime_type: &str) -> bool {
+        match type_name {
+            "text" => mime_type == TEXT_MIME,
+            "rich_text" => mime_type == TEXT_MIME || mime_type == HTML_MIME,
+            "image" => mime_type.starts_with("image/"),
+            "files" => [
+                TEXT_MIME,
+                copied_files::URI_LIST_MIME,
+                copied_files::GNOME_COPIED_FILES_MIME,
+                copied_files::KDE_CUT_SELECTION_MIME,
+            ]
+            .contains(&mime_type),
+            _ => false,
+        }
+    }
+}
+
+/// A representation of an item stored besides its main content (e.g. RTF or SVG)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClipboardFormat {
+    /// MIME type (or X11 target name) the source offered
+    pub mime_type: String,
+    /// Blob store key of the bytes
+    pub blob: String,
+    /// Size in bytes
+    pub size: usize,
+}
+
+/// The X11/Wayland selection an item was captured from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionKind {
+    /// The regular clipboard (Ctrl+C)
+    #[default]
+    Clipboard,
+    /// The PRIMARY selection (select text, middle-click to paste)
+    Primary,
+}
+
+impl SelectionKind {
+    fn is_clipboard(&self) -> bool {
+        *self == SelectionKind::Clipboard
+    }
+}
+
+/// Optional mirroring between CLIPBOARD and PRIMARY
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelectionSync {
+    #[default]
+    Off,
+    ClipboardToPrimary,
+    PrimaryToClipboard,
+}
+
+impl SelectionSync {
+    /// Parses the `selection_sync` user setting ("off", "clipboard_to_primary", "primary_to_clipboard")
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "clipboard_to_primary" => SelectionSync::ClipboardToPrimary,
+            "primary_to_clipboard" => SelectionSync::PrimaryToClipboard,
+            _ => SelectionSync::Off,
+        }
+    }
+}
+
+/// What is deleted when the session locks or the machine suspends or shuts down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LockClearAction {
+    #[default]
+    Off,
+    /// Secrets flagged by a password manager
+    Sensitive,
+    /// Everything but the pinned items
+    Unpinned,
+    /// The whole history, pinned items included
+    All,
+}
+
+impl LockClearAction {
+    /// Parses the `clear_on_lock` user setting ("off", "sensitive", "unpinned", "all")
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "sensitive" => LockClearAction::Sensitive,
+            "unpinned" => LockClearAction::Unpinned,
+            "all" => LockClearAction::All,
+            _ => LockClearAction::Off,
+        }
+    }
+}
+
+/// Which item is put back on the empty clipboard when the app starts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StartupRestore {
+    #[default]
+    Off,
+    /// The newest copy
+    Latest,
+    /// The newest pinned item
+    Pinned,
+}
+
+impl StartupRestore {
+    /// Parses the `restore_on_startup` user setting ("off", "latest", "pinned")
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "latest" => StartupRestore::Latest,
+            "pinned" => StartupRestore::Pinned,
+            _ => StartupRestore::Off,
+        }
+    }
+}
+
+/// Whether new copies are recorded. Pausing keeps secrets out of history,
+/// e.g. during a screen share.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum CaptureState {
+    #[default]
+    Active,
+    /// Copies are ignored until `until`, or until resumed when there is none
+    Paused { until: Option<DateTime<Utc>> },
+}
+
+impl CaptureState {
+    pub fn is_paused(&self) -> bool {
+        matches!(self, CaptureState::Paused { .. })
+    }
+}
+
 /// A single clipboard history item
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipboardItem {
@@ -89,10 +300,183 @@ pub struct ClipboardItem {
     pub timestamp: DateTime<Utc>,
     /// Whether this item is pinned
     pub pinned: bool,
+    /// Marked by the user as a secret (taken off the clipboard again after a paste)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive: bool,
     /// Preview text (for display)
     pub preview: String,
+    /// When set, the item is a secret kept in memory only and dropped at this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Application the content was copied from (window class, else process name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_app: Option<String>,
+    /// Title of the source window at copy time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_window_title: Option<String>,
+    /// Process id of the source application
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_pid: Option<u32>,
+    /// Selection the item was captured from
+    #[serde(default, skip_serializing_if = "SelectionKind::is_clipboard")]
+    pub selection: SelectionKind,
+    /// Every other representation the source offered, re-offered on paste
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub formats: Vec<ClipboardFormat>,
+}
+
+/// Lightweight view of an item for the history list.
+/// Full content (HTML, complete text, image data) is loaded by id when needed.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ClipboardItemSummary {
+    pub id: String,
+    /// Content type, see `ClipboardContent::type_name`
+    pub kind: &'static str,
+    pub timestamp: DateTime<Utc>,
+    pub pinned: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive: bool,
+    pub preview: String,
+    /// Start of the plain text (or the paths of copied files)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Whether `text` was cut short
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Image dimensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Number of copied files and whether they were cut
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<FileOperation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_app: Option<String>,
+    #[serde(skip_serializing_if = "SelectionKind::is_clipboard")]
+    pub selection: SelectionKind,
+}
+
+/// One page of the history, in display order
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPage {
+    pub items: Vec<ClipboardItemSummary>,
+    /// Pass to `get_history_page` for the next page; None on the last page
+    pub next_cursor: Option<String>,
+    /// Number of items across all pages
+    pub total: usize,
+}
+
+/// The page of `items` after the one with id `cursor` (from the start if None)
+fn history_page(
+    items: &[&ClipboardItem],
+    cursor: Option<&str>,
+    limit: usize,
+) -> Result<HistoryPage, String> {
+    let start = match cursor {
+        None => 0,
+        Some(id) => {
+            items
+                .iter()
+                .position(|item| item.id == id)
+                .ok_or_else(|| format!("Cursor '{}' is no longer in the history", id))?
+                + 1
+        }
+    };
+    let end = (start + limit.clamp(1, MAX_PAGE_SIZE)).min(items.len());
+    let page = &items[start.min(end)..end];
+    Ok(HistoryPage {
+        items: page.iter().map(|item| item.summary()).collect(),
+        next_cursor: page
+            .last()
+            .filter(|_| end < items.len())
+            .map(|item| item.id.clone()),
+        total: items.len(),
+    })
+}
+
+/// Storage limits across the history in bytes (0 = no limit)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistoryBudget {
+    pub max_bytes: u64,
+    /// Limit for text, rich text and file items together
+    pub max_text_bytes: u64,
+    pub max_image_bytes: u64,
+}
+
+impl HistoryBudget {
+    fn is_unlimited(&self) -> bool {
+        self.max_bytes == 0 && self.max_text_bytes == 0 && self.max_image_bytes == 0
+    }
+}
+
+/// Number and storage size of items
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct ItemStats {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+impl ItemStats {
+    fn add(&mut self, bytes: u64) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// History size overall and per content type (see `ClipboardContent::type_name`)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HistoryStats {
+    pub total: ItemStats,
+    pub pinned: ItemStats,
+    pub by_type: BTreeMap<&'static str, ItemStats>,
+}
+
+/// A change to the history, forwarded to the frontend as an event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum HistoryEvent {
+    /// A new item, inserted at `index`
+    Added {
+        item: ClipboardItemSummary,
+        index: usize,
+    },
+    /// An existing item changed and now sits at `index`
+    Updated {
+        item: ClipboardItemSummary,
+        index: usize,
+    },
+    /// An item was deleted or replaced by a newer copy
+    Removed { id: String },
+    /// Items dropped by the size limit or expiry
+    Trimmed { ids: Vec<String> },
+    /// Everything but the pinned items was removed
+    Cleared,
+    /// Capture was paused or resumed
+    CaptureChanged(CaptureState),
+}
+
+impl HistoryEvent {
+    /// Name of the frontend event
+    pub fn name(&self) -> &'static str {
+        match self {
+            HistoryEvent::Added { .. } => "item-added",
+            HistoryEvent::Updated { .. } => "item-updated",
+            HistoryEvent::Removed { .. } => "item-removed",
+            HistoryEvent::Trimmed { .. } => "items-trimmed",
+            HistoryEvent::Cleared => "history-cleared",
+            HistoryEvent::CaptureChanged(_) => "capture-state-changed",
+        }
+    }
 }
 
+/// Receives history changes (set by the app to emit frontend events)
+pub type HistoryEventSink = Box<dyn Fn(HistoryEvent) + Send>;
+
 impl ClipboardItem {
     pub fn new_text(text: String) -> Self {
         let preview = if text.chars().count() > PREVIEW_TEXT_MAX_LEN {
@@ -120,16 +504,34 @@ impl ClipboardItem {
         Self::create(ClipboardContent::RichText { plain, html }, preview)
     }
 
-    pub fn new_image(base64: String, width: u32, height: u32, hash: u64) -> Self {
-        // We store the hash in the preview string to persist it across sessions
-        // without breaking the serialization schema of existing data.
-        let preview = format!("Image ({}x{}) #{}", width, height, hash);
+    pub fn new_image(
+        blob: String,
+        width: u32,
+        height: u32,
+        hash: u64,
+        thumbnail: Option<String>,
+    ) -> Self {
+        let preview = format!("Image ({}x{})", width, height);
 
         Self::create(
             ClipboardContent::Image {
-                base64,
+                blob,
                 width,
                 height,
+                hash: Some(hash),
+                thumbnail,
+                legacy_base64: None,
+            },
+            preview,
+        )
+    }
+
+    pub fn new_files(files: CopiedFiles) -> Self {
+        let preview = files.preview();
+        Self::create(
+            ClipboardContent::Files {
+                uris: files.uris,
+                operation: files.operation,
             },
             preview,
         )
@@ -141,21 +543,272 @@ impl ClipboardItem {
             content,
             timestamp: Utc::now(),
             pinned: false,
+            sensitive: false,
             preview,
+            expires_at: None,
+            source_app: None,
+            source_window_title: None,
+            source_pid: None,
+            selection: SelectionKind::Clipboard,
+            formats: Vec::new(),
         }
     }
 
-    /// Attempts to extract the image hash from the preview string.
-    /// Returns None if content is not an image or hash is missing.
-    pub fn extract_image_hash(&self) -> Option<u64> {
-        if !matches!(self.content, ClipboardContent::Image { .. }) {
-            return None;
+    /// Records the window the content was copied from.
+    pub fn set_source(&mut self, source: &ActiveWindow) {
+        self.source_app = source.app_class.clone().or_else(|| source.process_name());
+        self.source_window_title = source.title.clone();
+        self.source_pid = source.pid;
+    }
+
+    /// Whether the item is a secret: flagged by a password manager or marked by the user
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive || self.expires_at.is_some()
+    }
+
+    /// Whether the item was copied from `app` (see `active_window::app_matches`)
+    pub fn is_from_app(&self, app: &str) -> bool {
+        self.source_app
+            .as_deref()
+            .is_some_and(|source| active_window::app_matches(source, app))
+    }
+
+    /// Returns the pixel hash of an image item, if it was recorded.
+    pub fn image_hash(&self) -> Option<u64> {
+        match &self.content {
+            ClipboardContent::Image { hash, .. } => *hash,
+            _ => None,
+        }
+    }
+
+    /// Moves the inline base64 image of an item written before blob storage into
+    /// `blobs`. Must happen before the item is stored, as the inline image is never
+    /// written back. Returns false when the image is unreadable and the item is useless.
+    pub fn move_inline_image(&mut self, blobs: &BlobStore) -> Result<bool, String> {
+        let ClipboardContent::Image {
+            blob,
+            legacy_base64,
+            ..
+        } = &mut self.content
+        else {
+            return Ok(true);
+        };
+        let Some(base64) = legacy_base64.take() else {
+            return Ok(!blob.is_empty());
+        };
+
+        let png = match BASE64.decode(&base64) {
+            Ok(png) => png,
+            Err(e) => {
+                eprintln!("[ClipboardManager] Dropping unreadable legacy image: {}", e);
+                return Ok(false);
+            }
+        };
+        *blob = blobs
+            .put(&png)
+            .map_err(|e| format!("Failed to migrate image: {}", e))?;
+        Ok(true)
+    }
+
+    /// Returns the copied files of a file item.
+    pub fn copied_files(&self) -> Option<CopiedFiles> {
+        match &self.content {
+            ClipboardContent::Files { uris, operation } => Some(CopiedFiles {
+                uris: uris.clone(),
+                operation: *operation,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the blob key of an image item.
+    pub fn blob_key(&self) -> Option<&str> {
+        match &self.content {
+            ClipboardContent::Image { blob, .. } if !blob.is_empty() => Some(blob),
+            _ => None,
+        }
+    }
+
+    /// Returns the thumbnail blob key of an image item, if one was made.
+    pub fn thumbnail_key(&self) -> Option<&str> {
+        match &self.content {
+            ClipboardContent::Image {
+                thumbnail: Some(thumbnail),
+                ..
+            } => Some(thumbnail),
+            _ => None,
+        }
+    }
+
+    /// Lightweight view for the history list
+    pub fn summary(&self) -> ClipboardItemSummary {
+        let text = match &self.content {
+            ClipboardContent::Text(text) => Some(Cow::Borrowed(text.as_str())),
+            ClipboardContent::RichText { plain, .. } => Some(Cow::Borrowed(plain.as_str())),
+            ClipboardContent::Files { uris, .. } => Some(Cow::Owned(
+                uris.iter()
+                    .map(|uri| copied_files::display_path(uri))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+            ClipboardContent::Image { .. } => None,
+        };
+        let truncated = text
+            .as_ref()
+            .is_some_and(|text| text.chars().count() > SUMMARY_TEXT_MAX_LEN);
+        let (width, height) = match &self.content {
+            ClipboardContent::Image { width, height, .. } => (Some(*width), Some(*height)),
+            _ => (None, None),
+        };
+        let files = self.copied_files();
+
+        ClipboardItemSummary {
+            id: self.id.clone(),
+            kind: self.content.type_name(),
+            timestamp: self.timestamp,
+            pinned: self.pinned,
+            sensitive: self.sensitive,
+            preview: self.preview.clone(),
+            text: text.map(|text| text.chars().take(SUMMARY_TEXT_MAX_LEN).collect()),
+            truncated,
+            width,
+            height,
+            file_count: files.as_ref().map(|files| files.uris.len()),
+            operation: files.map(|files| files.operation),
+            expires_at: self.expires_at,
+            source_app: self.source_app.clone(),
+            selection: self.selection,
+        }
+    }
+
+    /// Every blob the item references (image and stored formats)
+    pub fn blob_keys(&self) -> impl Iterator<Item = &str> {
+        self.blob_key()
+            .into_iter()
+            .chain(self.thumbnail_key())
+            .chain(self.formats.iter().map(|format| format.blob.as_str()))
+    }
+
+    /// Points the item at renamed blobs (old key -> new key)
+    pub fn rename_blobs(&mut self, renamed: &HashMap<String, String>) {
+        let rename = |key: &mut String| {
+            if let Some(new_key) = renamed.get(key.as_str()) {
+                key.clone_from(new_key);
+            }
+        };
+        if let ClipboardContent::Image {
+            blob, thumbnail, ..
+        } = &mut self.content
+        {
+            rename(blob);
+            thumbnail.iter_mut().for_each(rename);
+        }
+        self.formats
+            .iter_mut()
+            .for_each(|format| rename(&mut format.blob));
+    }
+}
+
+// --- Schema Versioning ---
+
+/// Upgrade steps for stored items: `MIGRATIONS[n]` turns a version `n + 1` item into
+/// a version `n + 2` item. Steps must be idempotent, since rows written by this build
+/// can sit next to older rows until the store has been fully upgraded.
+const MIGRATIONS: &[fn(&mut Value)] = &[migrate_v1_image_hash];
+
+/// Version of the item schema written by this build.
+/// Version 1 is the unversioned format (a bare JSON array of items).
+pub const HISTORY_SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Versioned on-disk envelope of a history file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryFile {
+    pub version: u32,
+    pub items: Vec<Value>,
+}
+
+impl HistoryFile {
+    /// Wraps items in the current schema version
+    pub fn from_items(items: &[ClipboardItem]) -> Result<Self, String> {
+        let items = items
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to serialize history: {}", e))?;
+        Ok(Self {
+            version: HISTORY_SCHEMA_VERSION,
+            items,
+        })
+    }
+
+    /// Parses a history file: a versioned envelope, or the bare item array of version 1
+    pub fn parse(json: &str) -> Result<Self, String> {
+        match serde_json::from_str(json).map_err(|e| format!("Failed to parse history: {}", e))? {
+            Value::Array(items) => Ok(Self { version: 1, items }),
+            value => {
+                serde_json::from_value(value).map_err(|e| format!("Failed to parse history: {}", e))
+            }
         }
-        self.preview
-            .split('#')
-            .nth(1)
-            .and_then(|h| h.parse::<u64>().ok())
     }
+
+    /// Upgrades the items to the current schema. Unreadable items are skipped.
+    pub fn into_items(self) -> Result<Vec<ClipboardItem>, String> {
+        check_schema_version(self.version)?;
+        Ok(self
+            .items
+            .into_iter()
+            .filter_map(|item| match migrate_item(item, self.version) {
+                Ok(item) => Some(item),
+                Err(e) => {
+                    eprintln!("[ClipboardManager] Skipping unreadable item: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+/// Fails for data written by a newer build, which this one cannot interpret safely
+pub fn check_schema_version(version: u32) -> Result<(), String> {
+    if version > HISTORY_SCHEMA_VERSION {
+        return Err(format!(
+            "History uses schema version {}, newer than the supported {}",
+            version, HISTORY_SCHEMA_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Upgrades one stored item from `version` to the current schema
+pub fn migrate_item(mut item: Value, version: u32) -> Result<ClipboardItem, String> {
+    check_schema_version(version)?;
+    let applied = version.max(1) as usize - 1;
+    for migration in &MIGRATIONS[applied..] {
+        migration(&mut item);
+    }
+    serde_json::from_value(item).map_err(|e| format!("Failed to parse item: {}", e))
+}
+
+/// v1 -> v2: the image hash moves from the preview ("Image (WxH) #hash") into the content
+fn migrate_v1_image_hash(item: &mut Value) {
+    if item.pointer("/content/type").and_then(Value::as_str) != Some("Image") {
+        return;
+    }
+    let Some((preview, hash)) = item
+        .get("preview")
+        .and_then(Value::as_str)
+        .and_then(|preview| preview.split_once(" #"))
+        .and_then(|(preview, hash)| Some((preview.to_string(), hash.parse::<u64>().ok()?)))
+    else {
+        return;
+    };
+    if let Some(data) = item
+        .pointer_mut("/content/data")
+        .and_then(Value::as_object_mut)
+    {
+        data.insert("hash".to_string(), hash.into());
+    }
+    item["preview"] = preview.into();
 }
 
 // --- Manager Logic ---
@@ -166,12 +819,59 @@ pub struct ClipboardManager {
     /// Track the last pasted content to avoid re-adding it to history
     last_pasted_text: Option<String>,
     last_pasted_image_hash: Option<u64>,
+    last_pasted_files_hash: Option<u64>,
     /// Track last added text hash to prevent duplicates from rapid copies
     last_added_text_hash: Option<u64>,
-    /// Path to save the history file
-    persistence_path: PathBuf,
+    /// Persistence backend (only changed items are written)
+    store: Box<dyn HistoryStore>,
+    /// Image payloads, shared between items with identical content
+    blobs: BlobStore,
+    /// Encryption settings the store and blobs are currently written with
+    encryption: EncryptionConfig,
+    /// Key the store and blobs are currently written with
+    cipher: Option<Arc<HistoryCipher>>,
+    /// Why the stored history could not be unlocked at startup, if it could not
+    encryption_error: Option<String>,
     /// Maximum number of history items to keep
     max_history_size: usize,
+    /// Lifetime of items flagged as secret by a password manager (0 = never store them)
+    concealed_expiry_secs: u64,
+    /// Applications (class, instance or process name) whose copies are ignored
+    excluded_apps: Vec<String>,
+    /// Whether PRIMARY selections are recorded in history
+    capture_primary: bool,
+    /// Mirroring between CLIPBOARD and PRIMARY
+    selection_sync: SelectionSync,
+    /// Size limits and encoding of copied images
+    image_policy: ImagePolicy,
+    /// Age limits and daily purge of unpinned items
+    retention: RetentionPolicy,
+    /// Storage limits applied together with `max_history_size`
+    budget: HistoryBudget,
+    /// Storage size of items by id, filled on demand (blob sizes need a file lookup)
+    size_cache: HashMap<String, u64>,
+    /// When retention was last enforced, to notice a passed daily purge time
+    last_retention_check: Option<DateTime<Local>>,
+    /// Whether copies are currently recorded
+    capture_state: CaptureState,
+    /// What `clear_for_lock` deletes
+    lock_clear_action: LockClearAction,
+    /// Which pasted items are taken off the clipboard again
+    sensitive_paste: SensitivePastePolicy,
+    /// Whether every paste puts the previous clipboard content back afterwards
+    transient_paste: bool,
+    /// Last paste, until it is taken off the clipboard
+    pending_revert: Option<PendingRevert>,
+    /// Whether the clipboard is taken over when its owner exits
+    persist_clipboard: bool,
+    /// Id of the item pasted last and when, as the clipboard holds it instead of the newest item
+    last_paste: Option<(String, DateTime<Utc>)>,
+    /// When a revert last emptied the clipboard
+    cleared_at: Option<DateTime<Utc>>,
+    /// Lowercased item text for `search_history`
+    search_cache: SearchCache,
+    /// Where history changes are reported
+    event_sink: Option<HistoryEventSink>,
 }
 
 impl ClipboardManager {
@@ -183,16 +883,128 @@ impl ClipboardManager {
         }
     }
 
-    pub fn new(persistence_path: PathBuf, max_history_size: usize) -> Self {
+    /// Creates a manager backed by the default on-disk store inside `data_dir`.
+    /// A legacy `history.json` found there is migrated automatically, and the
+    /// store is converted to or from encrypted form to match `encryption`.
+    pub fn new(data_dir: &Path, max_history_size: usize, encryption: &EncryptionConfig) -> Self {
+        let mut blobs = BlobStore::new(data_dir.join(IMAGE_BLOB_DIR));
+        match blobs.rename_legacy_files() {
+            Ok(0) => {}
+            Ok(count) => eprintln!("[ClipboardManager] Renamed {} legacy blobs", count),
+            Err(e) => eprintln!("[ClipboardManager] {}", e),
+        }
+        let mut store = match history_store::open_default_store(data_dir, &blobs) {
+            Ok(store) => store,
+            Err(e) => {
+                // Keep the app working, but leave the on-disk blobs alone
+                eprintln!(
+                    "[ClipboardManager] {}. History will not be persisted this session.",
+                    e
+                );
+                blobs = BlobStore::new(Self::volatile_blob_dir());
+                Box::new(MemoryHistoryStore::new())
+            }
+        };
+
+        let stored_encrypted = history_crypto::is_store_encrypted(store.as_mut()).unwrap_or(false);
+        let mut cipher = None;
+        let mut encryption_error = None;
+        // The key is needed to enable encryption, and also once to read an encrypted store back
+        if encryption.enabled || stored_encrypted {
+            match history_crypto::unlock_store(store.as_mut(), encryption) {
+                Ok(key) => cipher = Some(key),
+                Err(e) => {
+                    // Never fall back to writing plaintext next to (or instead of) encrypted data
+                    eprintln!(
+                        "[ClipboardManager] Cannot unlock encrypted history: {}. History will not be persisted this session.",
+                        e
+                    );
+                    encryption_error = Some(e);
+                    store = Box::new(MemoryHistoryStore::new());
+                    blobs = BlobStore::new(Self::volatile_blob_dir());
+                }
+            }
+        }
+
+        // Keep writing in the stored mode until the migration below has rewritten everything
+        let read_cipher = if stored_encrypted {
+            cipher.clone()
+        } else {
+            None
+        };
+        store.set_cipher(read_cipher.clone());
+        blobs.set_cipher(read_cipher.clone());
+
+        let mut manager = Self::with_store(store, blobs, max_history_size);
+        manager.encryption = EncryptionConfig {
+            enabled: read_cipher.is_some(),
+            passphrase_file: encryption.passphrase_file.clone(),
+        };
+        manager.cipher = read_cipher;
+        manager.encryption_error = encryption_error;
+
+        if let Some(key) = cipher.filter(|_| encryption.enabled != stored_encrypted) {
+            let target = if encryption.enabled { Some(key) } else { None };
+            if let Err(e) = manager.set_encryption(encryption, target) {
+                eprintln!(
+                    "[ClipboardManager] Failed to migrate history encryption: {}",
+                    e
+                );
+            }
+        }
+        if manager.encryption.enabled {
+            history_store::remove_legacy_backup(data_dir);
+        }
+        manager
+    }
+
+    /// Scratch directory for image blobs when history cannot be persisted
+    fn volatile_blob_dir() -> std::path::PathBuf {
+        dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("win11-clipboard-history")
+            .join(IMAGE_BLOB_DIR)
+    }
+
+    /// Creates a manager using the given storage backends.
+    pub fn with_store(
+        store: Box<dyn HistoryStore>,
+        blobs: BlobStore,
+        max_history_size: usize,
+    ) -> Self {
         // Normalize the requested max size and avoid huge allocations
         let max_size = Self::clamp_max_history_size(max_history_size);
         let mut manager = Self {
             history: Vec::with_capacity(max_size),
             last_pasted_text: None,
             last_pasted_image_hash: None,
+            last_pasted_files_hash: None,
             last_added_text_hash: None,
-            persistence_path,
+            store,
+            blobs,
+            encryption: EncryptionConfig::default(),
+            cipher: None,
+            encryption_error: None,
             max_history_size: max_size,
+            concealed_expiry_secs: 0,
+            excluded_apps: Vec::new(),
+            capture_primary: false,
+            selection_sync: SelectionSync::Off,
+            image_policy: ImagePolicy::default(),
+            retention: RetentionPolicy::default(),
+            budget: HistoryBudget::default(),
+            size_cache: HashMap::new(),
+            last_retention_check: None,
+            capture_state: CaptureState::Active,
+            lock_clear_action: LockClearAction::Off,
+            sensitive_paste: SensitivePastePolicy::default(),
+            transient_paste: false,
+            pending_revert: None,
+            persist_clipboard: false,
+            last_paste: None,
+            cleared_at: None,
+            search_cache: SearchCache::default(),
+            event_sink: None,
         };
         manager.load_history();
         manager
@@ -213,9 +1025,8 @@ impl ClipboardManager {
         }
         self.max_history_size = clamped;
         let trimmed = self.enforce_history_limit();
-        if trimmed {
-            self.save_history();
-        }
+        self.persist_removals(&trimmed);
+        self.emit_trimmed(&trimmed);
     }
 
     /// Gets the current maximum history size
@@ -223,213 +1034,564 @@ impl ClipboardManager {
         self.max_history_size
     }
 
-    fn load_history(&mut self) {
-        if !self.persistence_path.exists() {
-            return;
-        }
+    /// Sets how long secrets (password-manager copies) are kept; 0 drops them entirely
+    pub fn set_concealed_expiry(&mut self, secs: u64) {
+        self.concealed_expiry_secs = secs;
+    }
 
-        match fs::read_to_string(&self.persistence_path) {
-            Ok(content) => {
-                match serde_json::from_str::<Vec<ClipboardItem>>(&content) {
-                    Ok(items) => {
-                        // Reorder items so pinned come first while preserving order within each group
-                        let mut pinned_items = Vec::new();
-                        let mut unpinned_items = Vec::new();
-
-                        for item in items {
-                            if item.pinned {
-                                pinned_items.push(item);
-                            } else {
-                                unpinned_items.push(item);
-                            }
-                        }
-
-                        pinned_items.extend(unpinned_items);
-                        self.history = pinned_items;
-                        // Ensure loaded history respects configured limit immediately
-                        let history_trimmed = self.enforce_history_limit();
-                        // If the loaded history was trimmed, persist it so disk stays in sync.
-                        // Avoid saving when nothing changed.
-                        if history_trimmed {
-                            self.save_history();
-                        }
-                        // Initialize last_added_text_hash from the most recent item (even if pinned)
-                        // This prevents duplication on startup if the clipboard content matches the top item
-                        if let Some(first) = self.history.first() {
-                            match &first.content {
-                                ClipboardContent::Text(text) => {
-                                    self.last_added_text_hash = Some(calculate_hash(text));
-                                }
-                                ClipboardContent::RichText { plain, .. } => {
-                                    self.last_added_text_hash = Some(calculate_hash(plain));
-                                }
-                                ClipboardContent::Image { .. } => {
-                                    if let Some(_hash) = first.extract_image_hash() {
-                                        // We don't have a separate last_added_image_hash,
-                                        // but we can at least avoid text hash collision
-                                        self.last_added_text_hash = None;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => eprintln!("Failed to parse history: {}", e),
-                }
-            }
-            Err(e) => eprintln!("Failed to read history file: {}", e),
-        }
+    pub fn set_lock_clear_action(&mut self, action: LockClearAction) {
+        self.lock_clear_action = action;
     }
 
-    fn save_history(&self) {
-        match serde_json::to_string_pretty(&self.history) {
-            Ok(content) => {
-                if let Some(parent) = self.persistence_path.parent() {
-                    let _ = fs::create_dir_all(parent);
-                }
-                if let Err(e) = fs::write(&self.persistence_path, content) {
-                    eprintln!("Failed to save history: {}", e);
-                }
-            }
-            Err(e) => eprintln!("Failed to serialize history: {}", e),
-        }
+    pub fn set_sensitive_paste(&mut self, policy: SensitivePastePolicy) {
+        self.sensitive_paste = policy;
     }
 
-    // --- Monitoring / Reading ---
+    pub fn set_transient_paste(&mut self, enabled: bool) {
+        self.transient_paste = enabled;
+    }
 
-    pub fn get_current_text(&mut self) -> Result<String, arboard::Error> {
-        // We unwrap internal map error because arboard::Error is the expected return type here
-        // for the monitoring loop in main.rs
-        Clipboard::new()?.get_text()
+    pub fn set_persist_clipboard(&mut self, enabled: bool) {
+        self.persist_clipboard = enabled;
     }
 
-    /// Try to get HTML content from clipboard. Returns None if not available.
-    pub fn get_current_html(&self) -> Option<String> {
-        let mut clipboard = get_system_clipboard().ok()?;
-        clipboard.get().html().ok()
+    pub fn set_excluded_apps(&mut self, apps: Vec<String>) {
+        self.excluded_apps = apps;
     }
 
-    pub fn get_current_image(
-        &mut self,
-    ) -> Result<Option<(ImageData<'static>, u64)>, arboard::Error> {
-        let mut clipboard = Clipboard::new()?;
-
-        match clipboard.get_image() {
-            Ok(image) => {
-                let hash = calculate_hash(&image.bytes);
-                let owned = ImageData {
-                    width: image.width,
-                    height: image.height,
-                    bytes: image.bytes.into_owned().into(),
-                };
-                Ok(Some((owned, hash)))
-            }
-            Err(arboard::Error::ContentNotAvailable) => Ok(None),
-            Err(e) => Err(e),
-        }
+    pub fn image_policy(&self) -> ImagePolicy {
+        self.image_policy
     }
 
-    // --- Adding Items ---
+    pub fn set_image_policy(&mut self, policy: ImagePolicy) {
+        self.image_policy = policy;
+    }
 
-    /// Add text content to history, with optional HTML for rich text
-    pub fn add_text(&mut self, text: String, html: Option<String>) -> Option<ClipboardItem> {
-        if self.should_skip_text(&text) {
-            return None;
-        }
+    /// Sets the storage limits, evicting the oldest unpinned items that no longer fit
+    pub fn set_budget(&mut self, budget: HistoryBudget) {
+        self.budget = budget;
+        let trimmed = self.enforce_history_limit();
+        self.persist_removals(&trimmed);
+        self.emit_trimmed(&trimmed);
+    }
 
-        let text_hash = calculate_hash(&text);
+    /// Sets the retention rules; items already past a new age limit are removed right away
+    pub fn set_retention(&mut self, retention: RetentionPolicy) {
+        self.retention = retention;
+        self.remove_aged(Utc::now());
+    }
 
-        // Rapid copy detection
-        if Some(text_hash) == self.last_added_text_hash {
-            return None;
-        }
+    /// Reports every later history change to `sink`
+    pub fn set_event_sink(&mut self, sink: HistoryEventSink) {
+        self.event_sink = Some(sink);
+    }
 
-        // Check if this exact text is already the most recent non-pinned item
-        // If so, skip entirely - no need to add or move
-        if self.is_duplicate_text(&text) {
-            self.last_added_text_hash = Some(text_hash);
-            return None;
+    fn emit(&self, event: HistoryEvent) {
+        if let Some(sink) = &self.event_sink {
+            sink(event);
         }
-
-        // Check if this text exists elsewhere in history (not at top)
-        // If so, remove the old entry so we can add fresh at top
-        self.remove_duplicate_text_from_history(&text);
-
-        // Create new item - use RichText if HTML is available, otherwise plain Text
-        let item = match html {
-            Some(html_content) if !html_content.trim().is_empty() => {
-                ClipboardItem::new_rich_text(text, html_content)
-            }
-            _ => ClipboardItem::new_text(text),
-        };
-        self.insert_item(item.clone());
-
-        self.last_added_text_hash = Some(text_hash);
-
-        Some(item)
     }
 
-    pub fn add_image(&mut self, image_data: ImageData<'_>, hash: u64) -> Option<ClipboardItem> {
-        if self.should_skip_image(hash) {
-            return None;
+    fn emit_trimmed(&self, removed: &[ClipboardItem]) {
+        if !removed.is_empty() {
+            self.emit(HistoryEvent::Trimmed {
+                ids: removed.iter().map(|item| item.id.clone()).collect(),
+            });
         }
+    }
 
-        let base64_image = self.convert_image_to_base64(&image_data)?;
+    pub fn set_primary_selection(&mut self, capture: bool, sync: SelectionSync) {
+        self.capture_primary = capture;
+        self.selection_sync = sync;
+    }
 
-        let item = ClipboardItem::new_image(
-            base64_image,
-            image_data.width as u32,
-            image_data.height as u32,
-            hash,
-        );
+    pub fn captures_primary(&self) -> bool {
+        self.capture_primary
+    }
 
-        self.insert_item(item.clone());
-        Some(item)
+    pub fn selection_sync(&self) -> SelectionSync {
+        self.selection_sync
     }
 
-    // --- State Management Helpers ---
+    /// Whether PRIMARY changes need to be watched at all
+    pub fn watches_primary(&self) -> bool {
+        self.capture_primary || self.selection_sync == SelectionSync::PrimaryToClipboard
+    }
 
-    fn should_skip_text(&mut self, text: &str) -> bool {
-        if text.trim().is_empty() {
-            return true;
-        }
+    pub fn capture_state(&self) -> CaptureState {
+        self.capture_state
+    }
 
-        // Skip internal GIF cache URIs
-        if text.contains(FILE_URI_PREFIX) && text.contains(GIF_CACHE_MARKER) {
-            eprintln!("[ClipboardManager] Skipping GIF cache URI");
-            return true;
-        }
+    pub fn is_capture_paused(&self) -> bool {
+        self.capture_state.is_paused()
+    }
 
-        // Skip self-pasted content
-        if let Some(ref pasted) = self.last_pasted_text {
-            if pasted == text || text.contains(pasted) {
-                // Clear the lock so future copies allow this text
-                self.last_pasted_text = None;
-                return true;
-            }
-        }
+    /// Stops recording copies, for `duration` or until `resume_capture` when `None`
+    pub fn pause_capture(&mut self, duration: Option<chrono::Duration>) {
+        let until = duration.map(|duration| Utc::now() + duration);
+        self.set_capture_state(CaptureState::Paused { until });
+    }
 
-        false
+    pub fn resume_capture(&mut self) {
+        self.set_capture_state(CaptureState::Active);
     }
 
-    fn should_skip_image(&mut self, hash: u64) -> bool {
-        // Check if just pasted
-        if let Some(pasted_hash) = self.last_pasted_image_hash {
-            if pasted_hash == hash {
-                self.last_pasted_image_hash = None;
-                return true;
+    /// Ends a timed pause once its time is up (checked periodically by the clipboard monitor)
+    pub fn resume_capture_if_due(&mut self, now: DateTime<Utc>) {
+        if let CaptureState::Paused { until: Some(until) } = self.capture_state {
+            if until <= now {
+                self.resume_capture();
             }
         }
+    }
 
-        // Check if it's the exact same image as the most recent non-pinned item
-        if let Some(item) = self.history.iter().find(|item| !item.pinned) {
-            if let Some(item_hash) = item.extract_image_hash() {
-                if item_hash == hash {
-                    return true;
-                }
+    fn set_capture_state(&mut self, state: CaptureState) {
+        if state == self.capture_state {
+            return;
+        }
+        match state {
+            CaptureState::Active => eprintln!("[ClipboardManager] Capture resumed"),
+            CaptureState::Paused { until: Some(until) } => eprintln!(
+                "[ClipboardManager] Capture paused until {}",
+                until.with_timezone(&Local).format("%H:%M")
+            ),
+            CaptureState::Paused { until: None } => {
+                eprintln!("[ClipboardManager] Capture paused")
             }
         }
-
-        false
+        self.capture_state = state;
+        self.emit(HistoryEvent::CaptureChanged(state));
+    }
+
+    /// Whether content copied from `source` must not be recorded
+    pub fn is_excluded_source(&self, source: Option<&ActiveWindow>) -> bool {
+        let Some(source) = source else {
+            return false;
+        };
+        let excluded = source.matches_any(&self.excluded_apps);
+        if excluded {
+            eprintln!(
+                "[ClipboardManager] Ignoring copy from excluded app {:?}",
+                source.app_class
+            );
+        }
+        excluded
+    }
+
+    /// Applies changed encryption settings, migrating the stored history if needed.
+    /// A new key source (keyring or another passphrase file) re-encrypts everything.
+    pub fn configure_encryption(&mut self, config: &EncryptionConfig) -> Result<(), String> {
+        if self.encryption_error.is_some() {
+            // The stored history is not open this session; the settings apply on the next start
+            return Ok(());
+        }
+        let current = if self.encryption.enabled {
+            Some(&self.encryption)
+        } else {
+            None
+        };
+        let wanted = if config.enabled { Some(config) } else { None };
+        if current == wanted {
+            return Ok(());
+        }
+        let target = if config.enabled {
+            Some(history_crypto::store_key(self.store.as_mut(), config)?)
+        } else {
+            None
+        };
+        self.set_encryption(config, target)
+    }
+
+    /// Why the stored history could not be unlocked at startup; history is only kept in
+    /// memory for this session then
+    pub fn encryption_error(&self) -> Option<&str> {
+        self.encryption_error.as_deref()
+    }
+
+    /// Rewrites every stored item and image with the given cipher (`None` = plaintext),
+    /// obtained for `config`. Images are re-encoded (and renamed, as blob names depend on
+    /// the key) next to the originals, which are only removed once the store has been
+    /// rewritten, so a failure leaves the old state.
+    fn set_encryption(
+        &mut self,
+        config: &EncryptionConfig,
+        cipher: Option<Arc<HistoryCipher>>,
+    ) -> Result<(), String> {
+        let keys: HashSet<&str> = self.history.iter().flat_map(|i| i.blob_keys()).collect();
+        let staged = self.blobs.stage_rewrite(keys, cipher.as_deref())?;
+        let previous = self.history.clone();
+        for item in &mut self.history {
+            item.rename_blobs(staged.renamed());
+        }
+
+        if let Err(e) = self.write_store_encrypted(cipher.clone()) {
+            staged.discard();
+            self.history = previous;
+            // Put the rows back as they were; the history is still in memory
+            if let Err(e) = self.write_store_encrypted(self.cipher.clone()) {
+                eprintln!("[ClipboardManager] Failed to restore history: {}", e);
+            }
+            return Err(e);
+        }
+        staged.commit()?;
+        self.blobs.set_cipher(cipher.clone());
+        // Blob sizes include the encryption overhead
+        self.size_cache.clear();
+        // Drop stale copies of the old rows from free pages and the WAL
+        self.store.compact()?;
+
+        self.encryption = config.clone();
+        self.encryption.enabled = cipher.is_some();
+        self.cipher = cipher;
+        eprintln!(
+            "[ClipboardManager] History is now stored {}",
+            if self.encryption.enabled {
+                "encrypted"
+            } else {
+                "in plaintext"
+            }
+        );
+        Ok(())
+    }
+
+    /// Rewrites every stored item with `cipher` and records the store's mode
+    fn write_store_encrypted(&mut self, cipher: Option<Arc<HistoryCipher>>) -> Result<(), String> {
+        self.store.set_cipher(cipher.clone());
+        self.store.replace_all(&self.persistable_history())?;
+        history_crypto::record_store_mode(self.store.as_mut(), cipher.as_deref())
+    }
+
+    fn load_history(&mut self) {
+        let items = match self.store.load() {
+            Ok(items) => items,
+            Err(e) => {
+                eprintln!("Failed to load history: {}", e);
+                return;
+            }
+        };
+
+        // Reorder items so pinned come first while preserving order within each group
+        let (mut pinned_items, unpinned_items): (Vec<_>, Vec<_>) =
+            items.into_iter().partition(|item| item.pinned);
+
+        pinned_items.extend(unpinned_items);
+        self.history = pinned_items;
+        // Ensure loaded history respects configured limit immediately.
+        // Only the trimmed rows are deleted from the store.
+        let trimmed = self.enforce_history_limit();
+        self.persist_removals(&trimmed);
+        // Drop blobs left behind by a crash between a delete and its cleanup.
+        // Skipped rows or a volatile store would make live blobs look orphaned.
+        if self.store.loaded_completely() {
+            let referenced: HashSet<&str> =
+                self.history.iter().flat_map(|i| i.blob_keys()).collect();
+            let orphans = self.blobs.retain(&referenced);
+            if orphans > 0 {
+                eprintln!(
+                    "[ClipboardManager] Removed {} orphaned image blobs",
+                    orphans
+                );
+            }
+        }
+        // Initialize last_added_text_hash from the most recent item (even if pinned)
+        // This prevents duplication on startup if the clipboard content matches the top item
+        if let Some(first) = self.history.first() {
+            match &first.content {
+                ClipboardContent::Text(text) => {
+                    self.last_added_text_hash = Some(calculate_hash(text));
+                }
+                ClipboardContent::RichText { plain, .. } => {
+                    self.last_added_text_hash = Some(calculate_hash(plain));
+                }
+                ClipboardContent::Image { .. } => {
+                    // We don't have a separate last_added_image_hash,
+                    // but we can at least avoid text hash collision
+                    self.last_added_text_hash = None;
+                }
+                ClipboardContent::Files { .. } => {}
+            }
+        }
+    }
+
+    /// Writes a batch of changes to the store, logging (but not propagating) failures
+    /// so that the in-memory history keeps working even if the disk is unavailable.
+    /// Upserts of expiring (secret) items are dropped here so they never reach the disk.
+    fn persist(&mut self, changes: &[StoreChange<'_>]) {
+        let changes: Vec<StoreChange<'_>> = changes
+            .iter()
+            .filter(
+                |change| !matches!(change, StoreChange::Upsert(item) if item.expires_at.is_some()),
+            )
+            .copied()
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        if let Err(e) = self.store.commit(&changes) {
+            eprintln!("Failed to save history: {}", e);
+        }
+    }
+
+    fn persist_removals(&mut self, removed: &[ClipboardItem]) {
+        let changes: Vec<StoreChange<'_>> = removed
+            .iter()
+            .map(|item| StoreChange::Remove(&item.id))
+            .collect();
+        self.persist(&changes);
+        self.release_blobs(removed);
+    }
+
+    /// History without the memory-only (expiring) items
+    fn persistable_history(&self) -> Vec<ClipboardItem> {
+        self.history
+            .iter()
+            .filter(|item| item.expires_at.is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// Deletes the blobs of removed items that no remaining item points at.
+    /// Must run after the removal was committed so the store never references a missing blob.
+    fn release_blobs(&self, removed: &[ClipboardItem]) {
+        for key in removed.iter().flat_map(|item| item.blob_keys()) {
+            if self
+                .history
+                .iter()
+                .any(|item| item.blob_keys().any(|k| k == key))
+            {
+                continue;
+            }
+            if let Err(e) = self.blobs.remove(key) {
+                eprintln!("[ClipboardManager] {}", e);
+            }
+        }
+    }
+
+    // --- Adding Items ---
+
+    /// Add text content to history, with optional HTML for rich text.
+    /// `concealed` marks text the source flagged as a secret (password-manager hint).
+    pub fn add_text(
+        &mut self,
+        text: String,
+        html: Option<String>,
+        concealed: bool,
+        source: Option<&ActiveWindow>,
+    ) -> Option<ClipboardItem> {
+        self.add_text_from(SelectionKind::Clipboard, text, html, concealed, source)
+    }
+
+    /// Add text taken from the PRIMARY selection, tagged as such
+    pub fn add_primary_text(
+        &mut self,
+        text: String,
+        source: Option<&ActiveWindow>,
+    ) -> Option<ClipboardItem> {
+        self.add_text_from(SelectionKind::Primary, text, None, false, source)
+    }
+
+    fn add_text_from(
+        &mut self,
+        selection: SelectionKind,
+        text: String,
+        html: Option<String>,
+        concealed: bool,
+        source: Option<&ActiveWindow>,
+    ) -> Option<ClipboardItem> {
+        if self.should_skip_text(&text, concealed) {
+            return None;
+        }
+
+        let text_hash = calculate_hash(&text);
+
+        // Rapid copy detection
+        if Some(text_hash) == self.last_added_text_hash {
+            return None;
+        }
+
+        // Check if this exact text is already the most recent non-pinned item
+        // If so, skip entirely - no need to add or move
+        if self.is_duplicate_text(&text) {
+            self.last_added_text_hash = Some(text_hash);
+            return None;
+        }
+
+        // Check if this text exists elsewhere in history (not at top)
+        // If so, remove the old entry so we can add fresh at top
+        let duplicate = self.remove_duplicate_text_from_history(&text);
+
+        // Create new item - use RichText if HTML is available, otherwise plain Text
+        let mut item = match html {
+            Some(html_content) if !html_content.trim().is_empty() => {
+                ClipboardItem::new_rich_text(text, html_content)
+            }
+            _ => ClipboardItem::new_text(text),
+        };
+        item.selection = selection;
+        if let Some(source) = source {
+            item.set_source(source);
+        }
+        if concealed {
+            item.expires_at =
+                Some(item.timestamp + chrono::Duration::seconds(self.concealed_expiry_secs as i64));
+        }
+        self.insert_item(item.clone(), duplicate);
+
+        self.last_added_text_hash = Some(text_hash);
+
+        Some(item)
+    }
+
+    /// Add an image encoded by `image_policy::encode_image`.
+    /// Callers check `should_skip_image` before spending time on the encoding.
+    pub fn add_image(
+        &mut self,
+        image: EncodedImage,
+        source: Option<&ActiveWindow>,
+    ) -> Option<ClipboardItem> {
+        let key = match self.blobs.put(&image.bytes) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("[ClipboardManager] Failed to store image: {}", e);
+                return None;
+            }
+        };
+        let thumbnail = image.thumbnail.and_then(|png| self.store_thumbnail(&png));
+
+        let mut item =
+            ClipboardItem::new_image(key, image.width, image.height, image.hash, thumbnail);
+        if let Some(source) = source {
+            item.set_source(source);
+        }
+
+        self.insert_item(item.clone(), None);
+        Some(item)
+    }
+
+    /// Add files copied in a file manager. Copying the same files again moves them to the top.
+    pub fn add_files(
+        &mut self,
+        files: CopiedFiles,
+        source: Option<&ActiveWindow>,
+    ) -> Option<ClipboardItem> {
+        // Pasted GIFs are offered as files from our own cache
+        if files.uris.iter().all(|uri| uri.contains(GIF_CACHE_MARKER)) {
+            eprintln!("[ClipboardManager] Skipping GIF cache URI");
+            return None;
+        }
+
+        let hash = calculate_hash(&files);
+        if self.last_pasted_files_hash == Some(hash) {
+            self.last_pasted_files_hash = None;
+            return None;
+        }
+
+        let is_same = |item: &ClipboardItem| item.copied_files().as_ref() == Some(&files);
+        if self
+            .history
+            .iter()
+            .find(|item| !item.pinned)
+            .is_some_and(is_same)
+        {
+            return None;
+        }
+        let duplicate = self
+            .history
+            .iter()
+            .position(|item| !item.pinned && is_same(item))
+            .map(|pos| self.history.remove(pos));
+
+        let mut item = ClipboardItem::new_files(files);
+        if let Some(source) = source {
+            item.set_source(source);
+        }
+        self.insert_item(item.clone(), duplicate);
+        Some(item)
+    }
+
+    /// Stores the other representations offered with a recorded item, so that
+    /// pasting it offers all of them again. Returns the updated item.
+    pub fn attach_formats(
+        &mut self,
+        id: &str,
+        formats: Vec<SelectionTarget>,
+    ) -> Option<ClipboardItem> {
+        let content = &self.history.iter().find(|item| item.id == id)?.content;
+        // Alternative encodings of the main content would bypass the image limits
+        let formats: Vec<SelectionTarget> = formats
+            .into_iter()
+            .filter(|(mime_type, _)| !content.represents(mime_type))
+            .collect();
+
+        let mut stored = Vec::new();
+        for (mime_type, bytes) in formats {
+            let blob = match self.blobs.put(&bytes) {
+                Ok(blob) => blob,
+                Err(e) => {
+                    eprintln!("[ClipboardManager] Failed to store {}: {}", mime_type, e);
+                    continue;
+                }
+            };
+            stored.push(ClipboardFormat {
+                mime_type,
+                blob,
+                size: bytes.len(),
+            });
+        }
+
+        let item = self.history.iter_mut().find(|item| item.id == id)?;
+        item.formats = stored;
+        let item = item.clone();
+        self.size_cache.remove(id);
+        self.persist(&[StoreChange::Upsert(&item)]);
+        Some(item)
+    }
+
+    // --- State Management Helpers ---
+
+    fn should_skip_text(&mut self, text: &str, concealed: bool) -> bool {
+        if text.trim().is_empty() {
+            return true;
+        }
+
+        // Skip secrets unless they are configured to be kept temporarily
+        if concealed && self.concealed_expiry_secs == 0 {
+            eprintln!("[ClipboardManager] Skipping text marked as secret by its source");
+            return true;
+        }
+
+        // Skip self-pasted content
+        if let Some(ref pasted) = self.last_pasted_text {
+            if pasted == text || text.contains(pasted) {
+                // Clear the lock so future copies allow this text
+                self.last_pasted_text = None;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Whether a copied image is the one just pasted or already the latest item
+    pub fn should_skip_image(&mut self, hash: u64) -> bool {
+        // Check if just pasted
+        if let Some(pasted_hash) = self.last_pasted_image_hash {
+            if pasted_hash == hash {
+                self.last_pasted_image_hash = None;
+                return true;
+            }
+        }
+
+        // Check if it's the exact same image as the most recent non-pinned item
+        if let Some(item) = self.history.iter().find(|item| !item.pinned) {
+            if let Some(item_hash) = item.image_hash() {
+                if item_hash == hash {
+                    return true;
+                }
+            }
+        }
+
+        false
     }
 
     fn is_duplicate_text(&self, text: &str) -> bool {
@@ -445,8 +1607,8 @@ impl ClipboardManager {
         false
     }
 
-    fn remove_duplicate_text_from_history(&mut self, text: &str) {
-        if let Some(pos) = self.history.iter().position(|item| {
+    fn remove_duplicate_text_from_history(&mut self, text: &str) -> Option<ClipboardItem> {
+        let pos = self.history.iter().position(|item| {
             if item.pinned {
                 return false;
             }
@@ -455,26 +1617,24 @@ impl ClipboardManager {
                 ClipboardContent::RichText { plain, .. } => plain == text,
                 _ => false,
             }
-        }) {
-            self.history.remove(pos);
-        }
+        })?;
+        Some(self.history.remove(pos))
     }
 
-    fn convert_image_to_base64(&self, image_data: &ImageData<'_>) -> Option<String> {
-        let img = DynamicImage::ImageRgba8(
-            image::RgbaImage::from_raw(
-                image_data.width as u32,
-                image_data.height as u32,
-                image_data.bytes.to_vec(),
-            )?, // Returns None if dimensions don't match bytes
-        );
-
-        let mut buffer = Cursor::new(Vec::new());
-        img.write_to(&mut buffer, ImageFormat::Png).ok()?;
-        Some(BASE64.encode(buffer.get_ref()))
+    /// Stores a list thumbnail, returning its key
+    fn store_thumbnail(&self, png: &[u8]) -> Option<String> {
+        match self.blobs.put(png) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                eprintln!("[ClipboardManager] Failed to store thumbnail: {}", e);
+                None
+            }
+        }
     }
 
-    fn insert_item(&mut self, item: ClipboardItem) {
+    /// Inserts a new item and persists it together with any removals
+    /// (the replaced duplicate and trimmed items) in a single transaction.
+    fn insert_item(&mut self, item: ClipboardItem, replaced: Option<ClipboardItem>) {
         // Insert after pinned items (first non-pinned slot)
         // If all items are pinned, insert at the end to preserve pinned ordering
         let insert_pos = self
@@ -482,26 +1642,160 @@ impl ClipboardManager {
             .iter()
             .position(|i| !i.pinned)
             .unwrap_or(self.history.len());
-        self.history.insert(insert_pos, item);
+        self.history.insert(insert_pos, item.clone());
 
         // Trim history
-        self.enforce_history_limit();
-        self.save_history();
+        let trimmed = self.enforce_history_limit();
+
+        let mut changes: Vec<StoreChange<'_>> = trimmed
+            .iter()
+            .chain(&replaced)
+            .map(|i| StoreChange::Remove(&i.id))
+            .collect();
+        changes.push(StoreChange::Upsert(&item));
+        self.persist(&changes);
+
+        if let Some(replaced) = &replaced {
+            self.emit(HistoryEvent::Removed {
+                id: replaced.id.clone(),
+            });
+        }
+        self.emit(HistoryEvent::Added {
+            item: item.summary(),
+            index: insert_pos,
+        });
+        self.emit_trimmed(&trimmed);
+
+        let mut removed = trimmed;
+        removed.extend(replaced);
+        self.release_blobs(&removed);
     }
 
-    /// Enforce the configured history size. Returns true if trimming occurred.
-    fn enforce_history_limit(&mut self) -> bool {
-        let before = self.history.len();
+    /// Enforce the configured history size and storage budget.
+    /// Pinned items and the newest unpinned item (the one just captured) are always kept.
+    /// Returns the items that were trimmed.
+    fn enforce_history_limit(&mut self) -> Vec<ClipboardItem> {
+        let mut removed = Vec::new();
+        let newest = self.history.iter().position(|i| !i.pinned);
         while self.history.len() > self.max_history_size {
             // Remove from the end, skipping pinned items if possible
-            if let Some(pos) = self.history.iter().rposition(|i| !i.pinned) {
-                self.history.remove(pos);
+            let oldest = self.history.iter().rposition(|i| !i.pinned);
+            if let Some(pos) = oldest.filter(|&pos| Some(pos) != newest) {
+                removed.push(self.history.remove(pos));
             } else {
-                // All items are pinned. We stopped removing to avoid deleting pins.
+                // Only pins and the newest item are left; we never delete those.
                 break;
             }
         }
-        self.history.len() != before
+        removed.extend(self.enforce_budget());
+        removed
+    }
+
+    /// Evicts the oldest unpinned items until the history fits the storage budget.
+    /// Pinned items and the newest unpinned item are always kept.
+    fn enforce_budget(&mut self) -> Vec<ClipboardItem> {
+        if self.budget.is_unlimited() {
+            return Vec::new();
+        }
+        self.prune_size_cache();
+        let sizes: Vec<u64> = (0..self.history.len())
+            .map(|index| self.cached_item_bytes(index))
+            .collect();
+        let is_image =
+            |item: &ClipboardItem| matches!(item.content, ClipboardContent::Image { .. });
+
+        let mut total: u64 = sizes.iter().sum();
+        let mut images: u64 = self
+            .history
+            .iter()
+            .zip(&sizes)
+            .filter(|(item, _)| is_image(item))
+            .map(|(_, size)| size)
+            .sum();
+        let mut texts = total - images;
+        let over = |limit: u64, used: u64| limit > 0 && used > limit;
+
+        let newest = self.history.iter().position(|item| !item.pinned);
+        let mut evict = vec![false; self.history.len()];
+        for (index, item) in self.history.iter().enumerate().rev() {
+            if item.pinned || Some(index) == newest {
+                continue;
+            }
+            let image = is_image(item);
+            let over_type = if image {
+                over(self.budget.max_image_bytes, images)
+            } else {
+                over(self.budget.max_text_bytes, texts)
+            };
+            if !over_type && !over(self.budget.max_bytes, total) {
+                continue;
+            }
+            evict[index] = true;
+            total -= sizes[index];
+            if image {
+                images -= sizes[index];
+            } else {
+                texts -= sizes[index];
+            }
+        }
+
+        let mut removed = Vec::new();
+        let mut index = 0;
+        self.history.retain(|item| {
+            let keep = !evict[index];
+            index += 1;
+            if !keep {
+                removed.push(item.clone());
+            }
+            keep
+        });
+        if !removed.is_empty() {
+            eprintln!(
+                "[ClipboardManager] Evicted {} items to fit the storage budget",
+                removed.len()
+            );
+        }
+        removed
+    }
+
+    /// Storage size of the item at `index`, looked up once and then cached
+    fn cached_item_bytes(&mut self, index: usize) -> u64 {
+        let item = &self.history[index];
+        if let Some(bytes) = self.size_cache.get(&item.id) {
+            return *bytes;
+        }
+        let bytes = item_bytes(&self.blobs, item);
+        self.size_cache.insert(item.id.clone(), bytes);
+        bytes
+    }
+
+    /// Drops cached sizes of items that are gone
+    fn prune_size_cache(&mut self) {
+        if self.size_cache.len() <= self.history.len() * 2 {
+            return;
+        }
+        let live: HashSet<&str> = self.history.iter().map(|item| item.id.as_str()).collect();
+        self.size_cache.retain(|id, _| live.contains(id.as_str()));
+    }
+
+    /// Counts and storage sizes of the history, overall and per content type
+    pub fn get_history_stats(&mut self) -> HistoryStats {
+        self.prune_size_cache();
+        let mut stats = HistoryStats::default();
+        for index in 0..self.history.len() {
+            let bytes = self.cached_item_bytes(index);
+            let item = &self.history[index];
+            stats.total.add(bytes);
+            if item.pinned {
+                stats.pinned.add(bytes);
+            }
+            stats
+                .by_type
+                .entry(item.content.type_name())
+                .or_default()
+                .add(bytes);
+        }
+        stats
     }
 
     // --- Accessors ---
@@ -510,26 +1804,248 @@ impl ClipboardManager {
         self.history.clone()
     }
 
+    /// One page of the items copied from the given application, like `get_history_page`
+    pub fn get_history_page_for_app(
+        &self,
+        app: &str,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<HistoryPage, String> {
+        let items: Vec<&ClipboardItem> = self
+            .history
+            .iter()
+            .filter(|item| item.is_from_app(app))
+            .collect();
+        history_page(&items, cursor, limit)
+    }
+
+    /// One page of the items matching a search query (see `history_search` for the syntax)
+    pub fn search_history(
+        &mut self,
+        query: &str,
+        regex: bool,
+        offset: usize,
+        limit: usize,
+    ) -> Result<SearchResults, String> {
+        let query = SearchQuery::parse(query, regex)?;
+        Ok(self
+            .search_cache
+            .search(&self.history, &query, offset, limit))
+    }
+
+    /// Distinct source applications present in the history, in history order
+    pub fn get_source_apps(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.history
+            .iter()
+            .filter_map(|item| item.source_app.clone())
+            .filter(|app| seen.insert(app.to_lowercase()))
+            .collect()
+    }
+
+    /// One page of history summaries, starting after the item with id `cursor`
+    /// (from the start when None). Fails if the cursor item is gone; the caller then
+    /// starts over.
+    pub fn get_history_page(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<HistoryPage, String> {
+        let items: Vec<&ClipboardItem> = self.history.iter().collect();
+        history_page(&items, cursor, limit)
+    }
+
     pub fn get_item(&self, id: &str) -> Option<&ClipboardItem> {
         self.history.iter().find(|item| item.id == id)
     }
 
+    /// Returns the list thumbnail of an image item as base64 PNG. Small images are
+    /// returned as they are; items recorded before thumbnails get one made now.
+    pub fn get_thumbnail_base64(&mut self, id: &str) -> Result<String, String> {
+        let pos = self
+            .history
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| format!("Item '{}' not found", id))?;
+        let item = &self.history[pos];
+        if let Some(key) = item.thumbnail_key() {
+            return Ok(BASE64.encode(self.blobs.read(key)?));
+        }
+        let ClipboardContent::Image {
+            blob,
+            width,
+            height,
+            ..
+        } = &item.content
+        else {
+            return Err(format!("Item '{}' is not an image", id));
+        };
+        let png = self.blobs.read(blob)?;
+        if *width <= THUMBNAIL_MAX_SIZE && *height <= THUMBNAIL_MAX_SIZE {
+            return Ok(BASE64.encode(png));
+        }
+
+        let img =
+            image::load_from_memory(&png).map_err(|e| format!("Failed to decode image: {}", e))?;
+        let thumbnail = image_policy::make_thumbnail(&img)
+            .ok_or_else(|| "Failed to create thumbnail".to_string())?;
+        let key = self
+            .store_thumbnail(&thumbnail)
+            .ok_or_else(|| "Failed to store thumbnail".to_string())?;
+
+        let item = &mut self.history[pos];
+        if let ClipboardContent::Image { thumbnail, .. } = &mut item.content {
+            *thumbnail = Some(key);
+        }
+        let item = item.clone();
+        self.size_cache.remove(id);
+        self.persist(&[StoreChange::Upsert(&item)]);
+        Ok(BASE64.encode(thumbnail))
+    }
+
+    /// Returns the PNG of an image item as base64, loaded from the blob store.
+    pub fn get_image_base64(&self, id: &str) -> Result<String, String> {
+        let item = self
+            .get_item(id)
+            .ok_or_else(|| format!("Item '{}' not found", id))?;
+        let key = item
+            .blob_key()
+            .ok_or_else(|| format!("Item '{}' is not an image", id))?;
+        Ok(BASE64.encode(self.blobs.read(key)?))
+    }
+
     pub fn clear(&mut self) {
-        self.history.retain(|item| item.pinned);
-        self.save_history();
+        let (pinned, removed): (Vec<_>, Vec<_>) =
+            self.history.drain(..).partition(|item| item.pinned);
+        self.history = pinned;
+        self.persist_removals(&removed);
+        self.emit(HistoryEvent::Cleared);
     }
 
     pub fn remove_item(&mut self, id: &str) {
-        self.history.retain(|item| item.id != id);
-        self.save_history();
+        if let Some(pos) = self.history.iter().position(|item| item.id == id) {
+            let removed = self.history.remove(pos);
+            self.persist_removals(&[removed]);
+            self.emit(HistoryEvent::Removed { id: id.to_string() });
+        }
+    }
+
+    /// Deletes what the `clear_on_lock` setting chose; called when the session locks
+    /// or the machine is about to suspend or shut down.
+    pub fn clear_for_lock(&mut self) {
+        let matches: fn(&ClipboardItem) -> bool = match self.lock_clear_action {
+            LockClearAction::Off => return,
+            LockClearAction::Sensitive => ClipboardItem::is_sensitive,
+            LockClearAction::Unpinned => |item| !item.pinned,
+            LockClearAction::All => |_| true,
+        };
+        // The last pasted text may be one of the removed secrets
+        self.last_pasted_text = None;
+
+        let (removed, kept): (Vec<_>, Vec<_>) = self.history.drain(..).partition(matches);
+        self.history = kept;
+        if removed.is_empty() {
+            return;
+        }
+        eprintln!(
+            "[ClipboardManager] Cleared {} items for session lock",
+            removed.len()
+        );
+        self.persist_removals(&removed);
+        self.emit_trimmed(&removed);
+    }
+
+    /// Drops items whose expiry has passed. Returns true if anything was removed.
+    pub fn remove_expired(&mut self) -> bool {
+        let now = Utc::now();
+        let (expired, kept): (Vec<_>, Vec<_>) = self
+            .history
+            .drain(..)
+            .partition(|item| item.expires_at.is_some_and(|at| at <= now));
+        self.history = kept;
+        if expired.is_empty() {
+            return false;
+        }
+        self.persist_removals(&expired);
+        self.emit_trimmed(&expired);
+        true
+    }
+
+    /// Applies the retention rules: runs the daily purge if its time has passed since
+    /// the last call, otherwise drops unpinned items past their age limit.
+    pub fn enforce_retention(&mut self) {
+        self.enforce_retention_at(Local::now());
+    }
+
+    fn enforce_retention_at(&mut self, now: DateTime<Local>) {
+        let since = self.last_retention_check.replace(now);
+        if since.is_some_and(|since| self.retention.purge_due(since, now)) {
+            eprintln!("[ClipboardManager] Daily purge of unpinned history");
+            self.clear();
+            return;
+        }
+        self.remove_aged(now.with_timezone(&Utc));
+    }
+
+    /// Drops unpinned items past their age limit. Returns true if anything was removed.
+    fn remove_aged(&mut self, now: DateTime<Utc>) -> bool {
+        if !self
+            .history
+            .iter()
+            .any(|item| self.retention.is_expired(item, now))
+        {
+            return false;
+        }
+        let retention = self.retention;
+        let (aged, kept): (Vec<_>, Vec<_>) = self
+            .history
+            .drain(..)
+            .partition(|item| retention.is_expired(item, now));
+        self.history = kept;
+        eprintln!("[ClipboardManager] Removed {} aged items", aged.len());
+        self.persist_removals(&aged);
+        self.emit_trimmed(&aged);
+        true
     }
 
+    /// Pins or unpins an item. A pinned item moves to the end of the pinned ones,
+    /// an unpinned one back to its place among the others (newest first).
     pub fn toggle_pin(&mut self, id: &str) -> Option<ClipboardItem> {
-        let item = self.history.iter_mut().find(|i| i.id == id)?;
+        let pos = self.history.iter().position(|i| i.id == id)?;
+        let mut item = self.history.remove(pos);
         item.pinned = !item.pinned;
-        let item_clone = item.clone();
-        self.save_history();
-        Some(item_clone)
+
+        let index = if item.pinned {
+            self.history.iter().take_while(|i| i.pinned).count()
+        } else {
+            self.history
+                .iter()
+                .position(|i| !i.pinned && i.timestamp < item.timestamp)
+                .unwrap_or(self.history.len())
+        };
+        self.history.insert(index, item.clone());
+
+        self.persist(&[StoreChange::Upsert(&item)]);
+        self.emit(HistoryEvent::Updated {
+            item: item.summary(),
+            index,
+        });
+        Some(item)
+    }
+
+    /// Marks an item as sensitive or back. Returns the updated item.
+    pub fn toggle_sensitive(&mut self, id: &str) -> Option<ClipboardItem> {
+        let index = self.history.iter().position(|i| i.id == id)?;
+        let item = &mut self.history[index];
+        item.sensitive = !item.sensitive;
+        let item = item.clone();
+
+        self.persist(&[StoreChange::Upsert(&item)]);
+        self.emit(HistoryEvent::Updated {
+            item: item.summary(),
+            index,
+        });
+        Some(item)
     }
 
     // --- Paste Logic ---
@@ -545,14 +2061,36 @@ impl ClipboardManager {
                 self.last_pasted_image_hash = None;
             }
             ClipboardContent::Image { .. } => {
-                if let Some(hash) = item.extract_image_hash() {
+                if let Some(hash) = item.image_hash() {
                     self.last_pasted_image_hash = Some(hash);
                 }
                 self.last_pasted_text = None;
             }
+            ClipboardContent::Files { .. } => {
+                self.last_pasted_files_hash =
+                    item.copied_files().map(|files| calculate_hash(&files));
+                self.last_pasted_text = None;
+                self.last_pasted_image_hash = None;
+            }
         }
     }
 
+    /// Like `mark_as_pasted`, for content put back on the clipboard as a snapshot
+    /// (see `sensitive_paste::snapshot_clipboard`)
+    pub fn mark_snapshot_as_pasted(&mut self, snapshot: &[SelectionTarget]) {
+        let mime_types: Vec<String> = snapshot.iter().map(|(mime, _)| mime.clone()).collect();
+        let read = |mime: &str| {
+            snapshot
+                .iter()
+                .find(|(offered, _)| offered == mime)
+                .map(|(_, bytes)| bytes.clone())
+        };
+        self.last_pasted_text = sensitive_paste::snapshot_text(snapshot);
+        self.last_pasted_image_hash = sensitive_paste::snapshot_image_hash(snapshot);
+        self.last_pasted_files_hash =
+            CopiedFiles::from_offer(&mime_types, read).map(|files| calculate_hash(&files));
+    }
+
     /// Mark a specific text as pasted (to prevent it from appearing in history)
     /// Used for emojis/special insertions
     pub fn mark_text_as_pasted(&mut self, text: &str) {
@@ -560,11 +2098,216 @@ impl ClipboardManager {
         self.last_added_text_hash = Some(calculate_hash(&text));
     }
 
-    pub fn paste_item(&mut self, item: &ClipboardItem) -> Result<(), String> {
+    /// First step of a paste, before the clipboard is overwritten: whether it has to be
+    /// saved so it can be given back afterwards. `item` is `None` for content that is
+    /// not a history item (emoji, GIF). The saving itself is done without the lock.
+    pub fn begin_paste(&mut self, item: Option<&ClipboardItem>) -> ClipboardSave {
+        let earlier = self.pending_revert.take();
+        let restore_sensitive = item.is_some_and(|item| self.sensitive_paste.applies_to(item))
+            && self.sensitive_paste.action == SensitivePasteAction::Restore;
+        if self.transient_paste || restore_sensitive {
+            ClipboardSave::Needed { earlier }
+        } else {
+            ClipboardSave::NotNeeded
+        }
+    }
+
+    /// Pastes an item. Sensitive items are taken off the clipboard again later,
+    /// as set by `set_sensitive_paste`; in transient mode every item is.
+    /// `previous` is the clipboard saved as asked by `begin_paste`.
+    pub fn paste_item(
+        &mut self,
+        item: &ClipboardItem,
+        previous: Option<Vec<SelectionTarget>>,
+    ) -> Result<(), String> {
+        self.paste_item_content(item)?;
+        self.last_paste = Some((item.id.clone(), Utc::now()));
+
+        if self.transient_paste && previous.is_some() {
+            self.schedule_revert(Some(item), previous, TRANSIENT_RESTORE_DELAY);
+        } else if self.sensitive_paste.applies_to(item) {
+            eprintln!(
+                "[ClipboardManager] Sensitive item pasted, reverting the clipboard in {}s",
+                self.sensitive_paste.delay.num_seconds()
+            );
+            self.schedule_revert(Some(item), previous, self.sensitive_paste.delay);
+        } else if self.transient_paste {
+            eprintln!("[ClipboardManager] Clipboard was not saved, keeping the pasted item");
+        }
+        Ok(())
+    }
+
+    /// Gives the clipboard saved as asked by `begin_paste` back after a paste of content
+    /// that is not a history item, once the paste of `pasted` (if known, to check it is
+    /// still on the clipboard) had time to complete
+    pub fn end_transient_paste(
+        &mut self,
+        previous: Option<Vec<SelectionTarget>>,
+        pasted: Option<&ClipboardItem>,
+    ) {
+        if !self.transient_paste {
+            return;
+        }
+        if previous.is_some() {
+            self.schedule_revert(pasted, previous, TRANSIENT_RESTORE_DELAY);
+        } else {
+            eprintln!("[ClipboardManager] Clipboard was not saved, keeping the pasted content");
+        }
+    }
+
+    /// Takes pasted content off the clipboard after `delay`: puts `previous` back,
+    /// or clears the clipboard when there is none
+    fn schedule_revert(
+        &mut self,
+        pasted: Option<&ClipboardItem>,
+        previous: Option<Vec<SelectionTarget>>,
+        delay: chrono::Duration,
+    ) {
+        self.pending_revert = Some(PendingRevert {
+            pasted: pasted.and_then(|item| self.pasted_content(item)),
+            due: Utc::now() + delay,
+            previous: previous.unwrap_or_default(),
+        });
+    }
+
+    /// What pasting `item` put on the clipboard, to check later it is still there
+    fn pasted_content(&self, item: &ClipboardItem) -> Option<PastedContent> {
+        match &item.content {
+            ClipboardContent::Text(text) | ClipboardContent::RichText { plain: text, .. } => {
+                Some(PastedContent::Text(text.clone()))
+            }
+            ClipboardContent::Image { blob, .. } => {
+                self.blobs.read(blob).ok().map(PastedContent::Image)
+            }
+            ClipboardContent::Files { .. } => item.copied_files().map(PastedContent::Files),
+        }
+    }
+
+    /// The paste waiting to be taken off the clipboard, once it is due. Called periodically
+    /// by the clipboard monitor, which hands it to `sensitive_paste::revert`.
+    pub fn take_due_revert(&mut self, now: DateTime<Utc>) -> Option<PendingRevert> {
+        if !self
+            .pending_revert
+            .as_ref()
+            .is_some_and(|pending| pending.due <= now)
+        {
+            return None;
+        }
+        self.pending_revert.take()
+    }
+
+    /// Records that a paste is being reverted to `previous` (cleared when empty)
+    pub fn note_revert(&mut self, previous: &[SelectionTarget], now: DateTime<Utc>) {
+        self.last_paste = None;
+        if previous.is_empty() {
+            self.cleared_at = Some(now);
+        } else {
+            // The restored content is already in history (or was never meant to be)
+            self.mark_snapshot_as_pasted(previous);
+        }
+    }
+
+    /// Takes over the clipboard after its owner exited, offering the item it held again
+    /// with all its formats. Does nothing unless clipboard persistence is enabled, while
+    /// capture is paused (the clipboard held something not in history), or for sensitive
+    /// items, which are not kept available beyond their source application.
+    pub fn reoffer_clipboard(&mut self, now: DateTime<Utc>) {
+        if !self.persist_clipboard || self.is_capture_paused() {
+            return;
+        }
+        if self
+            .cleared_at
+            .is_some_and(|at| now - at < chrono::Duration::seconds(CLEARED_OWNER_LOSS_GRACE_SECS))
+        {
+            return;
+        }
+        let Some(item) = self.clipboard_item().cloned() else {
+            return;
+        };
+        if item.is_sensitive() || self.sensitive_paste.applies_to(&item) {
+            return;
+        }
+
+        eprintln!("[ClipboardManager] Clipboard owner exited, offering the last item again");
+        if let Err(e) = self.offer_item(&item) {
+            eprintln!("[ClipboardManager] Failed to keep clipboard content: {}", e);
+        }
+    }
+
+    /// Puts an item from history on the clipboard at startup, as chosen by `restore`,
+    /// unless the clipboard already holds something
+    pub fn restore_clipboard(&mut self, restore: StartupRestore) {
+        let Some(item) = self.startup_item(restore).cloned() else {
+            return;
+        };
+        if clipboard_targets::get_clipboard_targets().is_some_and(|targets| !targets.is_empty()) {
+            return;
+        }
+
+        eprintln!("[ClipboardManager] Restoring the last item to the clipboard");
+        if let Err(e) = self.offer_item(&item) {
+            eprintln!("[ClipboardManager] Failed to restore clipboard: {}", e);
+            return;
+        }
+        // Reading the restored item back must not add it again
+        if let ClipboardContent::Text(text) | ClipboardContent::RichText { plain: text, .. } =
+            &item.content
+        {
+            self.last_added_text_hash = Some(calculate_hash(text));
+        }
+    }
+
+    /// Item restored at startup: the newest (pinned) copy, unless it is sensitive
+    fn startup_item(&self, restore: StartupRestore) -> Option<&ClipboardItem> {
+        let pinned_only = match restore {
+            StartupRestore::Off => return None,
+            StartupRestore::Latest => false,
+            StartupRestore::Pinned => true,
+        };
+        self.history
+            .iter()
+            .filter(|item| item.selection == SelectionKind::Clipboard)
+            .filter(|item| item.pinned || !pinned_only)
+            .max_by_key(|item| item.timestamp)
+            .filter(|item| !item.is_sensitive())
+    }
+
+    /// Item the clipboard held last: the newest CLIPBOARD copy, or an item pasted after it
+    fn clipboard_item(&self) -> Option<&ClipboardItem> {
+        let newest = self
+            .history
+            .iter()
+            .filter(|item| item.selection == SelectionKind::Clipboard)
+            .max_by_key(|item| item.timestamp);
+        match &self.last_paste {
+            Some((id, at)) if newest.map_or(true, |item| item.timestamp < *at) => {
+                self.get_item(id).or(newest)
+            }
+            _ => newest,
+        }
+    }
+
+    /// Puts `item` on the clipboard with all its formats, without pasting it
+    fn offer_item(&mut self, item: &ClipboardItem) -> Result<(), String> {
+        let targets = self.selection_targets(item)?;
+        self.mark_as_pasted(item);
+        selection_owner::offer_selection(SelectionKind::Clipboard, targets)
+    }
+
+    fn paste_item_content(&mut self, item: &ClipboardItem) -> Result<(), String> {
         // 1. Prevent loop: Mark as pasted before OS action
         self.mark_as_pasted(item);
 
         // 2. Write content to OS clipboard
+        if !item.formats.is_empty() {
+            // Re-offer every stored representation next to the main content
+            selection_owner::offer_selection(
+                SelectionKind::Clipboard,
+                self.selection_targets(item)?,
+            )?;
+            return self.simulate_paste_action();
+        }
+
         let mut clipboard = get_system_clipboard()?;
 
         match &item.content {
@@ -578,11 +2321,20 @@ impl ClipboardManager {
                     .map_err(|e| e.to_string())?;
             }
             ClipboardContent::Image {
-                base64,
+                blob,
                 width,
                 height,
+                ..
             } => {
-                self.write_image_to_clipboard(&mut clipboard, base64, *width, *height)?;
+                self.write_image_to_clipboard(&mut clipboard, blob, *width, *height)?;
+            }
+            ClipboardContent::Files { uris, operation } => {
+                // File managers need several targets at once, which arboard cannot offer
+                let files = CopiedFiles {
+                    uris: uris.clone(),
+                    operation: *operation,
+                };
+                selection_owner::offer_selection(SelectionKind::Clipboard, files.to_targets())?;
             }
         }
 
@@ -592,16 +2344,51 @@ impl ClipboardManager {
         Ok(())
     }
 
+    /// Main content of the item followed by its stored formats
+    fn selection_targets(&self, item: &ClipboardItem) -> Result<Vec<SelectionTarget>, String> {
+        let mut targets: Vec<SelectionTarget> = match &item.content {
+            ClipboardContent::Text(text) => {
+                vec![(TEXT_MIME.to_string(), text.clone().into_bytes())]
+            }
+            ClipboardContent::RichText { plain, html } => vec![
+                (TEXT_MIME.to_string(), plain.clone().into_bytes()),
+                (HTML_MIME.to_string(), html.clone().into_bytes()),
+            ],
+            ClipboardContent::Image { blob, .. } => {
+                let bytes = self.blobs.read(blob)?;
+                vec![(image_policy::mime_type(&bytes).to_string(), bytes)]
+            }
+            ClipboardContent::Files { .. } => item
+                .copied_files()
+                .map(|files| files.to_targets())
+                .unwrap_or_default(),
+        };
+
+        for format in &item.formats {
+            if item.content.represents(&format.mime_type)
+                || targets.iter().any(|(mime, _)| *mime == format.mime_type)
+            {
+                continue;
+            }
+            match self.blobs.read(&format.blob) {
+                Ok(bytes) => targets.push((format.mime_type.clone(), bytes)),
+                Err(e) => eprintln!(
+                    "[ClipboardManager] Skipping {} on paste: {}",
+                    format.mime_type, e
+                ),
+            }
+        }
+        Ok(targets)
+    }
+
     fn write_image_to_clipboard(
         &self,
         clipboard: &mut Clipboard,
-        base64_str: &str,
+        blob_key: &str,
         width: u32,
         height: u32,
     ) -> Result<(), String> {
-        let bytes = BASE64
-            .decode(base64_str)
-            .map_err(|e| format!("Base64 decode failed: {}", e))?;
+        let bytes = self.blobs.read(blob_key)?;
         let img =
             image::load_from_memory(&bytes).map_err(|e| format!("Image load failed: {}", e))?;
         let rgba = img.to_rgba8();
@@ -630,3 +2417,553 @@ impl ClipboardManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, test_manager};
+
+    #[test]
+    fn test_concealed_text_skipped_by_default() {
+        let mut manager = test_manager();
+        assert!(manager
+            .add_text("hunter2".to_string(), None, true, None)
+            .is_none());
+        assert!(manager.get_history().is_empty());
+    }
+
+    #[test]
+    fn test_concealed_text_expires_and_is_not_persisted() {
+        let mut manager = test_manager();
+        manager.set_concealed_expiry(30);
+
+        let item = manager
+            .add_text("hunter2".to_string(), None, true, None)
+            .unwrap();
+        assert!(item.expires_at.is_some());
+        assert!(manager.store.load().unwrap().is_empty());
+        assert!(!manager.remove_expired());
+
+        // Force the expiry into the past
+        manager.history[0].expires_at = Some(Utc::now() - chrono::Duration::seconds(1));
+        assert!(manager.remove_expired());
+        assert!(manager.get_history().is_empty());
+    }
+
+    #[test]
+    fn test_source_metadata_and_app_filter() {
+        let mut manager = test_manager();
+        let source = ActiveWindow {
+            app_class: Some("firefox".to_string()),
+            title: Some("Example Domain".to_string()),
+            pid: Some(1234),
+            ..Default::default()
+        };
+        manager.add_text("from browser".to_string(), None, false, Some(&source));
+        manager.add_text("unknown source".to_string(), None, false, None);
+
+        let page = manager
+            .get_history_page_for_app("Firefox", None, DEFAULT_PAGE_SIZE)
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert!(page.next_cursor.is_none());
+        let from_firefox = manager.get_item(&page.items[0].id).unwrap();
+        assert_eq!(
+            from_firefox.source_window_title.as_deref(),
+            Some("Example Domain")
+        );
+        assert_eq!(from_firefox.source_pid, Some(1234));
+        assert_eq!(manager.get_source_apps(), vec!["firefox".to_string()]);
+
+        // Metadata survives the JSON round trip used by the store
+        let json = serde_json::to_string(from_firefox).unwrap();
+        let restored: ClipboardItem = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.source_app.as_deref(), Some("firefox"));
+    }
+
+    #[test]
+    fn test_primary_selection_items_are_tagged() {
+        let mut manager = test_manager();
+        let item = manager
+            .add_primary_text("selected words".to_string(), None)
+            .unwrap();
+        assert_eq!(item.selection, SelectionKind::Primary);
+
+        let json = serde_json::to_string(&item).unwrap();
+        assert!(json.contains(r#""selection":"primary""#));
+
+        // Clipboard items omit the tag and default to the clipboard when loaded
+        let item = manager
+            .add_text("copied".to_string(), None, false, None)
+            .unwrap();
+        let json = serde_json::to_string(&item).unwrap();
+        assert!(!json.contains("selection"));
+        let restored: ClipboardItem = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.selection, SelectionKind::Clipboard);
+    }
+
+    #[test]
+    fn test_copied_files_are_deduplicated_and_gif_cache_skipped() {
+        let mut manager = test_manager();
+        let files = CopiedFiles {
+            uris: vec!["file:///home/user/a.txt".to_string()],
+            operation: FileOperation::Cut,
+        };
+        let item = manager.add_files(files.clone(), None).unwrap();
+        assert_eq!(item.preview, "a.txt");
+        assert!(manager.add_files(files.clone(), None).is_none());
+
+        manager.add_text("other".to_string(), None, false, None);
+        assert!(manager.add_files(files.clone(), None).is_some());
+        assert_eq!(manager.get_history().len(), 2);
+        assert_eq!(manager.get_history()[0].copied_files(), Some(files));
+
+        let gif = CopiedFiles {
+            uris: vec![format!(
+                "file:///home/user/.cache/{}1.gif",
+                GIF_CACHE_MARKER
+            )],
+            operation: FileOperation::Copy,
+        };
+        assert!(manager.add_files(gif, None).is_none());
+    }
+
+    #[test]
+    fn test_formats_are_stored_and_offered_after_content() {
+        let mut manager = test_manager();
+        let item = manager
+            .add_text("hello".to_string(), None, false, None)
+            .unwrap();
+        let item = manager
+            .attach_formats(
+                &item.id,
+                vec![
+                    ("text/rtf".to_string(), br"{\rtf1 hello}".to_vec()),
+                    (TEXT_MIME.to_string(), b"stale".to_vec()),
+                ],
+            )
+            .unwrap();
+        // The text is the main content and not stored twice
+        assert_eq!(item.formats.len(), 1);
+        assert_eq!(item.blob_keys().count(), 1);
+
+        let targets = manager.selection_targets(&item).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0], (TEXT_MIME.to_string(), b"hello".to_vec()));
+        assert_eq!(targets[1].0, "text/rtf");
+
+        manager.remove_item(&item.id);
+        assert!(manager.blobs.read(&item.formats[0].blob).is_err());
+    }
+
+    #[test]
+    fn test_unversioned_history_is_migrated() {
+        let legacy = r#"[{
+            "id": "a",
+            "content": {"type": "Image", "data": {"blob": "k", "width": 2, "height": 3}},
+            "timestamp": "2024-01-01T00:00:00Z",
+            "pinned": false,
+            "preview": "Image (2x3) #12345"
+        }]"#;
+        let items = HistoryFile::parse(legacy).unwrap().into_items().unwrap();
+        assert_eq!(items[0].image_hash(), Some(12345));
+        assert_eq!(items[0].preview, "Image (2x3)");
+
+        // Current files round-trip unchanged, newer ones are refused
+        let json = serde_json::to_string(&HistoryFile::from_items(&items).unwrap()).unwrap();
+        let items = HistoryFile::parse(&json).unwrap().into_items().unwrap();
+        assert_eq!(items[0].image_hash(), Some(12345));
+        let newer = HistoryFile {
+            version: HISTORY_SCHEMA_VERSION + 1,
+            items: Vec::new(),
+        };
+        assert!(newer.into_items().is_err());
+    }
+
+    #[test]
+    fn test_clear_for_lock() {
+        let mut manager = test_manager();
+        manager.set_concealed_expiry(30);
+        manager.add_text("note".to_string(), None, false, None);
+        manager.add_text("hunter2".to_string(), None, true, None);
+        let pinned = manager
+            .add_text("pinned".to_string(), None, false, None)
+            .unwrap();
+        manager.toggle_pin(&pinned.id);
+
+        manager.clear_for_lock();
+        assert_eq!(manager.get_history().len(), 3);
+
+        manager.set_lock_clear_action(LockClearAction::Sensitive);
+        manager.clear_for_lock();
+        assert_eq!(manager.get_history().len(), 2);
+
+        manager.set_lock_clear_action(LockClearAction::Unpinned);
+        manager.clear_for_lock();
+        assert_eq!(manager.get_history()[0].id, pinned.id);
+        assert_eq!(manager.get_history().len(), 1);
+
+        manager.set_lock_clear_action(LockClearAction::All);
+        manager.clear_for_lock();
+        assert!(manager.get_history().is_empty());
+    }
+
+    #[test]
+    fn test_sensitive_items_and_restored_snapshots() {
+        let mut manager = test_manager();
+        let item = manager
+            .add_text("token".to_string(), None, false, None)
+            .unwrap();
+        assert!(!item.is_sensitive());
+        let item = manager.toggle_sensitive(&item.id).unwrap();
+        assert!(item.is_sensitive() && item.summary().sensitive);
+
+        // Restoring the clipboard must not record its previous content again
+        let snapshot = vec![(
+            sensitive_paste::SNAPSHOT_TEXT_MIME.to_string(),
+            b"before".to_vec(),
+        )];
+        manager.mark_snapshot_as_pasted(&snapshot);
+        assert!(manager
+            .add_text("before".to_string(), None, false, None)
+            .is_none());
+        assert!(manager
+            .add_text("before".to_string(), None, false, None)
+            .is_some());
+    }
+
+    #[test]
+    fn test_clipboard_item_follows_copies_and_pastes() {
+        let mut manager = test_manager();
+        assert!(manager.clipboard_item().is_none());
+        let first = manager
+            .add_text("first".to_string(), None, false, None)
+            .unwrap();
+        let second = manager
+            .add_text("second".to_string(), None, false, None)
+            .unwrap();
+        manager
+            .add_primary_text("selected".to_string(), None)
+            .unwrap();
+        assert_eq!(manager.clipboard_item().unwrap().id, second.id);
+
+        // A paste of an older item replaces the newest copy, until something is copied
+        manager.last_paste = Some((first.id.clone(), Utc::now()));
+        assert_eq!(manager.clipboard_item().unwrap().id, first.id);
+        let third = manager
+            .add_text("third".to_string(), None, false, None)
+            .unwrap();
+        assert_eq!(manager.clipboard_item().unwrap().id, third.id);
+    }
+
+    #[test]
+    fn test_startup_item() {
+        let mut manager = test_manager();
+        let pinned = manager
+            .add_text("pinned".to_string(), None, false, None)
+            .unwrap();
+        manager.toggle_pin(&pinned.id);
+        let latest = manager
+            .add_text("latest".to_string(), None, false, None)
+            .unwrap();
+
+        assert!(manager.startup_item(StartupRestore::Off).is_none());
+        assert_eq!(
+            manager.startup_item(StartupRestore::Latest).unwrap().id,
+            latest.id
+        );
+        assert_eq!(
+            manager.startup_item(StartupRestore::Pinned).unwrap().id,
+            pinned.id
+        );
+        manager.toggle_sensitive(&latest.id);
+        assert!(manager.startup_item(StartupRestore::Latest).is_none());
+    }
+
+    #[test]
+    fn test_timed_capture_pause_resumes() {
+        let mut manager = test_manager();
+        let events = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        manager.set_event_sink(Box::new(move |event| sink.lock().push(event)));
+
+        manager.pause_capture(Some(chrono::Duration::minutes(5)));
+        assert!(manager.is_capture_paused());
+        manager.resume_capture_if_due(Utc::now());
+        assert!(manager.is_capture_paused());
+        manager.resume_capture_if_due(Utc::now() + chrono::Duration::minutes(6));
+        assert_eq!(manager.capture_state(), CaptureState::Active);
+
+        // A pause without a timer lasts until resumed by hand
+        manager.pause_capture(None);
+        manager.resume_capture_if_due(Utc::now() + chrono::Duration::days(1));
+        assert!(manager.is_capture_paused());
+        assert_eq!(
+            events.lock().last(),
+            Some(&HistoryEvent::CaptureChanged(CaptureState::Paused {
+                until: None
+            }))
+        );
+        assert_eq!(events.lock().len(), 3);
+    }
+
+    #[test]
+    fn test_history_pages_and_events() {
+        let mut manager = test_manager();
+        let events = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        manager.set_event_sink(Box::new(move |event| sink.lock().push(event)));
+        manager.set_max_history_size(3);
+
+        for text in ["a", "b", "c", "d"] {
+            manager.add_text(text.to_string(), None, false, None);
+        }
+        let first = manager.get_history_page(None, 2).unwrap();
+        assert_eq!(first.total, 3);
+        assert_eq!(first.items[0].text.as_deref(), Some("d"));
+        let cursor = first.next_cursor.unwrap();
+        let second = manager.get_history_page(Some(&cursor), 2).unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert!(second.next_cursor.is_none());
+
+        // Adding "d" trimmed "a"; pinning "b" moves it to the top
+        let trimmed_id = events
+            .lock()
+            .iter()
+            .find_map(|event| match event {
+                HistoryEvent::Trimmed { ids } => Some(ids[0].clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert!(manager.get_item(&trimmed_id).is_none());
+        let b = second.items[0].id.clone();
+        manager.toggle_pin(&b);
+        assert_eq!(
+            events.lock().last().map(HistoryEvent::name),
+            Some("item-updated")
+        );
+        assert_eq!(manager.get_history()[0].id, b);
+        manager.remove_item(&cursor);
+        assert!(manager.get_history_page(Some(&cursor), 2).is_err());
+    }
+
+    #[test]
+    fn test_large_images_get_a_thumbnail() {
+        let mut manager = test_manager();
+        let image = |width: usize, height: usize, hash: u64| {
+            let data = ImageData {
+                width,
+                height,
+                bytes: vec![128; width * height * 4].into(),
+            };
+            image_policy::encode_image(&data, hash, &ImagePolicy::default()).unwrap()
+        };
+
+        let large = manager.add_image(image(600, 300, 1), None).unwrap();
+        let key = large.thumbnail_key().unwrap().to_string();
+        assert_eq!(large.blob_keys().count(), 2);
+        let thumbnail = BASE64
+            .decode(manager.get_thumbnail_base64(&large.id).unwrap())
+            .unwrap();
+        let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+        // Small images are shown as they are
+        let small = manager.add_image(image(8, 8, 2), None).unwrap();
+        assert!(small.thumbnail_key().is_none());
+        assert_eq!(
+            manager.get_thumbnail_base64(&small.id),
+            manager.get_image_base64(&small.id)
+        );
+
+        manager.remove_item(&large.id);
+        assert!(manager.blobs.read(&key).is_err());
+    }
+
+    #[test]
+    fn test_orphan_sweep_needs_a_complete_load() {
+        let dir = test_support::temp_dir("sweep");
+        let orphan = BlobStore::new(dir.to_path_buf()).put(b"orphan").unwrap();
+
+        // A volatile store knows nothing about the blobs on disk
+        let manager = ClipboardManager::with_store(
+            Box::new(MemoryHistoryStore::new()),
+            BlobStore::new(dir.to_path_buf()),
+            DEFAULT_MAX_HISTORY_SIZE,
+        );
+        assert!(manager.blobs.read(&orphan).is_ok());
+
+        let manager = ClipboardManager::with_store(
+            Box::new(history_store::SqliteHistoryStore::open_in_memory().unwrap()),
+            BlobStore::new(dir.to_path_buf()),
+            DEFAULT_MAX_HISTORY_SIZE,
+        );
+        assert!(manager.blobs.read(&orphan).is_err());
+    }
+
+    #[test]
+    fn test_new_item_survives_a_full_pinned_history() {
+        let mut manager = test_manager();
+        let events = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        manager.set_event_sink(Box::new(move |event| sink.lock().push(event)));
+        for text in ["a", "b"] {
+            let item = manager
+                .add_text(text.to_string(), None, false, None)
+                .unwrap();
+            manager.toggle_pin(&item.id);
+        }
+        manager.set_max_history_size(2);
+        events.lock().clear();
+
+        let new = manager
+            .add_text("new".to_string(), None, false, None)
+            .unwrap();
+        assert_eq!(manager.get_history().len(), 3);
+        assert!(manager.get_item(&new.id).is_some());
+        assert!(manager.store.load().unwrap().iter().any(|i| i.id == new.id));
+        assert!(!events
+            .lock()
+            .iter()
+            .any(|event| matches!(event, HistoryEvent::Trimmed { .. })));
+
+        // The next capture replaces it instead of growing the history
+        let newer = manager
+            .add_text("newer".to_string(), None, false, None)
+            .unwrap();
+        assert_eq!(manager.get_history().len(), 3);
+        assert!(manager.get_item(&new.id).is_none());
+        assert!(manager.get_item(&newer.id).is_some());
+    }
+
+    #[test]
+    fn test_retention_spares_pinned_items() {
+        let mut manager = test_manager();
+        let old = manager
+            .add_text("old".to_string(), None, false, None)
+            .unwrap();
+        let pinned = manager
+            .add_text("pinned".to_string(), None, false, None)
+            .unwrap();
+        manager.add_text("new".to_string(), None, false, None);
+        manager.toggle_pin(&pinned.id);
+        for item in manager
+            .history
+            .iter_mut()
+            .filter(|item| item.preview != "new")
+        {
+            item.timestamp -= chrono::Duration::days(10);
+        }
+
+        manager.set_retention(RetentionPolicy {
+            max_age: Some(chrono::Duration::days(7)),
+            daily_purge: chrono::NaiveTime::from_hms_opt(3, 0, 0),
+            ..Default::default()
+        });
+        assert!(manager.get_item(&old.id).is_none());
+        assert_eq!(manager.get_history().len(), 2);
+
+        let night = chrono::TimeZone::with_ymd_and_hms(&Local, 2024, 5, 2, 2, 0, 0).unwrap();
+        manager.enforce_retention_at(night);
+        assert_eq!(manager.get_history().len(), 2);
+        manager.enforce_retention_at(night + chrono::Duration::hours(2));
+        let history = manager.get_history();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].pinned);
+    }
+
+    #[test]
+    fn test_budget_evicts_oldest_unpinned_items() {
+        let mut manager = test_manager();
+        let pinned = manager
+            .add_text("p".repeat(100), None, false, None)
+            .unwrap();
+        manager.toggle_pin(&pinned.id);
+        for text in ["a", "b", "c"] {
+            manager.add_text(text.repeat(40), None, false, None);
+        }
+
+        let stats = manager.get_history_stats();
+        assert_eq!(
+            stats.total,
+            ItemStats {
+                count: 4,
+                bytes: 220
+            }
+        );
+        assert_eq!(stats.pinned.bytes, 100);
+        assert_eq!(stats.by_type["text"].count, 4);
+
+        // Pinned items count towards the budget but are never evicted
+        manager.set_budget(HistoryBudget {
+            max_bytes: 190,
+            ..Default::default()
+        });
+        let history = manager.get_history();
+        assert_eq!(history.len(), 3);
+        assert!(history.iter().all(|item| !item.preview.starts_with('a')));
+
+        // The newest item is kept even when it alone exceeds a budget
+        manager.set_budget(HistoryBudget {
+            max_text_bytes: 1,
+            ..Default::default()
+        });
+        assert_eq!(manager.get_history().len(), 2);
+        assert_eq!(manager.get_history_stats().total.bytes, 140);
+    }
+
+    #[test]
+    fn test_changed_passphrase_file_rekeys_history() {
+        let data_dir = test_support::temp_dir("rekey");
+        let passphrase = |name: &str, value: &str| {
+            let path = data_dir.join(name);
+            std::fs::write(&path, value).unwrap();
+            EncryptionConfig {
+                enabled: true,
+                passphrase_file: Some(path),
+            }
+        };
+        let (old, new) = (
+            passphrase("old", "correct horse"),
+            passphrase("new", "battery"),
+        );
+        let image = ImageData {
+            width: 4,
+            height: 4,
+            bytes: vec![200; 64].into(),
+        };
+        let image = image_policy::encode_image(&image, 9, &ImagePolicy::default()).unwrap();
+
+        let mut manager = ClipboardManager::new(&data_dir, 10, &old);
+        manager.add_text("kept".to_string(), None, false, None);
+        let id = manager.add_image(image, None).unwrap().id;
+        let old_key = manager
+            .get_item(&id)
+            .unwrap()
+            .blob_key()
+            .unwrap()
+            .to_string();
+        manager.configure_encryption(&new).unwrap();
+        // Blob names depend on the key, so the image moved to a new name
+        let new_key = manager
+            .get_item(&id)
+            .unwrap()
+            .blob_key()
+            .unwrap()
+            .to_string();
+        assert_ne!(new_key, old_key);
+        assert!(manager.blobs.size(&old_key).is_err());
+        drop(manager);
+
+        let manager = ClipboardManager::new(&data_dir, 10, &new);
+        assert_eq!(manager.encryption_error(), None);
+        assert_eq!(manager.get_history().len(), 2);
+        assert!(manager.get_image_base64(&id).is_ok());
+        drop(manager);
+
+        // The old key no longer opens it, and that is reported instead of ignored
+        let manager = ClipboardManager::new(&data_dir, 10, &old);
+        assert!(manager.encryption_error().is_some());
+        assert!(manager.get_history().is_empty());
+    }
+}
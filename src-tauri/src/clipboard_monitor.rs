@@ -0,0 +1,616 @@
+//! Clipboard Monitor Module
+//! Records clipboard changes into the history.
+//!
+//! Two threads form a pipeline. The reader waits for the watcher, reads the new content
+//! (slow when the source application is slow to answer) and encodes images, then sends
+//! the result as a `Capture`. The recorder receives captures and inserts them into the
+//! `ClipboardManager`, so UI commands never wait behind clipboard I/O. The reader only
+//! takes the manager lock for a moment to look at settings and recent history.
+
+use crate::active_window::{self, ActiveWindow};
+use crate::clipboard_manager::{
+    calculate_hash, ClipboardContent, ClipboardManager, SelectionKind, SelectionSync,
+};
+use crate::clipboard_targets;
+use crate::clipboard_watcher::{self, ClipboardSnapshot, WatchEvent};
+use crate::copied_files::CopiedFiles;
+use crate::desktop_notification;
+use crate::focus_manager::is_pointer_button_held;
+use crate::image_policy::{self, EncodedImage, ImagePolicy, ImageRejected};
+use crate::selection_owner::{self, SelectionTarget};
+use crate::sensitive_paste;
+use crate::session::is_wayland;
+use arboard::{Clipboard, GetExtLinux, ImageData, LinuxClipboardKind};
+use parking_lot::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// --- Constants ---
+
+/// How often the monitor wakes up without clipboard activity (e.g. to expire secrets)
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Quiet period before a PRIMARY selection is recorded, so a selection being
+/// dragged out is not stored once per intermediate state
+const PRIMARY_DEBOUNCE: Duration = Duration::from_millis(400);
+
+/// How often age limits and the daily purge time are checked
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Captures waiting for the recorder before the reader blocks
+const CAPTURE_QUEUE_LEN: usize = 16;
+
+// --- Captures ---
+
+/// New clipboard content, read and prepared without the manager lock
+#[derive(Debug)]
+pub enum Capture {
+    Files {
+        files: CopiedFiles,
+        source: Option<ActiveWindow>,
+    },
+    Text {
+        text: String,
+        html: Option<String>,
+        /// Flagged as a secret by a password manager; no other formats are kept
+        concealed: bool,
+        formats: Vec<SelectionTarget>,
+        source: Option<ActiveWindow>,
+    },
+    Image {
+        image: EncodedImage,
+        formats: Vec<SelectionTarget>,
+        source: Option<ActiveWindow>,
+    },
+    /// Settled PRIMARY selection text
+    Primary {
+        text: String,
+        source: Option<ActiveWindow>,
+    },
+    /// The CLIPBOARD owner exited. Queued like content, so a copy made just before
+    /// is recorded first and is what gets offered again.
+    OwnerGone,
+}
+
+/// Starts the reader and recorder threads.
+pub fn start(manager: Arc<Mutex<ClipboardManager>>) {
+    let (sender, receiver) = mpsc::sync_channel(CAPTURE_QUEUE_LEN);
+
+    let recorder_manager = manager.clone();
+    std::thread::spawn(move || record_captures(&recorder_manager, receiver));
+    std::thread::spawn(move || Reader::new(manager, sender).run());
+}
+
+// --- Recorder ---
+
+/// Inserts captures into the history; the only place the monitor writes to the manager.
+/// Also applies the time-based rules: secret expiry, history retention, the end
+/// of a timed capture pause and taking pasted content off the clipboard again.
+fn record_captures(manager: &Mutex<ClipboardManager>, captures: Receiver<Capture>) {
+    let mut next_retention = Instant::now();
+    loop {
+        let capture = match captures.recv_timeout(IDLE_TIMEOUT) {
+            Ok(capture) => Some(capture),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        let due_revert = {
+            let mut manager = manager.lock();
+            let now = chrono::Utc::now();
+            manager.resume_capture_if_due(now);
+            // Drop secrets whose retention period has passed
+            manager.remove_expired();
+            if Instant::now() >= next_retention {
+                manager.enforce_retention();
+                next_retention = Instant::now() + RETENTION_INTERVAL;
+            }
+            // Capture may have been paused while this one was queued
+            if let Some(capture) = capture.filter(|_| !manager.is_capture_paused()) {
+                record(&mut manager, capture);
+            }
+            manager.take_due_revert(now)
+        };
+        // Reads and writes the clipboard, so not under the lock
+        if let Some(pending) = due_revert {
+            sensitive_paste::revert(manager, pending);
+        }
+    }
+}
+
+/// Adds one capture to the history, with the other formats offered alongside it
+pub fn record(manager: &mut ClipboardManager, capture: Capture) {
+    let (item, formats) = match capture {
+        Capture::OwnerGone => {
+            manager.reoffer_clipboard(chrono::Utc::now());
+            return;
+        }
+        Capture::Files { files, source } => (manager.add_files(files, source.as_ref()), Vec::new()),
+        Capture::Text {
+            text,
+            html,
+            concealed,
+            formats,
+            source,
+        } => (
+            manager.add_text(text, html, concealed, source.as_ref()),
+            // Secrets are kept as plain text only
+            if concealed { Vec::new() } else { formats },
+        ),
+        Capture::Image {
+            image,
+            formats,
+            source,
+        } => (manager.add_image(image, source.as_ref()), formats),
+        Capture::Primary { text, source } => {
+            (manager.add_primary_text(text, source.as_ref()), Vec::new())
+        }
+    };
+
+    if let Some(item) = item.filter(|_| !formats.is_empty()) {
+        manager.attach_formats(&item.id, formats);
+    }
+}
+
+// --- Reader ---
+
+/// PRIMARY selection text waiting for the debounce period to pass
+struct PendingPrimary {
+    changed_at: Instant,
+    hash: u64,
+    text: String,
+    /// Whether the text must be read again when settled (X11 sources may update
+    /// a selection they already own without any new event)
+    reread: bool,
+}
+
+/// Settings the reader needs, copied out of the manager once per wakeup
+struct ReaderSettings {
+    watch_primary: bool,
+    capture_primary: bool,
+    selection_sync: SelectionSync,
+    image_policy: ImagePolicy,
+}
+
+impl ReaderSettings {
+    fn of(manager: &ClipboardManager) -> Self {
+        Self {
+            watch_primary: manager.watches_primary(),
+            capture_primary: manager.captures_primary(),
+            selection_sync: manager.selection_sync(),
+            image_policy: manager.image_policy(),
+        }
+    }
+}
+
+/// Waits for clipboard changes and turns them into captures
+struct Reader {
+    manager: Arc<Mutex<ClipboardManager>>,
+    captures: SyncSender<Capture>,
+    last_text_hash: Option<u64>,
+    last_image_hash: Option<u64>,
+    last_files_hash: Option<u64>,
+    last_primary_hash: Option<u64>,
+    pending_primary: Option<PendingPrimary>,
+}
+
+impl Reader {
+    fn new(manager: Arc<Mutex<ClipboardManager>>, captures: SyncSender<Capture>) -> Self {
+        Self {
+            manager,
+            captures,
+            last_text_hash: None,
+            last_image_hash: None,
+            last_files_hash: None,
+            last_primary_hash: None,
+            pending_primary: None,
+        }
+    }
+
+    fn run(mut self) {
+        let mut watcher = clipboard_watcher::create_watcher();
+
+        loop {
+            let timeout = match &self.pending_primary {
+                Some(pending) => PRIMARY_DEBOUNCE
+                    .saturating_sub(pending.changed_at.elapsed())
+                    .min(IDLE_TIMEOUT),
+                None => IDLE_TIMEOUT,
+            };
+            let event = match watcher.wait(timeout) {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!(
+                        "[ClipboardMonitor] {} watcher failed: {}. Switching to polling.",
+                        watcher.name(),
+                        e
+                    );
+                    watcher = Box::new(clipboard_watcher::PollingWatcher::default());
+                    continue;
+                }
+            };
+
+            let settings = ReaderSettings::of(&self.manager.lock());
+            watcher.set_watch_primary(settings.watch_primary);
+
+            match &event {
+                WatchEvent::Changed(SelectionKind::Primary) => {
+                    self.update_pending_primary(read_primary_text(), true);
+                }
+                WatchEvent::Offer(SelectionKind::Primary, snapshot) => {
+                    self.update_pending_primary(snapshot.text.clone(), false);
+                }
+                _ => {}
+            }
+
+            if !settings.watch_primary {
+                self.pending_primary = None;
+            } else if let Some(pending) = self.pending_primary.take() {
+                if pending.changed_at.elapsed() < PRIMARY_DEBOUNCE {
+                    self.pending_primary = Some(pending);
+                } else if !is_wayland() && is_pointer_button_held() {
+                    // Still dragging out the selection: wait for the button to be released
+                    self.pending_primary = Some(PendingPrimary {
+                        changed_at: Instant::now(),
+                        ..pending
+                    });
+                } else {
+                    self.read_primary(&settings, pending);
+                }
+            }
+
+            // Contents delivered by the watcher are used as-is, otherwise read through arboard
+            match event {
+                WatchEvent::Changed(SelectionKind::Clipboard) => {
+                    self.read_clipboard(&settings, None)
+                }
+                WatchEvent::Offer(SelectionKind::Clipboard, snapshot) => {
+                    self.read_clipboard(&settings, Some(snapshot))
+                }
+                WatchEvent::OwnerGone(SelectionKind::Clipboard) => self.send(Capture::OwnerGone),
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&self, capture: Capture) {
+        if self.captures.send(capture).is_err() {
+            eprintln!("[ClipboardMonitor] Recorder stopped, dropping capture");
+        }
+    }
+
+    /// Whether new content must not be recorded: capture is paused, or it comes
+    /// from an excluded application. Hashes are still updated, so content copied
+    /// during a pause is not recorded once capture resumes either.
+    fn is_ignored(&self, source: Option<&ActiveWindow>) -> bool {
+        let manager = self.manager.lock();
+        manager.is_capture_paused() || manager.is_excluded_source(source)
+    }
+
+    fn read_clipboard(
+        &mut self,
+        settings: &ReaderSettings,
+        mut snapshot: Option<ClipboardSnapshot>,
+    ) {
+        // Offered targets; a watcher that read the offer already reports them
+        let targets = match &snapshot {
+            Some(snapshot) => Some(snapshot.mime_types.clone()),
+            None => clipboard_targets::get_clipboard_targets(),
+        };
+
+        // Files (checked first, file managers also offer the paths as text)
+        let files = match &snapshot {
+            Some(snapshot) => snapshot.files.clone(),
+            None => targets.as_deref().and_then(|targets| {
+                CopiedFiles::from_offer(targets, clipboard_targets::read_clipboard_target)
+            }),
+        };
+        if let Some(files) = files {
+            let files_hash = calculate_hash(&files);
+            if Some(files_hash) != self.last_files_hash {
+                self.last_files_hash = Some(files_hash);
+                self.last_text_hash = None;
+                self.last_image_hash = None;
+
+                let source = active_window::get_active_window();
+                if !self.is_ignored(source.as_ref()) {
+                    self.send(Capture::Files { files, source });
+                }
+            }
+            return;
+        }
+
+        // Text
+        let text = match &snapshot {
+            Some(snapshot) => snapshot.text.clone(),
+            None => read_text(),
+        };
+        if let Some(text) = text.filter(|text| !text.is_empty()) {
+            let text_hash = calculate_hash(&text);
+
+            if Some(text_hash) != self.last_text_hash {
+                self.last_text_hash = Some(text_hash);
+                self.last_image_hash = None;
+                self.last_files_hash = None;
+
+                let source = active_window::get_active_window();
+                if self.is_ignored(source.as_ref()) {
+                    return;
+                }
+
+                // Try to get HTML content for rich text support
+                let html = match &snapshot {
+                    Some(snapshot) => snapshot.html.clone(),
+                    None => read_html(),
+                };
+
+                // Password managers flag secrets with a hint target
+                let concealed = match &snapshot {
+                    Some(snapshot) => snapshot.concealed,
+                    None => targets.as_deref().is_some_and(|targets| {
+                        clipboard_targets::is_concealed(
+                            targets,
+                            clipboard_targets::read_clipboard_target,
+                        )
+                    }),
+                };
+
+                // Secrets are never copied into a second selection
+                if !concealed && settings.selection_sync == SelectionSync::ClipboardToPrimary {
+                    match mirror_text(&text, SelectionKind::Primary) {
+                        Ok(()) => self.last_primary_hash = Some(text_hash),
+                        Err(e) => eprintln!("[ClipboardMonitor] {}", e),
+                    }
+                }
+
+                let formats = if concealed {
+                    Vec::new()
+                } else {
+                    let main_type = if html.is_some() { "rich_text" } else { "text" };
+                    read_formats(&mut snapshot, targets.as_deref(), main_type)
+                };
+                self.send(Capture::Text {
+                    text,
+                    html,
+                    concealed,
+                    formats,
+                    source,
+                });
+            }
+        }
+
+        // Image
+        let image = match &mut snapshot {
+            Some(snapshot) => snapshot.image.take().map(|image_data| {
+                let hash = calculate_hash(&image_data.bytes);
+                (image_data, hash)
+            }),
+            None => read_image(),
+        };
+        if let Some((image_data, hash)) = image {
+            if Some(hash) != self.last_image_hash {
+                self.last_image_hash = Some(hash);
+                self.last_text_hash = None;
+                self.last_files_hash = None;
+
+                let source = active_window::get_active_window();
+                if self.is_ignored(source.as_ref()) || self.manager.lock().should_skip_image(hash) {
+                    return;
+                }
+
+                match image_policy::encode_image(&image_data, hash, &settings.image_policy) {
+                    Ok(image) => {
+                        let formats = read_formats(&mut snapshot, targets.as_deref(), "image");
+                        self.send(Capture::Image {
+                            image,
+                            formats,
+                            source,
+                        });
+                    }
+                    Err(e @ ImageRejected::TooLarge { .. }) => {
+                        eprintln!("[ClipboardMonitor] Skipping image: {}", e);
+                        desktop_notification::notify(
+                            "Image not saved to clipboard history",
+                            &format!("The copied {}.", e),
+                        );
+                    }
+                    Err(e) => eprintln!("[ClipboardMonitor] Failed to store image: {}", e),
+                }
+            }
+        }
+    }
+
+    /// Starts (or restarts) the debounce period when the PRIMARY selection changed
+    fn update_pending_primary(&mut self, text: Option<String>, reread: bool) {
+        let Some(text) = text.filter(|text| !text.is_empty()) else {
+            return;
+        };
+        let hash = calculate_hash(&text);
+        // Repeated notifications for the same text do not extend the debounce
+        let unchanged = match &self.pending_primary {
+            Some(pending) => pending.hash == hash,
+            None => self.last_primary_hash == Some(hash),
+        };
+        if !unchanged {
+            self.pending_primary = Some(PendingPrimary {
+                changed_at: Instant::now(),
+                hash,
+                text,
+                reread,
+            });
+        }
+    }
+
+    /// Records and/or mirrors a PRIMARY selection once it has settled
+    fn read_primary(&mut self, settings: &ReaderSettings, pending: PendingPrimary) {
+        let text = if pending.reread {
+            read_primary_text()
+        } else {
+            Some(pending.text)
+        };
+        let Some(text) = text.filter(|text| !text.is_empty()) else {
+            return;
+        };
+        let hash = calculate_hash(&text);
+        if Some(hash) == self.last_primary_hash {
+            return;
+        }
+        self.last_primary_hash = Some(hash);
+
+        let source = active_window::get_active_window();
+        if self.is_ignored(source.as_ref()) {
+            return;
+        }
+
+        if settings.selection_sync == SelectionSync::PrimaryToClipboard {
+            match mirror_text(&text, SelectionKind::Clipboard) {
+                // The resulting CLIPBOARD change must not be recorded a second time
+                Ok(()) => self.last_text_hash = Some(hash),
+                Err(e) => eprintln!("[ClipboardMonitor] {}", e),
+            }
+        }
+
+        if settings.capture_primary {
+            self.send(Capture::Primary { text, source });
+        }
+    }
+}
+
+// --- Clipboard Access ---
+
+fn read_text() -> Option<String> {
+    Clipboard::new().ok()?.get_text().ok()
+}
+
+/// HTML content of the clipboard, if offered
+fn read_html() -> Option<String> {
+    Clipboard::new().ok()?.get().html().ok()
+}
+
+/// Text of the PRIMARY selection, if any
+fn read_primary_text() -> Option<String> {
+    Clipboard::new()
+        .ok()?
+        .get()
+        .clipboard(LinuxClipboardKind::Primary)
+        .text()
+        .ok()
+}
+
+/// Image on the clipboard with the hash of its pixels
+fn read_image() -> Option<(ImageData<'static>, u64)> {
+    let image = Clipboard::new().ok()?.get_image().ok()?;
+    let hash = calculate_hash(&image.bytes);
+    let owned = ImageData {
+        width: image.width,
+        height: image.height,
+        bytes: image.bytes.into_owned().into(),
+    };
+    Some((owned, hash))
+}
+
+/// Copies text into the given selection (used to mirror CLIPBOARD and PRIMARY).
+/// The selection is served by our own owner thread: a temporary arboard instance
+/// would give it up again as soon as it is dropped.
+fn mirror_text(text: &str, target: SelectionKind) -> Result<(), String> {
+    let targets = vec![(
+        selection_owner::UTF8_TEXT_MIME.to_string(),
+        text.as_bytes().to_vec(),
+    )];
+    selection_owner::offer_selection(target, targets)
+        .map_err(|e| format!("Failed to mirror selection: {}", e))
+}
+
+/// The other formats offered together with new content of type `main_type`
+fn read_formats(
+    snapshot: &mut Option<ClipboardSnapshot>,
+    targets: Option<&[String]>,
+    main_type: &str,
+) -> Vec<SelectionTarget> {
+    match snapshot {
+        Some(snapshot) => std::mem::take(&mut snapshot.formats),
+        None => targets
+            .map(|targets| {
+                clipboard_targets::read_clipboard_formats(targets, |mime| {
+                    ClipboardContent::type_represents(main_type, mime)
+                })
+            })
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_manager;
+
+    #[test]
+    fn test_captures_are_recorded_with_their_formats() {
+        let mut manager = test_manager();
+        manager.set_concealed_expiry(30);
+        let rtf = || vec![("text/rtf".to_string(), br"{\rtf1 x}".to_vec())];
+
+        record(
+            &mut manager,
+            Capture::Text {
+                text: "hello".to_string(),
+                html: None,
+                concealed: false,
+                formats: rtf(),
+                source: None,
+            },
+        );
+        record(
+            &mut manager,
+            Capture::Text {
+                text: "hunter2".to_string(),
+                html: None,
+                concealed: true,
+                formats: rtf(),
+                source: None,
+            },
+        );
+        record(
+            &mut manager,
+            Capture::Primary {
+                text: "selected".to_string(),
+                source: None,
+            },
+        );
+
+        let history = manager.get_history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].selection, SelectionKind::Primary);
+        assert!(history[1].formats.is_empty());
+        assert_eq!(history[2].formats.len(), 1);
+    }
+
+    #[test]
+    fn test_image_captures_store_no_other_image_encodings() {
+        let mut manager = test_manager();
+
+        record(
+            &mut manager,
+            Capture::Image {
+                image: EncodedImage {
+                    hash: 1,
+                    bytes: b"downscaled png".to_vec(),
+                    width: 1,
+                    height: 1,
+                    thumbnail: None,
+                },
+                formats: vec![
+                    ("image/bmp".to_string(), b"full size bmp".to_vec()),
+                    ("image/png".to_string(), b"full size png".to_vec()),
+                    ("text/html".to_string(), b"<img>".to_vec()),
+                ],
+                source: None,
+            },
+        );
+
+        let formats = &manager.get_history()[0].formats;
+        assert_eq!(formats.len(), 1);
+        assert_eq!(formats[0].mime_type, "text/html");
+    }
+}
@@ -0,0 +1,525 @@
+//! Clipboard Targets Module
+//! Lists and reads the MIME types (selection targets) offered by the current clipboard owner.
+//!
+//! arboard only hands out text, HTML and image data, so metadata such as the
+//! password-manager hint, or copied files, has to be read from the offer itself:
+//! through `TARGETS` and selection conversion on X11, and the data-control protocol on
+//! Wayland (`wl-paste` on compositors without it).
+
+use crate::selection_owner::SelectionTarget;
+use crate::session;
+
+// --- Constants ---
+
+/// Targets set by KeePassXC, Bitwarden, KDE and others on copied secrets
+const PASSWORD_MANAGER_HINTS: &[&str] = &[
+    "x-kde-passwordManagerHint",
+    "application/x-kde-passwordManagerHint",
+];
+/// Hint value of a secret; other values (e.g. KeePassXC's "public") are not concealed
+const PASSWORD_MANAGER_SECRET: &[u8] = b"secret";
+
+/// Upper bound for the extra representations stored with one item
+pub const MAX_FORMATS_BYTES: usize = 16 * 1024 * 1024;
+
+/// Text targets; the text itself is always kept with the item
+const TEXT_TARGETS: &[&str] = &[
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+    "COMPOUND_TEXT",
+    "text/plain",
+    "text/plain;charset=utf-8",
+];
+
+// --- Public API ---
+
+/// Returns the targets offered for the CLIPBOARD selection, or None if they
+/// could not be determined (no owner, missing tools, timeout).
+pub fn get_clipboard_targets() -> Option<Vec<String>> {
+    if session::is_wayland() {
+        wayland::list_targets()
+    } else {
+        x11::list_targets()
+    }
+}
+
+/// Reads one target (MIME type) of the CLIPBOARD selection.
+/// Returns None if it is not offered or could not be transferred.
+pub fn read_clipboard_target(target: &str) -> Option<Vec<u8>> {
+    if session::is_wayland() {
+        wayland::read_target(target)
+    } else {
+        x11::read_target(target)
+    }
+}
+
+/// Reads the representations worth storing besides text from the CLIPBOARD selection,
+/// leaving out those `covered` by the main content of the item.
+pub fn read_clipboard_formats(
+    targets: &[String],
+    covered: impl Fn(&str) -> bool,
+) -> Vec<SelectionTarget> {
+    if session::is_wayland() {
+        wayland::read_formats(targets, covered)
+    } else {
+        x11::read_formats(targets, covered)
+    }
+}
+
+/// Reads every content target except text and those `covered` by the main content,
+/// skipping those that would exceed `MAX_FORMATS_BYTES` in total. `read` fetches the
+/// bytes of one target.
+pub fn read_formats(
+    targets: &[String],
+    covered: impl Fn(&str) -> bool,
+    mut read: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Vec<SelectionTarget> {
+    let mut remaining = MAX_FORMATS_BYTES;
+    let mut formats = Vec::new();
+    let wanted = |target: &&String| is_format_target(target) && !covered(target);
+    for target in targets.iter().filter(wanted) {
+        let Some(bytes) = read(target) else {
+            continue;
+        };
+        if bytes.is_empty() || bytes.len() > remaining {
+            continue;
+        }
+        remaining -= bytes.len();
+        formats.push((target.clone(), bytes));
+    }
+    formats
+}
+
+/// Whether `target` is content kept and offered again next to the text
+pub fn is_format_target(target: &str) -> bool {
+    // X11 meta targets (TARGETS, TIMESTAMP, MULTIPLE...) and legacy atoms are not MIME types
+    target.contains('/')
+        && !TEXT_TARGETS.contains(&target)
+        && !PASSWORD_MANAGER_HINTS.contains(&target)
+}
+
+/// Whether the offered targets mark the content as a secret (e.g. a password): a
+/// password-manager hint whose value is `secret`, as Klipper checks it. `read`
+/// fetches the bytes of one target.
+pub fn is_concealed(targets: &[String], mut read: impl FnMut(&str) -> Option<Vec<u8>>) -> bool {
+    targets
+        .iter()
+        .filter(|target| PASSWORD_MANAGER_HINTS.contains(&target.as_str()))
+        .any(|target| read(target).is_some_and(|value| value == PASSWORD_MANAGER_SECRET))
+}
+
+// --- Wayland ---
+
+#[cfg(target_os = "linux")]
+mod wayland {
+    use super::*;
+    use crate::wayland_data_control::ClipboardReader;
+    use std::process::Command;
+    use std::sync::Once;
+
+    static FALLBACK_LOGGED: Once = Once::new();
+    static WL_PASTE_MISSING_LOGGED: Once = Once::new();
+
+    pub fn list_targets() -> Option<Vec<String>> {
+        match reader() {
+            Some(reader) => Some(reader.mime_types()),
+            None => {
+                let output = wl_paste(&["--list-types"])?;
+                Some(
+                    String::from_utf8_lossy(&output)
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(String::from)
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    pub fn read_target(target: &str) -> Option<Vec<u8>> {
+        match reader() {
+            Some(reader) => reader.read(target),
+            None => wl_paste(&["--type", target]),
+        }
+    }
+
+    pub fn read_formats(
+        targets: &[String],
+        covered: impl Fn(&str) -> bool,
+    ) -> Vec<SelectionTarget> {
+        match reader() {
+            Some(reader) => super::read_formats(targets, covered, |target| reader.read(target)),
+            None => super::read_formats(targets, covered, |target| wl_paste(&["--type", target])),
+        }
+    }
+
+    /// Reader on the data-control protocol; `None` on compositors without one (GNOME),
+    /// where `wl-paste` is used instead
+    fn reader() -> Option<ClipboardReader> {
+        ClipboardReader::new()
+            .map_err(|e| {
+                FALLBACK_LOGGED.call_once(|| {
+                    eprintln!(
+                        "[ClipboardTargets] {}; reading clipboard targets through wl-paste",
+                        e
+                    )
+                })
+            })
+            .ok()
+    }
+
+    fn wl_paste(args: &[&str]) -> Option<Vec<u8>> {
+        let output = match Command::new("wl-paste")
+            .arg("--no-newline")
+            .args(args)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) => {
+                WL_PASTE_MISSING_LOGGED.call_once(|| {
+                    eprintln!(
+                        "[ClipboardTargets] Failed to run wl-paste (is wl-clipboard installed?): {}",
+                        e
+                    )
+                });
+                return None;
+            }
+        };
+
+        // wl-paste exits non-zero when the clipboard is empty
+        output.status.success().then_some(output.stdout)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod wayland {
+    pub use super::x11::*;
+}
+
+// --- X11 ---
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt, CreateWindowAux, EventMask, GetPropertyReply, Property,
+        Window, WindowClass,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+
+    /// Owners normally answer within a few milliseconds
+    const CONVERT_TIMEOUT: Duration = Duration::from_millis(200);
+    const POLL_INTERVAL: Duration = Duration::from_millis(5);
+    /// Time the owner gets to deliver each chunk of an INCR transfer
+    const INCR_CHUNK_TIMEOUT: Duration = Duration::from_secs(1);
+    /// Largest INCR transfer accepted
+    const MAX_INCR_BYTES: usize = 64 * 1024 * 1024;
+
+    pub fn list_targets() -> Option<Vec<String>> {
+        match SelectionReader::new().and_then(|reader| reader.targets()) {
+            Ok(targets) => targets,
+            Err(e) => {
+                eprintln!("[ClipboardTargets] Failed to query X11 targets: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn read_formats(
+        targets: &[String],
+        covered: impl Fn(&str) -> bool,
+    ) -> Vec<super::SelectionTarget> {
+        match SelectionReader::new() {
+            Ok(reader) => super::read_formats(targets, covered, |target| {
+                reader.read(target).ok().flatten()
+            }),
+            Err(e) => {
+                eprintln!("[ClipboardTargets] Failed to read X11 formats: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn read_target(target: &str) -> Option<Vec<u8>> {
+        match SelectionReader::new().and_then(|reader| reader.read(target)) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!(
+                    "[ClipboardTargets] Failed to read X11 target {}: {}",
+                    target, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Hidden window that CLIPBOARD conversions are delivered to
+    struct SelectionReader {
+        conn: RustConnection,
+        window: Window,
+        clipboard: Atom,
+        property: Atom,
+    }
+
+    impl SelectionReader {
+        fn new() -> Result<Self, String> {
+            let (conn, screen_num) =
+                x11rb::connect(None).map_err(|e| format!("X11 connect failed: {}", e))?;
+            let root = conn.setup().roots[screen_num].root;
+
+            let clipboard = intern(&conn, "CLIPBOARD")?;
+            let property = intern(&conn, "WIN11_CLIPBOARD_TARGETS")?;
+
+            // Selection data is delivered to a window property, so we need a (hidden) window.
+            // Property changes announce the chunks of INCR transfers.
+            let window = conn.generate_id().map_err(|e| e.to_string())?;
+            conn.create_window(
+                0,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                0,
+                &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )
+            .map_err(|e| format!("Failed to create window: {}", e))?;
+
+            Ok(Self {
+                conn,
+                window,
+                clipboard,
+                property,
+            })
+        }
+
+        fn targets(&self) -> Result<Option<Vec<String>>, String> {
+            let Some(reply) = self.convert(intern(&self.conn, "TARGETS")?)? else {
+                return Ok(None);
+            };
+            let atoms: Vec<u32> = match reply.value32() {
+                Some(atoms) => atoms.collect(),
+                None => return Ok(Some(Vec::new())),
+            };
+
+            // Send all name lookups before waiting on any reply
+            let cookies: Vec<_> = atoms
+                .iter()
+                .filter_map(|&atom| self.conn.get_atom_name(atom).ok())
+                .collect();
+            let names = cookies
+                .into_iter()
+                .filter_map(|cookie| cookie.reply().ok())
+                .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+                .collect();
+            Ok(Some(names))
+        }
+
+        fn read(&self, target: &str) -> Result<Option<Vec<u8>>, String> {
+            let Some(reply) = self.convert(intern(&self.conn, target)?)? else {
+                return Ok(None);
+            };
+            if reply.type_ == intern(&self.conn, "INCR")? {
+                return self.read_incr();
+            }
+            Ok(Some(reply.value))
+        }
+
+        /// Collects the chunks of a large transfer (ICCCM INCR protocol). Deleting the INCR
+        /// property in `convert` asked the owner to start; each chunk is taken by deleting
+        /// it again, and an empty chunk ends the transfer.
+        fn read_incr(&self) -> Result<Option<Vec<u8>>, String> {
+            let mut bytes = Vec::new();
+            loop {
+                let new_value = self.wait_for(INCR_CHUNK_TIMEOUT, |event| {
+                    matches!(event, Event::PropertyNotify(event)
+                        if event.window == self.window
+                            && event.atom == self.property
+                            && event.state == Property::NEW_VALUE)
+                })?;
+                if new_value.is_none() {
+                    return Ok(None);
+                }
+
+                let chunk = self
+                    .conn
+                    .get_property(
+                        true,
+                        self.window,
+                        self.property,
+                        AtomEnum::ANY,
+                        0,
+                        u32::MAX / 4,
+                    )
+                    .map_err(|e| e.to_string())?
+                    .reply()
+                    .map_err(|e| format!("Failed to read selection chunk: {}", e))?;
+                if chunk.value.is_empty() {
+                    return Ok(Some(bytes));
+                }
+                bytes.extend_from_slice(&chunk.value);
+                if bytes.len() > MAX_INCR_BYTES {
+                    return Ok(None);
+                }
+            }
+        }
+
+        /// Asks the owner to convert the selection and returns the resulting property
+        fn convert(&self, target: Atom) -> Result<Option<GetPropertyReply>, String> {
+            self.conn
+                .convert_selection(
+                    self.window,
+                    self.clipboard,
+                    target,
+                    self.property,
+                    x11rb::CURRENT_TIME,
+                )
+                .map_err(|e| format!("Failed to convert selection: {}", e))?;
+            self.conn
+                .flush()
+                .map_err(|e| format!("Flush failed: {}", e))?;
+
+            let notify = self.wait_for(CONVERT_TIMEOUT, |event| {
+                matches!(event, Event::SelectionNotify(event) if event.requestor == self.window)
+            })?;
+            match notify {
+                // Property NONE means there is no owner or it refused the request
+                Some(Event::SelectionNotify(event))
+                    if event.property != u32::from(AtomEnum::NONE) => {}
+                _ => return Ok(None),
+            }
+
+            self.conn
+                .get_property(
+                    true,
+                    self.window,
+                    self.property,
+                    AtomEnum::ANY,
+                    0,
+                    u32::MAX / 4,
+                )
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map(Some)
+                .map_err(|e| format!("Failed to read selection property: {}", e))
+        }
+
+        /// Waits for the first event accepted by `wanted`, discarding others.
+        /// Returns None once `timeout` passed.
+        fn wait_for(
+            &self,
+            timeout: Duration,
+            wanted: impl Fn(&Event) -> bool,
+        ) -> Result<Option<Event>, String> {
+            let deadline = Instant::now() + timeout;
+            loop {
+                match self.conn.poll_for_event().map_err(|e| e.to_string())? {
+                    Some(event) if wanted(&event) => return Ok(Some(event)),
+                    Some(_) => continue,
+                    None if Instant::now() >= deadline => return Ok(None),
+                    None => thread::sleep(POLL_INTERVAL),
+                }
+            }
+        }
+    }
+
+    impl Drop for SelectionReader {
+        fn drop(&mut self) {
+            let _ = self.conn.destroy_window(self.window);
+            let _ = self.conn.flush();
+        }
+    }
+
+    fn intern(conn: &impl Connection, name: &str) -> Result<Atom, String> {
+        conn.intern_atom(false, name.as_bytes())
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map(|reply| reply.atom)
+            .map_err(|e| format!("Failed to intern {}: {}", name, e))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod x11 {
+    pub fn list_targets() -> Option<Vec<String>> {
+        None
+    }
+
+    pub fn read_target(_target: &str) -> Option<Vec<u8>> {
+        None
+    }
+
+    pub fn read_formats(
+        _targets: &[String],
+        _covered: impl Fn(&str) -> bool,
+    ) -> Vec<super::SelectionTarget> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_concealed() {
+        let hint = |value: &'static [u8]| move |_: &str| Some(value.to_vec());
+        let plain = vec!["text/plain".to_string(), "UTF8_STRING".to_string()];
+        assert!(!is_concealed(&plain, hint(b"secret")));
+
+        let hinted = vec![
+            "text/plain".to_string(),
+            "x-kde-passwordManagerHint".to_string(),
+        ];
+        assert!(is_concealed(&hinted, hint(b"secret")));
+        // The hint alone is not enough, its value decides
+        assert!(!is_concealed(&hinted, hint(b"public")));
+        assert!(!is_concealed(&hinted, |_| None));
+    }
+
+    #[test]
+    fn test_read_formats_skips_text_and_respects_budget() {
+        let targets: Vec<String> = ["TARGETS", "UTF8_STRING", "text/rtf", "image/svg+xml"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        let formats = read_formats(
+            &targets,
+            |_| false,
+            |target| match target {
+                "text/rtf" => Some(b"{\\rtf1}".to_vec()),
+                "image/svg+xml" => Some(vec![0; MAX_FORMATS_BYTES]),
+                _ => Some(b"unexpected".to_vec()),
+            },
+        );
+        assert_eq!(formats.len(), 1);
+        assert_eq!(formats[0].0, "text/rtf");
+    }
+
+    #[test]
+    fn test_read_formats_does_not_read_covered_targets() {
+        let targets: Vec<String> = ["image/png", "image/bmp", "text/html", "text/rtf"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        let mut read = Vec::new();
+        let formats = read_formats(
+            &targets,
+            |target| target.starts_with("image/"),
+            |target| {
+                read.push(target.to_string());
+                Some(b"bytes".to_vec())
+            },
+        );
+        assert_eq!(read, ["text/html", "text/rtf"]);
+        assert_eq!(formats.len(), 2);
+    }
+}
@@ -0,0 +1,260 @@
+//! Clipboard Watcher Module
+//! Tells the monitoring loop when the clipboard may have changed.
+//!
+//! On X11 the XFixes extension delivers an event whenever the CLIPBOARD selection
+//! changes owner, so the loop sleeps until something is actually copied. On Wayland
+//! the data-control protocols deliver the new offer itself (see `wayland_data_control`).
+//! Polling remains as a fallback for sessions where no event source is available.
+
+use crate::clipboard_manager::SelectionKind;
+use crate::copied_files::CopiedFiles;
+use crate::selection_owner::SelectionTarget;
+use arboard::ImageData;
+use std::thread;
+use std::time::Duration;
+
+// --- Constants ---
+
+/// Interval of the polling fallback
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// --- Watcher Trait ---
+
+/// Clipboard contents already read by the watcher
+#[derive(Debug, Clone, Default)]
+pub struct ClipboardSnapshot {
+    /// Every MIME type the source offered
+    pub mime_types: Vec<String>,
+    pub text: Option<String>,
+    pub html: Option<String>,
+    pub image: Option<ImageData<'static>>,
+    /// Files copied in a file manager
+    pub files: Option<CopiedFiles>,
+    /// Other representations (RTF, SVG, vendor formats) within the size budget
+    pub formats: Vec<SelectionTarget>,
+    /// Flagged as a secret by a password manager (see `clipboard_targets::is_concealed`)
+    pub concealed: bool,
+}
+
+/// Result of waiting for clipboard activity
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// The selection (may have) changed and should be read
+    Changed(SelectionKind),
+    /// The selection changed and the watcher already read its contents
+    Offer(SelectionKind, ClipboardSnapshot),
+    /// The application owning the selection exited (or dropped it) without a new owner
+    OwnerGone(SelectionKind),
+    /// The timeout passed without any clipboard activity
+    Idle,
+}
+
+/// Source of clipboard change notifications
+pub trait ClipboardWatcher: Send {
+    /// Short name for logging
+    fn name(&self) -> &'static str;
+
+    /// Blocks until the clipboard changes or `timeout` passes.
+    /// An error means the watcher is broken and should be replaced by polling.
+    fn wait(&mut self, timeout: Duration) -> Result<WatchEvent, String>;
+
+    /// Enables reporting of PRIMARY selection changes (off by default)
+    fn set_watch_primary(&mut self, enabled: bool);
+}
+
+/// Picks the best watcher available in this session, falling back to polling.
+pub fn create_watcher() -> Box<dyn ClipboardWatcher> {
+    #[cfg(target_os = "linux")]
+    if crate::session::is_wayland() {
+        match crate::wayland_data_control::DataControlWatcher::new() {
+            Ok(watcher) => return Box::new(watcher),
+            Err(e) => eprintln!("[ClipboardWatcher] Wayland data-control unavailable: {}", e),
+        }
+    } else {
+        match xfixes::XFixesWatcher::new() {
+            Ok(watcher) => {
+                eprintln!("[ClipboardWatcher] Using XFixes selection events");
+                return Box::new(watcher);
+            }
+            Err(e) => eprintln!("[ClipboardWatcher] XFixes unavailable: {}", e),
+        }
+    }
+
+    eprintln!(
+        "[ClipboardWatcher] Falling back to polling every {}ms",
+        POLL_INTERVAL.as_millis()
+    );
+    Box::new(PollingWatcher::default())
+}
+
+/// Waits until `fd` is readable or the timeout passes. Returns whether it is readable.
+#[cfg(target_os = "linux")]
+pub(crate) fn poll_readable(
+    fd: std::os::unix::io::RawFd,
+    timeout: Duration,
+) -> Result<bool, String> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    // SAFETY: `pollfd` is a valid pollfd for the duration of the call
+    let result = unsafe { libc::poll(&mut pollfd, 1, timeout_ms) };
+    if result < 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(format!("poll failed: {}", err));
+        }
+        return Ok(false);
+    }
+    Ok(result > 0)
+}
+
+// --- Polling Fallback ---
+
+/// Reports a (possible) change every interval; the caller compares contents itself
+pub struct PollingWatcher {
+    interval: Duration,
+    watch_primary: bool,
+    /// PRIMARY is reported right after each CLIPBOARD tick
+    primary_due: bool,
+}
+
+impl Default for PollingWatcher {
+    fn default() -> Self {
+        Self {
+            interval: POLL_INTERVAL,
+            watch_primary: false,
+            primary_due: false,
+        }
+    }
+}
+
+impl ClipboardWatcher for PollingWatcher {
+    fn name(&self) -> &'static str {
+        "polling"
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<WatchEvent, String> {
+        if self.primary_due {
+            self.primary_due = false;
+            return Ok(WatchEvent::Changed(SelectionKind::Primary));
+        }
+        if timeout < self.interval {
+            thread::sleep(timeout);
+            return Ok(WatchEvent::Idle);
+        }
+        thread::sleep(self.interval);
+        self.primary_due = self.watch_primary;
+        Ok(WatchEvent::Changed(SelectionKind::Clipboard))
+    }
+
+    fn set_watch_primary(&mut self, enabled: bool) {
+        self.watch_primary = enabled;
+    }
+}
+
+// --- X11 XFixes ---
+
+#[cfg(target_os = "linux")]
+mod xfixes {
+    use super::{ClipboardWatcher, SelectionKind, WatchEvent};
+    use std::os::unix::io::AsRawFd;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEvent, SelectionEventMask};
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+
+    pub struct XFixesWatcher {
+        conn: RustConnection,
+        watch_primary: bool,
+    }
+
+    impl XFixesWatcher {
+        pub fn new() -> Result<Self, String> {
+            let (conn, screen_num) =
+                x11rb::connect(None).map_err(|e| format!("X11 connect failed: {}", e))?;
+            let root = conn.setup().roots[screen_num].root;
+
+            // The version must be negotiated before any other XFixes request
+            conn.xfixes_query_version(5, 0)
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map_err(|e| format!("XFixes query failed: {}", e))?;
+
+            let clipboard = conn
+                .intern_atom(false, b"CLIPBOARD")
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map_err(|e| format!("Failed to intern CLIPBOARD: {}", e))?
+                .atom;
+
+            // PRIMARY events are always selected and filtered here, which is cheaper
+            // than re-selecting input whenever the setting changes
+            for selection in [clipboard, u32::from(AtomEnum::PRIMARY)] {
+                conn.xfixes_select_selection_input(
+                    root,
+                    selection,
+                    SelectionEventMask::SET_SELECTION_OWNER
+                        | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                        | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+                )
+                .map_err(|e| e.to_string())?
+                .check()
+                .map_err(|e| format!("Failed to select selection input: {}", e))?;
+            }
+
+            Ok(Self {
+                conn,
+                watch_primary: false,
+            })
+        }
+    }
+
+    impl ClipboardWatcher for XFixesWatcher {
+        fn name(&self) -> &'static str {
+            "xfixes"
+        }
+
+        fn wait(&mut self, timeout: Duration) -> Result<WatchEvent, String> {
+            let deadline = Instant::now() + timeout;
+            loop {
+                // Drain buffered events first; poll() only sees unread socket data
+                while let Some(event) = self
+                    .conn
+                    .poll_for_event()
+                    .map_err(|e| format!("X11 connection lost: {}", e))?
+                {
+                    if let Event::XfixesSelectionNotify(event) = event {
+                        if event.selection != u32::from(AtomEnum::PRIMARY) {
+                            let owner_gone = event.subtype
+                                == SelectionEvent::SELECTION_CLIENT_CLOSE
+                                || event.subtype == SelectionEvent::SELECTION_WINDOW_DESTROY;
+                            return Ok(if owner_gone {
+                                WatchEvent::OwnerGone(SelectionKind::Clipboard)
+                            } else {
+                                WatchEvent::Changed(SelectionKind::Clipboard)
+                            });
+                        }
+                        if self.watch_primary {
+                            return Ok(WatchEvent::Changed(SelectionKind::Primary));
+                        }
+                    }
+                }
+
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(WatchEvent::Idle);
+                }
+                super::poll_readable(self.conn.stream().as_raw_fd(), deadline - now)?;
+            }
+        }
+
+        fn set_watch_primary(&mut self, enabled: bool) {
+            self.watch_primary = enabled;
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Copied Files Module
+//! Reads and rebuilds the clipboard targets file managers use for copied files.
+//!
+//! Nautilus, Thunar, Nemo and other GTK file managers offer `x-special/gnome-copied-files`
+//! ("copy" or "cut" followed by one URI per line). Dolphin marks a cut with
+//! `application/x-kde-cutselection`. All of them also offer the standard `text/uri-list`.
+
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+
+// --- Constants ---
+
+pub const URI_LIST_MIME: &str = "text/uri-list";
+pub const GNOME_COPIED_FILES_MIME: &str = "x-special/gnome-copied-files";
+pub const KDE_CUT_SELECTION_MIME: &str = "application/x-kde-cutselection";
+const TEXT_MIME: &str = "text/plain;charset=utf-8";
+const FILE_URI_PREFIX: &str = "file://";
+
+// --- Data Structures ---
+
+/// Whether pasting copies or moves the files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileOperation {
+    #[default]
+    Copy,
+    Cut,
+}
+
+/// Files and folders copied in a file manager
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CopiedFiles {
+    pub uris: Vec<String>,
+    pub operation: FileOperation,
+}
+
+impl CopiedFiles {
+    /// Reads copied files from an offer, or None if it holds no files.
+    /// `read` fetches the bytes of one of the offered `mime_types`.
+    pub fn from_offer(
+        mime_types: &[String],
+        read: impl Fn(&str) -> Option<Vec<u8>>,
+    ) -> Option<Self> {
+        let offered = |mime: &str| mime_types.iter().any(|m| m == mime);
+
+        // The GNOME target carries the operation together with the URIs
+        if offered(GNOME_COPIED_FILES_MIME) {
+            let files = read(GNOME_COPIED_FILES_MIME)
+                .and_then(|bytes| parse_gnome_copied_files(&String::from_utf8_lossy(&bytes)));
+            if files.is_some() {
+                return files;
+            }
+        }
+
+        if !offered(URI_LIST_MIME) {
+            return None;
+        }
+        let uris = parse_uri_list(&String::from_utf8_lossy(&read(URI_LIST_MIME)?));
+        if uris.is_empty() {
+            return None;
+        }
+        let cut = offered(KDE_CUT_SELECTION_MIME)
+            && read(KDE_CUT_SELECTION_MIME).is_some_and(|bytes| bytes.first() == Some(&b'1'));
+        Some(Self {
+            uris,
+            operation: if cut {
+                FileOperation::Cut
+            } else {
+                FileOperation::Copy
+            },
+        })
+    }
+
+    /// Every target file managers (and text editors) read when the files are pasted
+    pub fn to_targets(&self) -> Vec<(String, Vec<u8>)> {
+        let (operation, kde_cut) = match self.operation {
+            FileOperation::Copy => ("copy", "0"),
+            FileOperation::Cut => ("cut", "1"),
+        };
+        let gnome: String = std::iter::once(operation)
+            .chain(self.uris.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("\n");
+        // RFC 2483: lines of a uri-list end in CRLF
+        let uri_list: String = self.uris.iter().map(|uri| format!("{}\r\n", uri)).collect();
+        let paths = self
+            .uris
+            .iter()
+            .map(|uri| display_path(uri))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        vec![
+            (GNOME_COPIED_FILES_MIME.to_string(), gnome.into_bytes()),
+            (URI_LIST_MIME.to_string(), uri_list.into_bytes()),
+            (
+                KDE_CUT_SELECTION_MIME.to_string(),
+                kde_cut.as_bytes().to_vec(),
+            ),
+            (TEXT_MIME.to_string(), paths.into_bytes()),
+        ]
+    }
+
+    /// Short description such as "report.pdf" or "report.pdf and 2 more"
+    pub fn preview(&self) -> String {
+        let first = self
+            .uris
+            .first()
+            .map(|uri| file_name(uri))
+            .unwrap_or_default();
+        match self.uris.len() {
+            0 | 1 => first,
+            n => format!("{} and {} more", first, n - 1),
+        }
+    }
+}
+
+// --- Helpers ---
+
+/// Local path of a `file://` URI (percent-decoded); other URIs are returned unchanged
+pub fn display_path(uri: &str) -> String {
+    match uri.strip_prefix(FILE_URI_PREFIX) {
+        Some(path) => percent_decode_str(path).decode_utf8_lossy().into_owned(),
+        None => uri.to_string(),
+    }
+}
+
+fn file_name(uri: &str) -> String {
+    let path = display_path(uri);
+    let trimmed = path.trim_end_matches('/');
+    trimmed
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(trimmed)
+        .to_string()
+}
+
+fn parse_uri_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+fn parse_gnome_copied_files(text: &str) -> Option<CopiedFiles> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let operation = match lines.next()? {
+        "copy" => FileOperation::Copy,
+        "cut" => FileOperation::Cut,
+        _ => return None,
+    };
+    let uris: Vec<String> = lines.map(String::from).collect();
+    (!uris.is_empty()).then_some(CopiedFiles { uris, operation })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn read_from(targets: &[(String, Vec<u8>)]) -> (Vec<String>, HashMap<String, Vec<u8>>) {
+        let mime_types = targets.iter().map(|(mime, _)| mime.clone()).collect();
+        (mime_types, targets.iter().cloned().collect())
+    }
+
+    #[test]
+    fn test_gnome_copied_files_round_trip() {
+        let files = CopiedFiles {
+            uris: vec![
+                "file:///home/user/My%20Report.pdf".to_string(),
+                "file:///home/user/photos/".to_string(),
+            ],
+            operation: FileOperation::Cut,
+        };
+        let (mime_types, data) = read_from(&files.to_targets());
+        let parsed = CopiedFiles::from_offer(&mime_types, |mime| data.get(mime).cloned());
+        assert_eq!(parsed, Some(files.clone()));
+        assert_eq!(files.preview(), "My Report.pdf and 1 more");
+    }
+
+    #[test]
+    fn test_uri_list_with_kde_cut_marker() {
+        let (mime_types, data) = read_from(&[
+            (
+                URI_LIST_MIME.to_string(),
+                b"# comment\r\nfile:///tmp/a.txt\r\n".to_vec(),
+            ),
+            (KDE_CUT_SELECTION_MIME.to_string(), b"1".to_vec()),
+        ]);
+        let parsed = CopiedFiles::from_offer(&mime_types, |mime| data.get(mime).cloned()).unwrap();
+        assert_eq!(parsed.uris, vec!["file:///tmp/a.txt".to_string()]);
+        assert_eq!(parsed.operation, FileOperation::Cut);
+    }
+
+    #[test]
+    fn test_plain_text_is_not_files() {
+        let mime_types = vec!["text/plain".to_string()];
+        assert!(CopiedFiles::from_offer(&mime_types, |_| Some(b"hello".to_vec())).is_none());
+    }
+}
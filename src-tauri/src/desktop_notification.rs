@@ -0,0 +1,58 @@
+//! Desktop Notification Module
+//! Shows short messages through the freedesktop notification service.
+//!
+//! Used for events the user would otherwise not notice because the popup is hidden,
+//! such as a copied image that was not recorded.
+
+const APP_NAME: &str = "Clipboard History";
+const APP_ICON: &str = "edit-paste";
+
+/// Shows a notification in the background. Failures are only logged.
+pub fn notify(summary: &str, body: &str) {
+    #[cfg(target_os = "linux")]
+    {
+        let (summary, body) = (summary.to_string(), body.to_string());
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    eprintln!("[Notification] Failed to start runtime: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = runtime.block_on(send(&summary, &body)) {
+                eprintln!("[Notification] Failed to show '{}': {}", summary, e);
+            }
+        });
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    eprintln!("[Notification] {}: {}", summary, body);
+}
+
+#[cfg(target_os = "linux")]
+async fn send(summary: &str, body: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use std::collections::HashMap;
+    use zbus::zvariant::Value;
+    use zbus::Connection;
+
+    let connection = Connection::session().await?;
+    let actions: Vec<&str> = Vec::new();
+    let hints: HashMap<&str, Value> = HashMap::new();
+    connection
+        .call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            // app name, replaced id, icon, summary, body, actions, hints, timeout (-1 = default)
+            &(
+                APP_NAME, 0u32, APP_ICON, summary, body, actions, hints, -1i32,
+            ),
+        )
+        .await?;
+    Ok(())
+}
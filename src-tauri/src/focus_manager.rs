@@ -11,7 +11,9 @@ use std::time::{Duration, Instant};
 #[cfg(target_os = "linux")]
 use x11rb::connection::Connection;
 #[cfg(target_os = "linux")]
-use x11rb::protocol::xproto::{AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, InputFocus};
+use x11rb::protocol::xproto::{
+    AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, InputFocus, KeyButMask,
+};
 
 /// Time to wait after restoring focus before allowing the paste to proceed
 #[cfg(target_os = "linux")]
@@ -76,6 +78,23 @@ pub fn get_focused_window() -> Option<u32> {
     Some(reply.focus)
 }
 
+/// Whether a mouse button is currently held down, e.g. while a selection is being dragged out
+#[cfg(target_os = "linux")]
+pub fn is_pointer_button_held() -> bool {
+    let Ok((conn, screen_num)) = x11rb::connect(None) else {
+        return false;
+    };
+    let root = conn.setup().roots[screen_num].root;
+    conn.query_pointer(root)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .is_some_and(|reply| {
+            reply
+                .mask
+                .intersects(KeyButMask::BUTTON1 | KeyButMask::BUTTON2 | KeyButMask::BUTTON3)
+        })
+}
+
 /// Helper to establish X11 connection
 #[cfg(target_os = "linux")]
 fn get_x11_connection() -> Result<impl Connection, String> {
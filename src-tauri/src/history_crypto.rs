@@ -0,0 +1,451 @@
+//! History Crypto Module
+//! Authenticated encryption (ChaCha20-Poly1305) for clipboard history at rest.
+//!
+//! The 256-bit key is kept in the Secret Service keyring (GNOME Keyring, KWallet,
+//! KeePassXC, ...). Headless setups can point to a passphrase file instead; the key
+//! is then derived with Argon2 using a random salt kept in the history store.
+
+use crate::history_store::HistoryStore;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+// --- Constants ---
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Prefix of every encrypted payload, so plaintext and ciphertext can coexist during migration
+const ENCRYPTED_MAGIC: &[u8] = b"W11CHE1";
+
+/// Context for deriving the blob naming key from the history key
+const BLOB_NAME_CONTEXT: &[u8] = b"win11-clipboard-history blob names";
+
+/// Known plaintext used to detect a wrong key before touching any item
+const KEY_CHECK_PLAINTEXT: &[u8] = b"win11-clipboard-history";
+
+const META_ENCRYPTION: &str = "encryption";
+const META_KDF_SALT: &str = "kdf_salt";
+const META_KEY_CHECK: &str = "key_check";
+const ENCRYPTION_ALGORITHM: &[u8] = b"chacha20poly1305";
+
+/// How long we wait for the keyring (including an unlock prompt) before giving up
+const SECRET_SERVICE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// --- Configuration ---
+
+/// Encryption preferences, derived from `UserSettings`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncryptionConfig {
+    /// Whether history should be encrypted at rest
+    pub enabled: bool,
+    /// Derive the key from this file instead of using the Secret Service keyring
+    pub passphrase_file: Option<PathBuf>,
+}
+
+// --- Cipher ---
+
+/// Symmetric cipher for history payloads. Output layout: magic || nonce || ciphertext+tag
+pub struct HistoryCipher {
+    cipher: ChaCha20Poly1305,
+    /// HMAC key for blob names, so a file name cannot be matched against known content
+    name_key: [u8; KEY_LEN],
+}
+
+impl fmt::Debug for HistoryCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print key material
+        f.write_str("HistoryCipher(..)")
+    }
+}
+
+impl HistoryCipher {
+    pub fn from_key(key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            name_key: hmac_sha256(key, BLOB_NAME_CONTEXT),
+        }
+    }
+
+    pub fn generate_key() -> [u8; KEY_LEN] {
+        ChaCha20Poly1305::generate_key(&mut OsRng).into()
+    }
+
+    /// Returns true if the data was produced by `encrypt`
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(ENCRYPTED_MAGIC)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Encryption failed".to_string())?;
+
+        let mut out = Vec::with_capacity(ENCRYPTED_MAGIC.len() + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(ENCRYPTED_MAGIC);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let body = data
+            .strip_prefix(ENCRYPTED_MAGIC)
+            .ok_or("Data is not encrypted")?;
+        if body.len() < NONCE_LEN {
+            return Err("Encrypted data is truncated".to_string());
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Decryption failed (wrong key or corrupted data)".to_string())
+    }
+
+    /// Keyed digest of some content, used to name its blob
+    pub fn blob_digest(&self, bytes: &[u8]) -> [u8; 32] {
+        hmac_sha256(&self.name_key, bytes)
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+// --- Store Metadata ---
+
+/// Whether the store was last written in encrypted mode
+pub fn is_store_encrypted(store: &mut dyn HistoryStore) -> Result<bool, String> {
+    Ok(store.get_meta(META_ENCRYPTION)?.as_deref() == Some(ENCRYPTION_ALGORITHM))
+}
+
+/// Records the store's encryption mode and the key check for the given cipher
+pub fn record_store_mode(
+    store: &mut dyn HistoryStore,
+    cipher: Option<&HistoryCipher>,
+) -> Result<(), String> {
+    match cipher {
+        Some(cipher) => {
+            let check = cipher.encrypt(KEY_CHECK_PLAINTEXT)?;
+            store.set_meta(META_KEY_CHECK, Some(&check))?;
+            store.set_meta(META_ENCRYPTION, Some(ENCRYPTION_ALGORITHM))
+        }
+        None => {
+            store.set_meta(META_KEY_CHECK, None)?;
+            store.set_meta(META_ENCRYPTION, None)
+        }
+    }
+}
+
+/// Obtains the key for this store and verifies it against the stored key check.
+pub fn unlock_store(
+    store: &mut dyn HistoryStore,
+    config: &EncryptionConfig,
+) -> Result<Arc<HistoryCipher>, String> {
+    let cipher = store_key(store, config)?;
+    if let Some(check) = store.get_meta(META_KEY_CHECK)? {
+        match cipher.decrypt(&check) {
+            Ok(plain) if plain == KEY_CHECK_PLAINTEXT => {}
+            _ => return Err("Encryption key does not match the stored history".to_string()),
+        }
+    }
+    Ok(cipher)
+}
+
+/// Obtains the key `config` points to without checking it against the stored history,
+/// to re-encrypt the store with it when the key source changes.
+pub fn store_key(
+    store: &mut dyn HistoryStore,
+    config: &EncryptionConfig,
+) -> Result<Arc<HistoryCipher>, String> {
+    let key = match &config.passphrase_file {
+        Some(path) => {
+            let salt = match store.get_meta(META_KDF_SALT)? {
+                Some(salt) => salt,
+                None => {
+                    let mut salt = vec![0u8; SALT_LEN];
+                    OsRng.fill_bytes(&mut salt);
+                    store.set_meta(META_KDF_SALT, Some(&salt))?;
+                    salt
+                }
+            };
+            derive_key_from_passphrase_file(path, &salt)?
+        }
+        None => secret_service::fetch_or_create_key()?,
+    };
+    Ok(Arc::new(HistoryCipher::from_key(&key)))
+}
+
+fn derive_key_from_passphrase_file(
+    path: &std::path::Path,
+    salt: &[u8],
+) -> Result<[u8; KEY_LEN], String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read passphrase file {:?}: {}", path, e))?;
+    let passphrase = content.trim_end_matches(['\r', '\n']);
+    if passphrase.is_empty() {
+        return Err(format!("Passphrase file {:?} is empty", path));
+    }
+
+    let mut key = [0u8; KEY_LEN];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+// --- Secret Service (org.freedesktop.secrets) ---
+
+mod secret_service {
+    use super::{HistoryCipher, KEY_LEN, SECRET_SERVICE_TIMEOUT};
+    use futures_lite::stream::StreamExt;
+    use std::collections::HashMap;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+    use zbus::{Connection, MatchRule, MessageStream};
+
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+    const SERVICE: &str = "org.freedesktop.secrets";
+    const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+    const SERVICE_IFACE: &str = "org.freedesktop.Secret.Service";
+    const COLLECTION_IFACE: &str = "org.freedesktop.Secret.Collection";
+    const PROMPT_IFACE: &str = "org.freedesktop.Secret.Prompt";
+    const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+    const NO_PROMPT: &str = "/";
+
+    const ITEM_LABEL: &str = "Clipboard History encryption key";
+    const ATTR_APPLICATION: (&str, &str) = ("application", "win11-clipboard-history");
+    const ATTR_PURPOSE: (&str, &str) = ("purpose", "history-encryption-key");
+
+    /// (session, parameters, value, content_type) as defined by the Secret Service spec
+    type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+    /// Looks up the history key in the default keyring, creating it on first use.
+    /// Runs on its own thread and runtime so it is safe to call from any context.
+    pub fn fetch_or_create_key() -> Result<[u8; KEY_LEN], String> {
+        std::thread::spawn(|| {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("Failed to start runtime: {}", e))?;
+            runtime.block_on(async {
+                match tokio::time::timeout(SECRET_SERVICE_TIMEOUT, fetch_or_create()).await {
+                    Ok(result) => result.map_err(|e| format!("Secret Service error: {}", e)),
+                    Err(_) => Err("Timed out waiting for the Secret Service".to_string()),
+                }
+            })
+        })
+        .join()
+        .map_err(|_| "Secret Service thread panicked".to_string())?
+    }
+
+    async fn fetch_or_create() -> Result<[u8; KEY_LEN], BoxError> {
+        let connection = Connection::session().await?;
+
+        // A "plain" session is enough here: the session bus is private to the user
+        let (_, session): (OwnedValue, OwnedObjectPath) = connection
+            .call_method(
+                Some(SERVICE),
+                SERVICE_PATH,
+                Some(SERVICE_IFACE),
+                "OpenSession",
+                &("plain", Value::from("")),
+            )
+            .await?
+            .body()
+            .deserialize()?;
+
+        let attributes: HashMap<&str, &str> = HashMap::from([ATTR_APPLICATION, ATTR_PURPOSE]);
+
+        let (mut unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = connection
+            .call_method(
+                Some(SERVICE),
+                SERVICE_PATH,
+                Some(SERVICE_IFACE),
+                "SearchItems",
+                &(&attributes,),
+            )
+            .await?
+            .body()
+            .deserialize()?;
+
+        if unlocked.is_empty() && !locked.is_empty() {
+            let (now_unlocked, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) = connection
+                .call_method(
+                    Some(SERVICE),
+                    SERVICE_PATH,
+                    Some(SERVICE_IFACE),
+                    "Unlock",
+                    &(&locked,),
+                )
+                .await?
+                .body()
+                .deserialize()?;
+            unlocked = now_unlocked;
+            if unlocked.is_empty() {
+                run_prompt(&connection, &prompt).await?;
+                unlocked = locked;
+            }
+        }
+
+        if let Some(item) = unlocked.first() {
+            let secrets: HashMap<OwnedObjectPath, Secret> = connection
+                .call_method(
+                    Some(SERVICE),
+                    SERVICE_PATH,
+                    Some(SERVICE_IFACE),
+                    "GetSecrets",
+                    &(vec![item], &session),
+                )
+                .await?
+                .body()
+                .deserialize()?;
+
+            if let Some((_, _, value, _)) = secrets.into_values().next() {
+                return <[u8; KEY_LEN]>::try_from(value.as_slice())
+                    .map_err(|_| "Stored key has an unexpected length".into());
+            }
+        }
+
+        // No key yet: generate one and store it in the default collection
+        let key = HistoryCipher::generate_key();
+        let properties: HashMap<&str, Value> = HashMap::from([
+            ("org.freedesktop.Secret.Item.Label", Value::from(ITEM_LABEL)),
+            (
+                "org.freedesktop.Secret.Item.Attributes",
+                Value::from(attributes),
+            ),
+        ]);
+        let secret: Secret = (
+            session,
+            Vec::new(),
+            key.to_vec(),
+            "application/octet-stream".to_string(),
+        );
+
+        let (_, prompt): (OwnedObjectPath, OwnedObjectPath) = connection
+            .call_method(
+                Some(SERVICE),
+                DEFAULT_COLLECTION,
+                Some(COLLECTION_IFACE),
+                "CreateItem",
+                &(properties, secret, true),
+            )
+            .await?
+            .body()
+            .deserialize()?;
+        run_prompt(&connection, &prompt).await?;
+
+        eprintln!("[HistoryCrypto] Stored a new history key in the Secret Service");
+        Ok(key)
+    }
+
+    /// Shows a keyring prompt (e.g. to unlock the collection) and waits for the user.
+    async fn run_prompt(connection: &Connection, prompt: &OwnedObjectPath) -> Result<(), BoxError> {
+        if prompt.as_str() == NO_PROMPT {
+            return Ok(());
+        }
+
+        // Subscribe before triggering the prompt so the Completed signal cannot be missed
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(PROMPT_IFACE)?
+            .member("Completed")?
+            .path(prompt.as_str())?
+            .build();
+        let mut stream = MessageStream::for_match_rule(rule, connection, None).await?;
+
+        connection
+            .call_method(
+                Some(SERVICE),
+                prompt.as_str(),
+                Some(PROMPT_IFACE),
+                "Prompt",
+                &("",),
+            )
+            .await?;
+
+        match stream.next().await {
+            Some(msg) => {
+                let (dismissed, _): (bool, OwnedValue) = msg?.body().deserialize()?;
+                if dismissed {
+                    Err("Keyring prompt was dismissed".into())
+                } else {
+                    Ok(())
+                }
+            }
+            None => Err("Keyring prompt closed without an answer".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history_store::MemoryHistoryStore;
+    use crate::test_support;
+    use std::path::Path;
+
+    fn passphrase_config(dir: &Path, passphrase: &str) -> (EncryptionConfig, PathBuf) {
+        let path = dir.join("passphrase");
+        std::fs::write(&path, passphrase).unwrap();
+        let config = EncryptionConfig {
+            enabled: true,
+            passphrase_file: Some(path.clone()),
+        };
+        (config, path)
+    }
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let cipher = HistoryCipher::from_key(&HistoryCipher::generate_key());
+        let encrypted = cipher.encrypt(b"secret token").unwrap();
+
+        assert!(HistoryCipher::is_encrypted(&encrypted));
+        assert!(!encrypted.windows(b"secret".len()).any(|w| w == b"secret"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"secret token");
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_key() {
+        let cipher = HistoryCipher::from_key(&HistoryCipher::generate_key());
+        let other = HistoryCipher::from_key(&HistoryCipher::generate_key());
+        let encrypted = cipher.encrypt(b"data").unwrap();
+
+        assert!(other.decrypt(&encrypted).is_err());
+        assert!(cipher.decrypt(b"plain data").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_unlock_and_key_check() {
+        let mut store = MemoryHistoryStore::new();
+        let dir = test_support::temp_dir("crypto");
+        let (config, path) = passphrase_config(&dir, "correct horse\n");
+
+        let cipher = unlock_store(&mut store, &config).unwrap();
+        record_store_mode(&mut store, Some(&cipher)).unwrap();
+        assert!(is_store_encrypted(&mut store).unwrap());
+
+        // Same passphrase + stored salt gives the same key
+        assert!(unlock_store(&mut store, &config).is_ok());
+
+        std::fs::write(&path, "battery staple").unwrap();
+        assert!(unlock_store(&mut store, &config).is_err());
+
+        // Re-keying records a key check for the new passphrase
+        let cipher = store_key(&mut store, &config).unwrap();
+        record_store_mode(&mut store, Some(&cipher)).unwrap();
+        assert!(unlock_store(&mut store, &config).is_ok());
+    }
+}
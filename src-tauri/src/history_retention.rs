@@ -0,0 +1,91 @@
+//! History Retention Module
+//! Age limits for history items and the daily purge schedule.
+//!
+//! Pinned items are never affected. The size limit is applied whenever an item is added;
+//! these rules depend on the clock instead, so the clipboard monitor applies them
+//! periodically through `ClipboardManager::enforce_retention`.
+
+use crate::clipboard_manager::{ClipboardContent, ClipboardItem};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+
+/// When unpinned items are deleted because of their age or the time of day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetentionPolicy {
+    /// Items older than this are deleted
+    pub max_age: Option<Duration>,
+    /// Limit for images instead of `max_age`, as they take the most space
+    pub image_max_age: Option<Duration>,
+    /// Local time of day at which all unpinned items are deleted
+    pub daily_purge: Option<NaiveTime>,
+}
+
+impl RetentionPolicy {
+    /// Whether `item` is past its age limit
+    pub fn is_expired(&self, item: &ClipboardItem, now: DateTime<Utc>) -> bool {
+        if item.pinned {
+            return false;
+        }
+        let max_age = match item.content {
+            ClipboardContent::Image { .. } => self.image_max_age.or(self.max_age),
+            _ => self.max_age,
+        };
+        max_age.is_some_and(|max_age| now - item.timestamp > max_age)
+    }
+
+    /// Whether the daily purge time passed after `since`, up to and including `now`
+    pub fn purge_due(&self, since: DateTime<Local>, now: DateTime<Local>) -> bool {
+        let Some(time) = self.daily_purge else {
+            return false;
+        };
+        let today = now.date_naive();
+        let yesterday = today.pred_opt().unwrap_or(today);
+        // Most recent purge time, skipping days where it does not exist (DST gaps)
+        [today, yesterday]
+            .into_iter()
+            .filter_map(|day| Local.from_local_datetime(&day.and_time(time)).earliest())
+            .find(|at| *at <= now)
+            .is_some_and(|at| at > since)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 2, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_age_limits_skip_pinned_items() {
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::days(7)),
+            image_max_age: Some(Duration::days(1)),
+            daily_purge: None,
+        };
+        let now = Utc::now();
+        let mut text = ClipboardItem::new_text("old".to_string());
+        text.timestamp = now - Duration::days(2);
+        let mut image = ClipboardItem::new_image("k".to_string(), 1, 1, 1, None);
+        image.timestamp = now - Duration::days(2);
+
+        assert!(!policy.is_expired(&text, now));
+        assert!(policy.is_expired(&image, now));
+        image.pinned = true;
+        assert!(!policy.is_expired(&image, now));
+    }
+
+    #[test]
+    fn test_purge_due_once_per_day() {
+        let policy = RetentionPolicy {
+            daily_purge: NaiveTime::from_hms_opt(3, 0, 0),
+            ..Default::default()
+        };
+        assert!(policy.purge_due(local(2, 59), local(3, 0)));
+        assert!(!policy.purge_due(local(3, 0), local(3, 1)));
+        assert!(!policy.purge_due(local(1, 0), local(2, 0)));
+        // A purge time missed while asleep is caught up on wake-up
+        assert!(policy.purge_due(local(2, 0) - Duration::days(1), local(2, 0)));
+        assert!(!RetentionPolicy::default().purge_due(local(0, 0), local(23, 0)));
+    }
+}
@@ -0,0 +1,426 @@
+//! History Search Module
+//! Filtering, fuzzy ranking and paging of clipboard history on the backend.
+//!
+//! A query is a list of words plus optional filters: `type:image`, `pinned:true`,
+//! `app:firefox`, `before:2024-05-01` and `after:2024-05-01`. Double quotes keep
+//! spaces in a word or value (`app:"google chrome"`). Words must all match
+//! (as substrings, or fuzzily as ordered letters), and results are ranked by how
+//! well they match. In regex mode the words form one case-insensitive pattern, with
+//! the spacing they had in the query.
+
+use crate::clipboard_manager::{
+    ClipboardContent, ClipboardItem, ClipboardItemSummary, MAX_PAGE_SIZE,
+};
+use crate::copied_files::display_path;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+// --- Constants ---
+
+/// Only the start of long texts is scanned for fuzzy (non-substring) matches
+const FUZZY_SCAN_LIMIT: usize = 4096;
+/// Compiled regex size limit, so a pathological pattern cannot exhaust memory
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+// --- Query ---
+
+/// Content type filter (`type:`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeFilter {
+    /// Plain and rich text
+    Text,
+    RichText,
+    Image,
+    Files,
+}
+
+impl TypeFilter {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(TypeFilter::Text),
+            "rich" | "richtext" | "rich_text" | "html" => Ok(TypeFilter::RichText),
+            "image" | "images" | "img" => Ok(TypeFilter::Image),
+            "file" | "files" => Ok(TypeFilter::Files),
+            _ => Err(format!("Unknown type filter: {}", value)),
+        }
+    }
+
+    fn matches(self, content: &ClipboardContent) -> bool {
+        matches!(
+            (self, content),
+            (
+                TypeFilter::Text,
+                ClipboardContent::Text(_) | ClipboardContent::RichText { .. }
+            ) | (TypeFilter::RichText, ClipboardContent::RichText { .. })
+                | (TypeFilter::Image, ClipboardContent::Image { .. })
+                | (TypeFilter::Files, ClipboardContent::Files { .. })
+        )
+    }
+}
+
+/// How the free text of a query is matched
+#[derive(Debug)]
+enum TextMatcher {
+    /// Every (lowercase) word must match
+    Words(Vec<String>),
+    Regex(Regex),
+}
+
+/// A parsed search query
+#[derive(Debug)]
+pub struct SearchQuery {
+    content_type: Option<TypeFilter>,
+    pinned: Option<bool>,
+    /// Lowercase part of the source application name
+    app: Option<String>,
+    before: Option<DateTime<Utc>>,
+    after: Option<DateTime<Utc>>,
+    text: TextMatcher,
+}
+
+impl SearchQuery {
+    /// Parses a query. Words with an unknown `key:` prefix (such as URLs) are plain words.
+    pub fn parse(query: &str, regex: bool) -> Result<Self, String> {
+        let mut content_type = None;
+        let mut pinned = None;
+        let mut app = None;
+        let mut before = None;
+        let mut after = None;
+        let mut words = Vec::new();
+
+        for (space, word) in split_words(query) {
+            let Some((key, value)) = word.split_once(':') else {
+                words.push((space, word));
+                continue;
+            };
+            match key.to_lowercase().as_str() {
+                "type" => content_type = Some(TypeFilter::parse(&value.to_lowercase())?),
+                "pinned" => pinned = Some(parse_bool(value)?),
+                "app" => app = Some(value.to_lowercase()),
+                "before" => before = Some(parse_date(value)?),
+                "after" => after = Some(parse_date(value)?),
+                _ => words.push((space, word)),
+            }
+        }
+
+        let text = if regex && !words.is_empty() {
+            let mut pattern = words[0].1.clone();
+            for (space, word) in &words[1..] {
+                pattern.push_str(space);
+                pattern.push_str(word);
+            }
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .build()
+                .map_err(|e| format!("Invalid regex pattern: {}", e))?;
+            TextMatcher::Regex(regex)
+        } else {
+            TextMatcher::Words(words.iter().map(|(_, word)| word.to_lowercase()).collect())
+        };
+
+        Ok(Self {
+            content_type,
+            pinned,
+            app,
+            before,
+            after,
+            text,
+        })
+    }
+
+    /// Whether the item passes every filter (the free text is not checked here)
+    fn passes_filters(&self, item: &ClipboardItem) -> bool {
+        self.content_type
+            .map_or(true, |filter| filter.matches(&item.content))
+            && self.pinned.map_or(true, |pinned| item.pinned == pinned)
+            && self.app.as_deref().map_or(true, |app| {
+                item.source_app
+                    .as_deref()
+                    .is_some_and(|source| source.to_lowercase().contains(app))
+            })
+            && self.before.map_or(true, |before| item.timestamp < before)
+            && self.after.map_or(true, |after| item.timestamp >= after)
+    }
+}
+
+/// Splits a query at whitespace outside double quotes. Quotes are dropped and `\"` is
+/// a literal quote. Each word comes with the whitespace in front of it.
+fn split_words(query: &str) -> Vec<(&str, String)> {
+    let mut words = Vec::new();
+    let mut rest = query;
+    loop {
+        let start = rest.trim_start();
+        if start.is_empty() {
+            return words;
+        }
+        let space = &rest[..rest.len() - start.len()];
+
+        let mut word = String::new();
+        let mut quoted = false;
+        let mut end = start.len();
+        let mut chars = start.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' if chars.peek().is_some_and(|&(_, next)| next == '"') => {
+                    chars.next();
+                    word.push('"');
+                }
+                c if c.is_whitespace() && !quoted => {
+                    end = index;
+                    break;
+                }
+                c => word.push(c),
+            }
+        }
+        words.push((space, word));
+        rest = &start[end..];
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(format!("Expected true or false, got: {}", value)),
+    }
+}
+
+/// Parses `YYYY-MM-DD` (start of that day, local time) or an RFC 3339 timestamp
+fn parse_date(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Expected a date like 2024-05-01, got: {}", value))?;
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+        .ok_or_else(|| format!("Date does not exist in the local time zone: {}", value))
+}
+
+// --- Search ---
+
+/// One page of search results
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResults {
+    pub items: Vec<ClipboardItemSummary>,
+    /// Number of matches across all pages
+    pub total: usize,
+    pub offset: usize,
+}
+
+/// Cache of the lowercased searchable text per item id, filled on first search; each
+/// search still scans the whole history. Item content never changes after creation,
+/// so entries stay valid for the item's lifetime.
+#[derive(Default)]
+pub struct SearchCache {
+    entries: HashMap<String, String>,
+}
+
+impl SearchCache {
+    /// Returns one page of the items matching `query`, best matches first.
+    /// Equally ranked items keep their history order (pinned first, then newest).
+    pub fn search(
+        &mut self,
+        history: &[ClipboardItem],
+        query: &SearchQuery,
+        offset: usize,
+        limit: usize,
+    ) -> SearchResults {
+        self.prune(history);
+
+        let mut matches: Vec<(u32, &ClipboardItem)> = history
+            .iter()
+            .filter(|item| query.passes_filters(item))
+            .filter_map(|item| Some((self.score(item, &query.text)?, item)))
+            .collect();
+        // Stable sort: ties stay in history order
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        SearchResults {
+            total: matches.len(),
+            offset,
+            items: matches
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .map(|(_, item)| item.summary())
+                .collect(),
+        }
+    }
+
+    /// Rank of the item for the free text, or None if it does not match
+    fn score(&mut self, item: &ClipboardItem, text: &TextMatcher) -> Option<u32> {
+        match text {
+            TextMatcher::Words(words) if words.is_empty() => Some(0),
+            TextMatcher::Words(words) => {
+                let haystack = self
+                    .entries
+                    .entry(item.id.clone())
+                    .or_insert_with(|| searchable_text(item).to_lowercase());
+                words
+                    .iter()
+                    .map(|word| score_word(haystack, word))
+                    .sum::<Option<u32>>()
+            }
+            TextMatcher::Regex(regex) => regex.is_match(&searchable_text(item)).then_some(0),
+        }
+    }
+
+    /// Drops entries of items that left the history once they clearly outnumber it
+    fn prune(&mut self, history: &[ClipboardItem]) {
+        if self.entries.len() <= history.len() * 2 {
+            return;
+        }
+        let live: HashSet<&str> = history.iter().map(|item| item.id.as_str()).collect();
+        self.entries.retain(|id, _| live.contains(id.as_str()));
+    }
+}
+
+/// Text a query is matched against: the plain text, or the paths of copied files
+fn searchable_text(item: &ClipboardItem) -> Cow<'_, str> {
+    match &item.content {
+        ClipboardContent::Text(text) => Cow::Borrowed(text),
+        ClipboardContent::RichText { plain, .. } => Cow::Borrowed(plain),
+        ClipboardContent::Files { uris, .. } => Cow::Owned(
+            uris.iter()
+                .map(|uri| display_path(uri))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        ClipboardContent::Image { .. } => Cow::Borrowed(""),
+    }
+}
+
+/// Scores one lowercase word: substring matches rank above fuzzy ones,
+/// and matches at the start of a word above those inside one
+fn score_word(haystack: &str, word: &str) -> Option<u32> {
+    if let Some(pos) = haystack.find(word) {
+        let at_word_start = haystack[..pos]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !c.is_alphanumeric());
+        return Some(if pos == 0 {
+            300
+        } else if at_word_start {
+            200
+        } else {
+            100
+        });
+    }
+    fuzzy_score(haystack, word)
+}
+
+/// Letters of `word` appearing in order in the start of `haystack`, close together.
+/// Returns a score below any substring match, higher for tighter matches.
+fn fuzzy_score(haystack: &str, word: &str) -> Option<u32> {
+    let needle: Vec<char> = word.chars().collect();
+    if needle.len() < 2 {
+        return None;
+    }
+    let chars: Vec<char> = haystack.chars().take(FUZZY_SCAN_LIMIT).collect();
+    // Letters skipped between the matched ones; more than the word length is noise
+    let max_gap = needle.len();
+
+    let best_gap = chars
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c == needle[0])
+        .filter_map(|(start, _)| {
+            let mut gap = 0;
+            let mut pos = start + 1;
+            for &wanted in &needle[1..] {
+                let offset = chars[pos..].iter().position(|&c| c == wanted)?;
+                gap += offset;
+                if gap > max_gap {
+                    return None;
+                }
+                pos += offset + 1;
+            }
+            Some(gap)
+        })
+        .min()?;
+    Some(50 - (best_gap * 40 / max_gap) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard_manager::DEFAULT_PAGE_SIZE;
+
+    fn search(history: &[ClipboardItem], query: &str, regex: bool) -> Vec<String> {
+        let query = SearchQuery::parse(query, regex).unwrap();
+        SearchCache::default()
+            .search(history, &query, 0, DEFAULT_PAGE_SIZE)
+            .items
+            .into_iter()
+            .map(|item| item.preview)
+            .collect()
+    }
+
+    #[test]
+    fn test_ranking_and_fuzzy_matches() {
+        let history = vec![
+            ClipboardItem::new_text("the configuration file".to_string()),
+            ClipboardItem::new_text("reconfigure".to_string()),
+            ClipboardItem::new_text("config".to_string()),
+            ClipboardItem::new_text("unrelated".to_string()),
+        ];
+        assert_eq!(
+            search(&history, "config", false),
+            vec!["config", "the configuration file", "reconfigure"]
+        );
+        // Letters in order with small gaps still match, below substring matches
+        assert_eq!(search(&history, "cnfg", false).len(), 3);
+        assert!(search(&history, "cgx", false).is_empty());
+    }
+
+    #[test]
+    fn test_filters_and_regex() {
+        let mut pinned = ClipboardItem::new_text("pinned note".to_string());
+        pinned.pinned = true;
+        let mut from_firefox = ClipboardItem::new_text("https://example.com".to_string());
+        from_firefox.source_app = Some("Firefox".to_string());
+        let image = ClipboardItem::new_image("k".to_string(), 4, 4, 1, None);
+        let history = vec![pinned, from_firefox, image];
+
+        assert_eq!(search(&history, "pinned:true", false), vec!["pinned note"]);
+        assert_eq!(
+            search(&history, "app:fire https:", false),
+            vec!["https://example.com"]
+        );
+        assert_eq!(search(&history, "type:image", false), vec!["Image (4x4)"]);
+        assert_eq!(search(&history, "before:2000-01-01", false).len(), 0);
+        assert_eq!(search(&history, "after:2000-01-01", false).len(), 3);
+        assert_eq!(
+            search(&history, r"^https?://\w+", true),
+            vec!["https://example.com"]
+        );
+
+        // Quotes keep spaces, both in filter values and in a regex
+        let mut chrome = ClipboardItem::new_text("two  spaces".to_string());
+        chrome.source_app = Some("Google Chrome".to_string());
+        let history = vec![chrome, ClipboardItem::new_text("two spaces".to_string())];
+        assert_eq!(
+            search(&history, r#"app:"google chrome""#, false),
+            vec!["two  spaces"]
+        );
+        assert_eq!(
+            search(&history, r#"^two"  "spaces$"#, true),
+            vec!["two  spaces"]
+        );
+        assert_eq!(search(&history, r#"two\s+"spaces""#, true).len(), 2);
+        assert_eq!(search(&history, "two  spaces", true), vec!["two  spaces"]);
+
+        assert!(SearchQuery::parse("type:video", false).is_err());
+        assert!(SearchQuery::parse("(unclosed", true).is_err());
+    }
+}
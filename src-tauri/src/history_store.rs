@@ -0,0 +1,795 @@
+//! History Store Module
+//! Storage backends for clipboard history persistence.
+//!
+//! `ClipboardManager` keeps the authoritative ordering in memory and only sends
+//! the rows that changed to the store, so a copy no longer rewrites the whole history.
+
+use crate::blob_store::BlobStore;
+use crate::clipboard_manager::{self, ClipboardItem, HistoryFile};
+use crate::history_crypto::HistoryCipher;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// --- Constants ---
+
+pub const HISTORY_DB_FILE: &str = "history.db";
+pub const LEGACY_HISTORY_FILE: &str = "history.json";
+const LEGACY_MIGRATED_SUFFIX: &str = "migrated";
+
+/// Current SQLite schema version (stored in `PRAGMA user_version`)
+const SCHEMA_VERSION: i32 = 2;
+
+/// Meta key holding the schema version of the item JSON in the `data` column
+const META_ITEM_VERSION: &str = "item_schema_version";
+
+// --- Trait ---
+
+/// A single change to apply to the store.
+#[derive(Debug, Clone, Copy)]
+pub enum StoreChange<'a> {
+    /// Insert a new item or update an existing one (keeps its original position)
+    Upsert(&'a ClipboardItem),
+    /// Remove the item with the given id
+    Remove(&'a str),
+}
+
+/// Persistence backend for `ClipboardManager`.
+/// Implementations must apply each batch of changes atomically.
+pub trait HistoryStore: Send {
+    /// Loads all stored items, most recently inserted first.
+    fn load(&mut self) -> Result<Vec<ClipboardItem>, String>;
+
+    /// Applies a batch of changes in a single transaction.
+    fn commit(&mut self, changes: &[StoreChange<'_>]) -> Result<(), String>;
+
+    /// Replaces the whole content of the store, keeping the given order.
+    fn replace_all(&mut self, items: &[ClipboardItem]) -> Result<(), String>;
+
+    /// Returns true if the store holds no items.
+    fn is_empty(&mut self) -> Result<bool, String>;
+
+    /// Sets the cipher for item payloads written from now on (`None` writes plaintext).
+    /// Reads accept both forms, so a store can be migrated in place.
+    fn set_cipher(&mut self, cipher: Option<Arc<HistoryCipher>>);
+
+    /// Reads a store-level metadata value (encryption mode, KDF salt, ...).
+    fn get_meta(&mut self, key: &str) -> Result<Option<Vec<u8>>, String>;
+
+    /// Writes a store-level metadata value; `None` deletes it.
+    fn set_meta(&mut self, key: &str, value: Option<&[u8]>) -> Result<(), String>;
+
+    /// Reclaims free space so rewritten rows leave no stale copies on disk.
+    fn compact(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Returns true if the last `load` read every stored item from persistent storage.
+    /// Only then can blobs not referenced by the loaded items be treated as orphans.
+    fn loaded_completely(&self) -> bool {
+        false
+    }
+}
+
+// --- SQLite Backend ---
+
+/// SQLite-backed store. Each item is a row keyed by id; `seq` preserves insertion order.
+/// The `data` column holds the item as JSON text, or as an encrypted blob when a cipher is set.
+/// Index columns (seq, pinned, timestamp, content type) stay readable either way.
+/// Rows of an older item schema are upgraded when loaded, then written back.
+pub struct SqliteHistoryStore {
+    conn: Connection,
+    cipher: Option<Arc<HistoryCipher>>,
+    /// Item schema version of the stored rows
+    item_version: u32,
+    /// Whether the last load returned every row
+    loaded_completely: bool,
+}
+
+impl SqliteHistoryStore {
+    /// Opens (and creates if missing) the database at the given path.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
+        Self::init(conn)
+    }
+
+    /// Opens a private in-memory database (used by tests).
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn =
+            Connection::open_in_memory().map_err(|e| format!("Failed to open database: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        // WAL keeps writes cheap and lets a crash leave the last committed state intact
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|e| format!("Failed to enable WAL: {}", e))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(|e| format!("Failed to set synchronous mode: {}", e))?;
+
+        let mut store = Self {
+            conn,
+            cipher: None,
+            item_version: clipboard_manager::HISTORY_SCHEMA_VERSION,
+            loaded_completely: false,
+        };
+        store.migrate_schema()?;
+        store.item_version = store.read_item_version()?;
+        Ok(store)
+    }
+
+    /// Reads the item schema version. Databases written before it was recorded hold
+    /// version 1 items; a new database starts at the current version.
+    fn read_item_version(&mut self) -> Result<u32, String> {
+        if let Some(value) = self.get_meta(META_ITEM_VERSION)? {
+            return String::from_utf8_lossy(&value)
+                .parse()
+                .map_err(|e| format!("Invalid item schema version: {}", e));
+        }
+        if !self.is_empty()? {
+            return Ok(1);
+        }
+        let version = clipboard_manager::HISTORY_SCHEMA_VERSION;
+        self.set_meta(META_ITEM_VERSION, Some(version.to_string().as_bytes()))?;
+        Ok(version)
+    }
+
+    fn migrate_schema(&self) -> Result<(), String> {
+        let version: i32 = self
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+        // A newer build wrote this database; leave it untouched
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "Database uses schema version {}, newer than the supported {}",
+                version, SCHEMA_VERSION
+            ));
+        }
+
+        if version < 1 {
+            self.conn
+                .execute_batch(
+                    "CREATE TABLE IF NOT EXISTS items (
+                        id TEXT PRIMARY KEY NOT NULL,
+                        seq INTEGER NOT NULL,
+                        pinned INTEGER NOT NULL DEFAULT 0,
+                        timestamp TEXT NOT NULL,
+                        content_type TEXT NOT NULL,
+                        data TEXT NOT NULL
+                    );
+                    CREATE INDEX IF NOT EXISTS idx_items_seq ON items(seq);
+                    CREATE INDEX IF NOT EXISTS idx_items_pinned ON items(pinned);
+                    CREATE INDEX IF NOT EXISTS idx_items_timestamp ON items(timestamp);",
+                )
+                .map_err(|e| format!("Failed to create schema: {}", e))?;
+        }
+
+        if version < 2 {
+            self.conn
+                .execute_batch(
+                    "CREATE TABLE IF NOT EXISTS meta (
+                        key TEXT PRIMARY KEY NOT NULL,
+                        value BLOB NOT NULL
+                    );",
+                )
+                .map_err(|e| format!("Failed to create schema: {}", e))?;
+        }
+
+        if version < SCHEMA_VERSION {
+            self.conn
+                .pragma_update(None, "user_version", SCHEMA_VERSION)
+                .map_err(|e| format!("Failed to write schema version: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn next_seq(tx: &rusqlite::Transaction<'_>) -> Result<i64, String> {
+        tx.query_row("SELECT COALESCE(MAX(seq), 0) + 1 FROM items", [], |row| {
+            row.get(0)
+        })
+        .map_err(|e| format!("Failed to read sequence: {}", e))
+    }
+
+    fn encode_item(&self, item: &ClipboardItem) -> Result<Value, String> {
+        let json =
+            serde_json::to_string(item).map_err(|e| format!("Failed to serialize item: {}", e))?;
+        match &self.cipher {
+            Some(cipher) => Ok(Value::Blob(cipher.encrypt(json.as_bytes())?)),
+            None => Ok(Value::Text(json)),
+        }
+    }
+
+    fn decode_item(&self, data: Value) -> Result<ClipboardItem, String> {
+        let json = match data {
+            Value::Text(json) => json.into_bytes(),
+            Value::Blob(bytes) => match &self.cipher {
+                Some(cipher) => cipher.decrypt(&bytes)?,
+                None => return Err("Item is encrypted but no key is available".to_string()),
+            },
+            _ => return Err("Unexpected data type".to_string()),
+        };
+        let value = serde_json::from_slice(&json).map_err(|e| e.to_string())?;
+        clipboard_manager::migrate_item(value, self.item_version)
+    }
+
+    fn write_row(
+        tx: &rusqlite::Transaction<'_>,
+        item: &ClipboardItem,
+        data: Value,
+        seq: Option<i64>,
+    ) -> Result<(), String> {
+        let existing: Option<i64> = tx
+            .query_row(
+                "SELECT seq FROM items WHERE id = ?1",
+                params![item.id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to query item: {}", e))?;
+
+        let seq = match (seq, existing) {
+            (Some(seq), _) => seq,
+            (None, Some(seq)) => seq,
+            (None, None) => Self::next_seq(tx)?,
+        };
+
+        tx.execute(
+            "INSERT INTO items (id, seq, pinned, timestamp, content_type, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(id) DO UPDATE SET
+                seq = excluded.seq,
+                pinned = excluded.pinned,
+                timestamp = excluded.timestamp,
+                content_type = excluded.content_type,
+                data = excluded.data",
+            params![
+                item.id,
+                seq,
+                item.pinned,
+                item.timestamp.to_rfc3339(),
+                item.content.type_name(),
+                data
+            ],
+        )
+        .map_err(|e| format!("Failed to write item: {}", e))?;
+
+        Ok(())
+    }
+}
+
+impl HistoryStore for SqliteHistoryStore {
+    fn load(&mut self) -> Result<Vec<ClipboardItem>, String> {
+        self.loaded_completely = false;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT data FROM items ORDER BY seq DESC")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt
+            .query_map([], |row| row.get::<_, Value>(0))
+            .map_err(|e| format!("Failed to query items: {}", e))?;
+
+        let mut encoded = Vec::new();
+        for row in rows {
+            encoded.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        drop(stmt);
+
+        clipboard_manager::check_schema_version(self.item_version)?;
+        let mut items = Vec::with_capacity(encoded.len());
+        let mut unreadable = 0;
+        for data in encoded {
+            match self.decode_item(data) {
+                Ok(item) => items.push(item),
+                // Skip a corrupt row instead of losing the whole history
+                Err(e) => {
+                    eprintln!("[HistoryStore] Skipping unreadable item: {}", e);
+                    unreadable += 1;
+                }
+            }
+        }
+
+        // Write upgraded rows back once, unless that would drop the unreadable ones
+        let current = clipboard_manager::HISTORY_SCHEMA_VERSION;
+        if self.item_version < current && unreadable == 0 {
+            self.replace_all(&items)?;
+            self.set_meta(META_ITEM_VERSION, Some(current.to_string().as_bytes()))?;
+            eprintln!(
+                "[HistoryStore] Upgraded {} items from schema version {} to {}",
+                items.len(),
+                self.item_version,
+                current
+            );
+            self.item_version = current;
+        }
+        self.loaded_completely = unreadable == 0;
+        Ok(items)
+    }
+
+    fn commit(&mut self, changes: &[StoreChange<'_>]) -> Result<(), String> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        // Encode before opening the transaction (it borrows the connection mutably)
+        let mut encoded = Vec::with_capacity(changes.len());
+        for change in changes {
+            encoded.push(match change {
+                StoreChange::Upsert(item) => Some(self.encode_item(item)?),
+                StoreChange::Remove(_) => None,
+            });
+        }
+
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+
+        for (change, data) in changes.iter().zip(encoded) {
+            match (change, data) {
+                (StoreChange::Upsert(item), Some(data)) => Self::write_row(&tx, item, data, None)?,
+                (StoreChange::Remove(id), _) => {
+                    tx.execute("DELETE FROM items WHERE id = ?1", params![id])
+                        .map_err(|e| format!("Failed to delete item: {}", e))?;
+                }
+                (StoreChange::Upsert(_), None) => unreachable!("upserts are always encoded"),
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit transaction: {}", e))
+    }
+
+    fn replace_all(&mut self, items: &[ClipboardItem]) -> Result<(), String> {
+        let encoded = items
+            .iter()
+            .map(|item| self.encode_item(item))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+
+        tx.execute("DELETE FROM items", [])
+            .map_err(|e| format!("Failed to clear items: {}", e))?;
+
+        // First item in the slice is the newest, so it gets the highest sequence number
+        let count = items.len() as i64;
+        for (index, (item, data)) in items.iter().zip(encoded).enumerate() {
+            Self::write_row(&tx, item, data, Some(count - index as i64))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit transaction: {}", e))
+    }
+
+    fn is_empty(&mut self) -> Result<bool, String> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to count items: {}", e))?;
+        Ok(count == 0)
+    }
+
+    fn set_cipher(&mut self, cipher: Option<Arc<HistoryCipher>>) {
+        self.cipher = cipher;
+    }
+
+    fn get_meta(&mut self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        self.conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read metadata: {}", e))
+    }
+
+    fn set_meta(&mut self, key: &str, value: Option<&[u8]>) -> Result<(), String> {
+        let result = match value {
+            Some(value) => self.conn.execute(
+                "INSERT INTO meta (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            ),
+            None => self
+                .conn
+                .execute("DELETE FROM meta WHERE key = ?1", params![key]),
+        };
+        result
+            .map(|_| ())
+            .map_err(|e| format!("Failed to write metadata: {}", e))
+    }
+
+    fn compact(&mut self) -> Result<(), String> {
+        // Fold the WAL back into the main file, then rebuild it without free pages
+        self.conn
+            .execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM;")
+            .map_err(|e| format!("Failed to compact database: {}", e))
+    }
+
+    fn loaded_completely(&self) -> bool {
+        self.loaded_completely
+    }
+}
+
+// --- In-Memory Backend ---
+
+/// Volatile store used when the database cannot be opened, and in tests.
+#[derive(Default)]
+pub struct MemoryHistoryStore {
+    items: Vec<ClipboardItem>,
+    meta: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryHistoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl HistoryStore for MemoryHistoryStore {
+    fn load(&mut self) -> Result<Vec<ClipboardItem>, String> {
+        Ok(self.items.clone())
+    }
+
+    fn commit(&mut self, changes: &[StoreChange<'_>]) -> Result<(), String> {
+        for change in changes {
+            match change {
+                StoreChange::Upsert(item) => {
+                    match self.items.iter_mut().find(|i| i.id == item.id) {
+                        Some(existing) => *existing = (*item).clone(),
+                        None => self.items.insert(0, (*item).clone()),
+                    }
+                }
+                StoreChange::Remove(id) => self.items.retain(|i| i.id != *id),
+            }
+        }
+        Ok(())
+    }
+
+    fn replace_all(&mut self, items: &[ClipboardItem]) -> Result<(), String> {
+        self.items = items.to_vec();
+        Ok(())
+    }
+
+    fn is_empty(&mut self) -> Result<bool, String> {
+        Ok(self.items.is_empty())
+    }
+
+    fn set_cipher(&mut self, _cipher: Option<Arc<HistoryCipher>>) {
+        // Nothing reaches the disk, so there is nothing to encrypt
+    }
+
+    fn get_meta(&mut self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        Ok(self.meta.get(key).cloned())
+    }
+
+    fn set_meta(&mut self, key: &str, value: Option<&[u8]>) -> Result<(), String> {
+        match value {
+            Some(value) => self.meta.insert(key.to_string(), value.to_vec()),
+            None => self.meta.remove(key),
+        };
+        Ok(())
+    }
+}
+
+// --- Helpers ---
+
+/// Opens the default on-disk store inside `data_dir`, importing a legacy
+/// `history.json` on first start (its inline images go to `blobs`).
+pub fn open_default_store(
+    data_dir: &Path,
+    blobs: &BlobStore,
+) -> Result<Box<dyn HistoryStore>, String> {
+    let mut store = SqliteHistoryStore::open(&data_dir.join(HISTORY_DB_FILE))?;
+    let legacy_path = data_dir.join(LEGACY_HISTORY_FILE);
+    match migrate_legacy_json(&mut store, blobs, &legacy_path) {
+        Ok(0) => {}
+        Ok(count) => eprintln!(
+            "[HistoryStore] Migrated {} items from {:?}",
+            count, legacy_path
+        ),
+        Err(e) => eprintln!("[HistoryStore] Legacy history migration failed: {}", e),
+    }
+    Ok(Box::new(store))
+}
+
+/// Where an imported legacy history file is kept after migration
+fn legacy_backup_path(json_path: &Path) -> PathBuf {
+    let mut path = json_path.to_path_buf();
+    path.set_extension(format!("json.{}", LEGACY_MIGRATED_SUFFIX));
+    path
+}
+
+/// Deletes the plaintext copy of an imported legacy history, if any.
+/// Called when encryption is turned on so no readable history stays behind.
+pub fn remove_legacy_backup(data_dir: &Path) {
+    let path = legacy_backup_path(&data_dir.join(LEGACY_HISTORY_FILE));
+    if path.exists() {
+        match fs::remove_file(&path) {
+            Ok(()) => eprintln!("[HistoryStore] Removed plaintext legacy backup {:?}", path),
+            Err(e) => eprintln!("[HistoryStore] Failed to remove {:?}: {}", path, e),
+        }
+    }
+}
+
+/// Imports items from a legacy `history.json` into an empty store, then renames
+/// the file so the import only happens once. Inline images are moved into `blobs`
+/// first; if that fails nothing is imported and the file is kept for the next start.
+/// Returns the number of imported items.
+pub fn migrate_legacy_json(
+    store: &mut dyn HistoryStore,
+    blobs: &BlobStore,
+    json_path: &Path,
+) -> Result<usize, String> {
+    if !json_path.exists() {
+        return Ok(0);
+    }
+
+    // Never overwrite a store that already has data
+    if !store.is_empty()? {
+        return Ok(0);
+    }
+
+    let content = fs::read_to_string(json_path)
+        .map_err(|e| format!("Failed to read legacy history: {}", e))?;
+    let parsed = HistoryFile::parse(&content)?.into_items()?;
+
+    // The store never writes inline images back, so they have to reach the blob store first
+    let mut items = Vec::with_capacity(parsed.len());
+    for mut item in parsed {
+        if item.move_inline_image(blobs)? {
+            items.push(item);
+        }
+    }
+
+    store.replace_all(&items)?;
+
+    if let Err(e) = fs::rename(json_path, legacy_backup_path(json_path)) {
+        eprintln!(
+            "[HistoryStore] Imported legacy history but failed to rename it: {}",
+            e
+        );
+    }
+
+    Ok(items.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn test_sqlite_commit_and_load_order() {
+        let mut store = SqliteHistoryStore::open_in_memory().unwrap();
+        let first = ClipboardItem::new_text("first".to_string());
+        let second = ClipboardItem::new_text("second".to_string());
+
+        store.commit(&[StoreChange::Upsert(&first)]).unwrap();
+        store.commit(&[StoreChange::Upsert(&second)]).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].id, second.id);
+        assert_eq!(loaded[1].id, first.id);
+    }
+
+    #[test]
+    fn test_sqlite_upsert_keeps_position() {
+        let mut store = SqliteHistoryStore::open_in_memory().unwrap();
+        let mut first = ClipboardItem::new_text("first".to_string());
+        let second = ClipboardItem::new_text("second".to_string());
+        store
+            .commit(&[StoreChange::Upsert(&first), StoreChange::Upsert(&second)])
+            .unwrap();
+
+        first.pinned = true;
+        store.commit(&[StoreChange::Upsert(&first)]).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded[1].id, first.id);
+        assert!(loaded[1].pinned);
+    }
+
+    #[test]
+    fn test_sqlite_remove() {
+        let mut store = SqliteHistoryStore::open_in_memory().unwrap();
+        let item = ClipboardItem::new_text("gone".to_string());
+        store.commit(&[StoreChange::Upsert(&item)]).unwrap();
+        store.commit(&[StoreChange::Remove(&item.id)]).unwrap();
+
+        assert!(store.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_encrypted_rows() {
+        let mut store = SqliteHistoryStore::open_in_memory().unwrap();
+        let cipher = Arc::new(HistoryCipher::from_key(&HistoryCipher::generate_key()));
+        let plain = ClipboardItem::new_text("written before encryption".to_string());
+        store.commit(&[StoreChange::Upsert(&plain)]).unwrap();
+
+        store.set_cipher(Some(cipher));
+        let secret = ClipboardItem::new_text("hunter2".to_string());
+        store.commit(&[StoreChange::Upsert(&secret)]).unwrap();
+
+        let raw: Vec<u8> = store
+            .conn
+            .query_row(
+                "SELECT data FROM items WHERE id = ?1",
+                params![secret.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(HistoryCipher::is_encrypted(&raw));
+
+        // Both plaintext and encrypted rows are readable with the key
+        assert_eq!(store.load().unwrap().len(), 2);
+
+        // Without the key only the plaintext row can be read
+        store.set_cipher(None);
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id, plain.id);
+    }
+
+    #[test]
+    fn test_migrate_legacy_json() {
+        let dir = test_support::temp_dir("store");
+        let json_path = dir.join(LEGACY_HISTORY_FILE);
+        let items = vec![
+            ClipboardItem::new_text("newest".to_string()),
+            ClipboardItem::new_text("oldest".to_string()),
+        ];
+        fs::write(&json_path, serde_json::to_string(&items).unwrap()).unwrap();
+
+        let blobs = BlobStore::new(dir.join("images"));
+        let mut store = SqliteHistoryStore::open(&dir.join(HISTORY_DB_FILE)).unwrap();
+        assert_eq!(
+            migrate_legacy_json(&mut store, &blobs, &json_path).unwrap(),
+            2
+        );
+        assert!(!json_path.exists());
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded[0].id, items[0].id);
+        assert_eq!(loaded[1].id, items[1].id);
+
+        // A second run is a no-op
+        assert_eq!(
+            migrate_legacy_json(&mut store, &blobs, &json_path).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_migrate_legacy_json_keeps_inline_images() {
+        use base64::Engine;
+
+        let dir = test_support::temp_dir("store");
+        let json_path = dir.join(LEGACY_HISTORY_FILE);
+        let png = b"\x89PNG legacy image".to_vec();
+        let legacy = format!(
+            r#"[{{
+                "id": "img",
+                "content": {{"type": "Image", "data": {{"base64": "{}", "width": 1, "height": 1}}}},
+                "timestamp": "2024-01-01T00:00:00Z",
+                "pinned": false,
+                "preview": "Image (1x1)"
+            }}, {{
+                "id": "broken",
+                "content": {{"type": "Image", "data": {{"base64": "%%%", "width": 1, "height": 1}}}},
+                "timestamp": "2024-01-01T00:00:00Z",
+                "pinned": false,
+                "preview": "Image (1x1)"
+            }}]"#,
+            base64::engine::general_purpose::STANDARD.encode(&png)
+        );
+        fs::write(&json_path, legacy).unwrap();
+
+        let blobs = BlobStore::new(dir.join("images"));
+        let mut store = SqliteHistoryStore::open(&dir.join(HISTORY_DB_FILE)).unwrap();
+        assert_eq!(
+            migrate_legacy_json(&mut store, &blobs, &json_path).unwrap(),
+            1
+        );
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        let key = loaded[0].blob_key().expect("image keeps its blob");
+        assert_eq!(blobs.read(key).unwrap(), png);
+    }
+
+    #[test]
+    fn test_open_default_store_reports_failure() {
+        let dir = test_support::temp_dir("store");
+        // A file where the data directory should be cannot hold a database
+        let data_dir = dir.join("not-a-dir");
+        fs::write(&data_dir, b"").unwrap();
+        let blobs = BlobStore::new(dir.join("blobs"));
+        assert!(open_default_store(&data_dir, &blobs).is_err());
+    }
+
+    #[test]
+    fn test_newer_database_schema_is_rejected() {
+        let dir = test_support::temp_dir("store");
+        let path = dir.join(HISTORY_DB_FILE);
+        let store = SqliteHistoryStore::open(&path).unwrap();
+        store
+            .conn
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        drop(store);
+
+        assert!(SqliteHistoryStore::open(&path).is_err());
+        let conn = Connection::open(&path).unwrap();
+        let version: i32 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION + 1);
+    }
+
+    #[test]
+    fn test_sqlite_rows_upgraded_on_load() {
+        let mut store = SqliteHistoryStore::open_in_memory().unwrap();
+        let mut item = ClipboardItem::new_image("k".to_string(), 2, 3, 42, None);
+        item.preview = "Image (2x3) #42".to_string();
+        let mut data: serde_json::Value = serde_json::to_value(&item).unwrap();
+        data["content"]["data"]
+            .as_object_mut()
+            .unwrap()
+            .remove("hash");
+        store
+            .conn
+            .execute(
+                "INSERT INTO items (id, seq, pinned, timestamp, content_type, data)
+                 VALUES (?1, 1, 0, ?2, 'image', ?3)",
+                params![item.id, item.timestamp.to_rfc3339(), data.to_string()],
+            )
+            .unwrap();
+        store.item_version = 1;
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded[0].image_hash(), Some(42));
+        assert_eq!(loaded[0].preview, "Image (2x3)");
+        assert_eq!(
+            store.item_version,
+            clipboard_manager::HISTORY_SCHEMA_VERSION
+        );
+        assert_eq!(store.read_item_version().unwrap(), store.item_version);
+    }
+
+    #[test]
+    fn test_sqlite_load_reports_skipped_rows() {
+        let mut store = SqliteHistoryStore::open_in_memory().unwrap();
+        let item = ClipboardItem::new_text("kept".to_string());
+        store.commit(&[StoreChange::Upsert(&item)]).unwrap();
+        assert!(!store.loaded_completely());
+        store.load().unwrap();
+        assert!(store.loaded_completely());
+
+        store
+            .conn
+            .execute(
+                "INSERT INTO items (id, seq, pinned, timestamp, content_type, data)
+                 VALUES ('broken', 2, 0, ?1, 'text', 'not json')",
+                params![item.timestamp.to_rfc3339()],
+            )
+            .unwrap();
+        assert_eq!(store.load().unwrap().len(), 1);
+        assert!(!store.loaded_completely());
+    }
+}
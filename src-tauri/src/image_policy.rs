@@ -0,0 +1,243 @@
+//! Image Policy Module
+//! Decides how copied images are stored: size limits, downscaling and encoding.
+//!
+//! Encoding a large screenshot takes a noticeable time, so it works on plain pixel data
+//! and never needs the clipboard manager. The clipboard monitor runs `encode_image` on
+//! its reader thread, without holding the manager lock.
+
+use arboard::ImageData;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use std::io::Cursor;
+
+// --- Constants ---
+
+/// Longest side of the thumbnails shown in the history list
+pub const THUMBNAIL_MAX_SIZE: u32 = 256;
+const JPEG_QUALITY: u8 = 85;
+/// Re-encodes tried when the byte limit is still exceeded after downscaling
+const MAX_DOWNSCALE_ATTEMPTS: usize = 4;
+
+// --- Policy ---
+
+/// What happens to images above the configured limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OversizedImageAction {
+    /// Scale the image down until it fits
+    #[default]
+    Downscale,
+    /// Do not record the image
+    Skip,
+}
+
+impl OversizedImageAction {
+    /// Parses the `oversized_image_action` user setting
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "skip" => Self::Skip,
+            _ => Self::Downscale,
+        }
+    }
+}
+
+/// Encoding of stored images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StoredImageFormat {
+    /// Lossless, keeps transparency
+    #[default]
+    Png,
+    /// Lossy and much smaller for photos and screenshots; transparency is lost
+    Jpeg,
+}
+
+impl StoredImageFormat {
+    /// Parses the `image_format` user setting
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "jpeg" => Self::Jpeg,
+            _ => Self::Png,
+        }
+    }
+}
+
+/// Limits and encoding applied to copied images. A limit of 0 means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImagePolicy {
+    pub max_pixels: u64,
+    pub max_bytes: u64,
+    pub oversized: OversizedImageAction,
+    pub format: StoredImageFormat,
+}
+
+/// An image ready to be stored
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    /// Hash of the original RGBA pixels
+    pub hash: u64,
+    pub bytes: Vec<u8>,
+    /// Dimensions of the stored (possibly downscaled) image
+    pub width: u32,
+    pub height: u32,
+    /// PNG thumbnail, `None` when the image already fits the thumbnail size
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// Why a copied image was not stored
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageRejected {
+    /// Over the limits while the policy says to skip such images
+    TooLarge { width: u32, height: u32 },
+    /// The pixel data could not be used
+    Invalid(String),
+}
+
+impl std::fmt::Display for ImageRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge { width, height } => {
+                write!(f, "{}×{} image exceeds the size limit", width, height)
+            }
+            Self::Invalid(e) => write!(f, "Invalid image: {}", e),
+        }
+    }
+}
+
+// --- Encoding ---
+
+/// Applies `policy` to a copied image and encodes it for storage.
+pub fn encode_image(
+    image_data: &ImageData<'_>,
+    hash: u64,
+    policy: &ImagePolicy,
+) -> Result<EncodedImage, ImageRejected> {
+    let (width, height) = (image_data.width as u32, image_data.height as u32);
+    let rgba = image::RgbaImage::from_raw(width, height, image_data.bytes.to_vec())
+        .ok_or_else(|| ImageRejected::Invalid("dimensions don't match the data".to_string()))?;
+    let mut img = DynamicImage::ImageRgba8(rgba);
+    let too_large = ImageRejected::TooLarge { width, height };
+
+    let pixels = u64::from(width) * u64::from(height);
+    if policy.max_pixels > 0 && pixels > policy.max_pixels {
+        if policy.oversized == OversizedImageAction::Skip {
+            return Err(too_large);
+        }
+        img = downscale(&img, (policy.max_pixels as f64 / pixels as f64).sqrt());
+    }
+
+    let mut bytes = encode(&img, policy.format)?;
+    let mut attempts = 0;
+    while policy.max_bytes > 0 && bytes.len() as u64 > policy.max_bytes {
+        if policy.oversized == OversizedImageAction::Skip || attempts == MAX_DOWNSCALE_ATTEMPTS {
+            return Err(too_large);
+        }
+        // Encoded size grows roughly with the pixel count; aim a little below the limit
+        let scale = (policy.max_bytes as f64 / bytes.len() as f64).sqrt() * 0.9;
+        img = downscale(&img, scale);
+        bytes = encode(&img, policy.format)?;
+        attempts += 1;
+    }
+
+    if (img.width(), img.height()) != (width, height) {
+        eprintln!(
+            "[ImagePolicy] Downscaled {}x{} image to {}x{}",
+            width,
+            height,
+            img.width(),
+            img.height()
+        );
+    }
+
+    Ok(EncodedImage {
+        hash,
+        bytes,
+        width: img.width(),
+        height: img.height(),
+        thumbnail: make_thumbnail(&img),
+    })
+}
+
+/// Scales a PNG thumbnail down to `THUMBNAIL_MAX_SIZE`. Images that already fit get none.
+pub fn make_thumbnail(img: &DynamicImage) -> Option<Vec<u8>> {
+    if img.width() <= THUMBNAIL_MAX_SIZE && img.height() <= THUMBNAIL_MAX_SIZE {
+        return None;
+    }
+    let thumbnail = img.thumbnail(THUMBNAIL_MAX_SIZE, THUMBNAIL_MAX_SIZE);
+    encode(&thumbnail, StoredImageFormat::Png).ok()
+}
+
+fn downscale(img: &DynamicImage, scale: f64) -> DynamicImage {
+    let width = ((img.width() as f64 * scale) as u32).max(1);
+    let height = ((img.height() as f64 * scale) as u32).max(1);
+    img.resize_exact(width, height, FilterType::Triangle)
+}
+
+fn encode(img: &DynamicImage, format: StoredImageFormat) -> Result<Vec<u8>, ImageRejected> {
+    let mut buffer = Cursor::new(Vec::new());
+    let result = match format {
+        StoredImageFormat::Png => img.write_to(&mut buffer, ImageFormat::Png),
+        StoredImageFormat::Jpeg => DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY)),
+    };
+    result.map_err(|e| ImageRejected::Invalid(format!("Failed to encode image: {}", e)))?;
+    Ok(buffer.into_inner())
+}
+
+/// MIME type of stored image bytes (PNG unless the data says otherwise)
+pub fn mime_type(bytes: &[u8]) -> &'static str {
+    image::guess_format(bytes)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("image/png")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize) -> ImageData<'static> {
+        // Noise, so the encoded size actually depends on the pixel count
+        let bytes = (0..width * height * 4)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect::<Vec<u8>>();
+        ImageData {
+            width,
+            height,
+            bytes: bytes.into(),
+        }
+    }
+
+    #[test]
+    fn test_pixel_limit_downscales_or_skips() {
+        let mut policy = ImagePolicy {
+            max_pixels: 10_000,
+            ..Default::default()
+        };
+        let encoded = encode_image(&image(400, 100), 7, &policy).unwrap();
+        assert_eq!((encoded.width, encoded.height), (200, 50));
+        assert_eq!(encoded.hash, 7);
+        assert!(encoded.thumbnail.is_none());
+
+        policy.oversized = OversizedImageAction::Skip;
+        assert_eq!(
+            encode_image(&image(400, 100), 7, &policy).unwrap_err(),
+            ImageRejected::TooLarge {
+                width: 400,
+                height: 100
+            }
+        );
+        assert!(encode_image(&image(100, 100), 7, &policy).is_ok());
+    }
+
+    #[test]
+    fn test_byte_limit_and_lossy_format() {
+        let policy = ImagePolicy {
+            max_bytes: 20_000,
+            format: StoredImageFormat::Jpeg,
+            ..Default::default()
+        };
+        let encoded = encode_image(&image(300, 300), 1, &policy).unwrap();
+        assert!(encoded.bytes.len() <= 20_000);
+        assert!(encoded.width < 300);
+        assert_eq!(mime_type(&encoded.bytes), "image/jpeg");
+    }
+}
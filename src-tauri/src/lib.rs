@@ -1,14 +1,28 @@
 //! Windows 11 Clipboard History For Linux Library
 //! This module re-exports the core functionality for use as a library
 
+pub mod active_window;
 pub mod autostart_manager;
+pub mod blob_store;
 pub mod clipboard_manager;
+pub mod clipboard_monitor;
+pub mod clipboard_targets;
+pub mod clipboard_watcher;
 pub mod config_manager;
+pub mod copied_files;
+pub mod desktop_notification;
 pub mod emoji_manager;
 pub mod focus_manager;
 pub mod gif_manager;
+pub mod history_crypto;
+pub mod history_retention;
+pub mod history_search;
+pub mod history_store;
+pub mod image_policy;
 pub mod input_simulator;
 pub mod permission_checker;
+pub mod selection_owner;
+pub mod sensitive_paste;
 pub mod session;
 pub mod shortcut_conflict_detector;
 pub mod shortcut_setup;
@@ -17,6 +31,13 @@ pub mod user_settings;
 
 #[cfg(target_os = "linux")]
 pub mod linux_shortcut_manager;
+#[cfg(target_os = "linux")]
+pub mod logind_listener;
+#[cfg(target_os = "linux")]
+pub mod wayland_data_control;
+
+#[cfg(test)]
+mod test_support;
 
 pub use clipboard_manager::{ClipboardContent, ClipboardItem, ClipboardManager};
 pub use config_manager::ConfigManager;
@@ -0,0 +1,167 @@
+//! Logind Listener Module
+//! Clears history when the session locks or the machine suspends or shuts down.
+//!
+//! Listens to `org.freedesktop.login1` on the system bus: the session's `Lock` signal
+//! (or its `LockedHint` becoming true, which is how most desktops report a lock screen)
+//! and the manager's `PrepareForSleep` / `PrepareForShutdown`. A delay inhibitor is
+//! held so the history is cleared before the machine actually goes down.
+//! What gets deleted is chosen by the `clear_on_lock` setting (see `LockClearAction`).
+
+use crate::clipboard_manager::ClipboardManager;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+const LOGIN1: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+/// Session events that clear history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionEvent {
+    Lock,
+    Sleep,
+    Shutdown,
+    /// Resumed from sleep; the inhibitor has to be taken again
+    Wake,
+}
+
+/// Listens for lock, sleep and shutdown until the system bus connection ends
+pub async fn listen(
+    manager: Arc<Mutex<ClipboardManager>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use futures_lite::stream::StreamExt;
+    use zbus::{Connection, MatchRule, MessageStream};
+
+    let connection = Connection::system().await?;
+    let session = own_session(&connection).await;
+
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .sender(LOGIN1)?
+        .build();
+    let mut stream = MessageStream::for_match_rule(rule, &connection, None).await?;
+    let mut inhibitor = take_inhibitor(&connection).await;
+
+    eprintln!("[LogindListener] Listening for lock, sleep and shutdown signals...");
+
+    while let Some(msg) = stream.next().await {
+        let Ok(msg) = msg else {
+            continue;
+        };
+        let Some(event) = parse_signal(&msg, session.as_ref()) else {
+            continue;
+        };
+
+        if event == SessionEvent::Wake {
+            if inhibitor.is_none() {
+                inhibitor = take_inhibitor(&connection).await;
+            }
+            continue;
+        }
+
+        eprintln!("[LogindListener] {:?}, clearing history", event);
+        manager.lock().clear_for_lock();
+        if event != SessionEvent::Lock {
+            // Done: let the sleep or shutdown go ahead
+            inhibitor = None;
+        }
+    }
+
+    Ok(())
+}
+
+/// Object path of the session this app runs in, so other users' locks are ignored.
+/// `None` when it cannot be determined; locks of any session are then honoured.
+async fn own_session(connection: &zbus::Connection) -> Option<zbus::zvariant::OwnedObjectPath> {
+    // "auto" resolves to the caller's session, or the user's graphical session for
+    // processes started outside one (e.g. by a systemd user unit)
+    let id = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string());
+    let reply = connection
+        .call_method(
+            Some(LOGIN1),
+            LOGIN1_PATH,
+            Some(MANAGER_INTERFACE),
+            "GetSession",
+            &(id.as_str(),),
+        )
+        .await;
+    match reply.and_then(|reply| reply.body().deserialize()) {
+        Ok(path) => Some(path),
+        Err(e) => {
+            eprintln!(
+                "[LogindListener] Failed to find own session ({}), watching all sessions",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Delays sleep and shutdown until the returned descriptor is closed
+async fn take_inhibitor(connection: &zbus::Connection) -> Option<zbus::zvariant::OwnedFd> {
+    let reply = connection
+        .call_method(
+            Some(LOGIN1),
+            LOGIN1_PATH,
+            Some(MANAGER_INTERFACE),
+            "Inhibit",
+            &(
+                "sleep:shutdown",
+                "Clipboard History",
+                "Clearing clipboard history",
+                "delay",
+            ),
+        )
+        .await;
+    match reply.and_then(|reply| reply.body().deserialize()) {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            eprintln!("[LogindListener] Failed to take inhibitor lock: {}", e);
+            None
+        }
+    }
+}
+
+fn parse_signal(
+    msg: &zbus::Message,
+    session: Option<&zbus::zvariant::OwnedObjectPath>,
+) -> Option<SessionEvent> {
+    use std::collections::HashMap;
+    use zbus::zvariant::OwnedValue;
+
+    let header = msg.header();
+    let interface = header.interface()?.as_str();
+    let member = header.member()?.as_str();
+    let from_session = session.map_or(true, |session| {
+        header.path().map(|path| path.as_str()) == Some(session.as_str())
+    });
+
+    match (interface, member) {
+        (SESSION_INTERFACE, "Lock") if from_session => Some(SessionEvent::Lock),
+        (MANAGER_INTERFACE, "PrepareForSleep") => {
+            let start: bool = msg.body().deserialize().ok()?;
+            Some(if start {
+                SessionEvent::Sleep
+            } else {
+                SessionEvent::Wake
+            })
+        }
+        (MANAGER_INTERFACE, "PrepareForShutdown") => {
+            let start: bool = msg.body().deserialize().ok()?;
+            start.then_some(SessionEvent::Shutdown)
+        }
+        ("org.freedesktop.DBus.Properties", "PropertiesChanged") if from_session => {
+            let body = msg.body();
+            let (changed_interface, changed, _invalidated) = body
+                .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+                .ok()?;
+            let locked = changed
+                .get("LockedHint")
+                .and_then(|value| value.downcast_ref::<bool>().ok());
+            (changed_interface == SESSION_INTERFACE && locked == Some(true))
+                .then_some(SessionEvent::Lock)
+        }
+        _ => None,
+    }
+}
@@ -7,18 +7,24 @@ use std::sync::Arc;
 use std::time::Duration;
 use tauri::{
     image::Image,
-    menu::{Menu, MenuItem},
+    menu::{Menu, MenuItem, Submenu},
     tray::{MouseButton, TrayIconBuilder, TrayIconEvent},
     AppHandle, Emitter, Manager, Monitor, PhysicalPosition, PhysicalSize, State, WebviewWindow,
     WindowEvent,
 };
 use win11_clipboard_history_lib::autostart_manager;
-use win11_clipboard_history_lib::clipboard_manager::{ClipboardItem, ClipboardManager};
+use win11_clipboard_history_lib::clipboard_manager::{
+    CaptureState, ClipboardItem, ClipboardItemSummary, ClipboardManager, HistoryEvent, HistoryPage,
+    HistoryStats, LockClearAction, SelectionSync, StartupRestore, DEFAULT_PAGE_SIZE,
+};
+use win11_clipboard_history_lib::clipboard_monitor;
 use win11_clipboard_history_lib::config_manager::{resolve_window_position, ConfigManager};
+use win11_clipboard_history_lib::desktop_notification;
 use win11_clipboard_history_lib::emoji_manager::{EmojiManager, EmojiUsage};
 #[cfg(target_os = "linux")]
 use win11_clipboard_history_lib::focus_manager::x11_robust_activate;
 use win11_clipboard_history_lib::focus_manager::{restore_focused_window, save_focused_window};
+use win11_clipboard_history_lib::history_search::SearchResults;
 use win11_clipboard_history_lib::input_simulator::simulate_paste_keystroke;
 use win11_clipboard_history_lib::permission_checker;
 use win11_clipboard_history_lib::session::is_wayland;
@@ -45,9 +51,23 @@ pub struct AppState {
 
 // --- Commands ---
 
+/// One page of history summaries; pass the returned `next_cursor` to get the next one
+#[tauri::command]
+fn get_history_page(
+    state: State<AppState>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<HistoryPage, String> {
+    state
+        .clipboard_manager
+        .lock()
+        .get_history_page(cursor.as_deref(), limit.unwrap_or(DEFAULT_PAGE_SIZE))
+}
+
+/// Full content of one item (the list only carries summaries)
 #[tauri::command]
-fn get_history(state: State<AppState>) -> Vec<ClipboardItem> {
-    state.clipboard_manager.lock().get_history()
+fn get_item(state: State<AppState>, id: String) -> Option<ClipboardItem> {
+    state.clipboard_manager.lock().get_item(&id).cloned()
 }
 
 #[tauri::command]
@@ -61,14 +81,118 @@ fn delete_item(state: State<AppState>, id: String) {
 }
 
 #[tauri::command]
-fn toggle_pin(state: State<AppState>, id: String) -> Option<ClipboardItem> {
-    let result = state.clipboard_manager.lock().toggle_pin(&id);
+fn toggle_pin(state: State<AppState>, id: String) -> Option<ClipboardItemSummary> {
+    let result = state
+        .clipboard_manager
+        .lock()
+        .toggle_pin(&id)
+        .map(|item| item.summary());
     if result.is_none() {
         eprintln!("[toggle_pin] Item with id '{}' not found in history.", id);
     }
     result
 }
 
+/// Marks an item as sensitive (taken off the clipboard again after a paste) or back
+#[tauri::command]
+fn toggle_sensitive(state: State<AppState>, id: String) -> Option<ClipboardItemSummary> {
+    let result = state
+        .clipboard_manager
+        .lock()
+        .toggle_sensitive(&id)
+        .map(|item| item.summary());
+    if result.is_none() {
+        eprintln!(
+            "[toggle_sensitive] Item with id '{}' not found in history.",
+            id
+        );
+    }
+    result
+}
+
+/// One page of the summaries of items copied from a given application
+/// (see `ClipboardItem::source_app`)
+#[tauri::command]
+fn get_history_by_app(
+    state: State<AppState>,
+    app: String,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<HistoryPage, String> {
+    state.clipboard_manager.lock().get_history_page_for_app(
+        &app,
+        cursor.as_deref(),
+        limit.unwrap_or(DEFAULT_PAGE_SIZE),
+    )
+}
+
+/// Searches the history on the backend, e.g. "invoice type:text app:firefox after:2024-05-01"
+#[tauri::command]
+fn search_history(
+    state: State<AppState>,
+    query: String,
+    regex: Option<bool>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<SearchResults, String> {
+    state.clipboard_manager.lock().search_history(
+        &query,
+        regex.unwrap_or(false),
+        offset.unwrap_or(0),
+        limit.unwrap_or(DEFAULT_PAGE_SIZE),
+    )
+}
+
+/// Item counts and storage sizes, overall and per content type
+#[tauri::command]
+fn get_history_stats(state: State<AppState>) -> HistoryStats {
+    state.clipboard_manager.lock().get_history_stats()
+}
+
+#[tauri::command]
+fn get_source_apps(state: State<AppState>) -> Vec<String> {
+    state.clipboard_manager.lock().get_source_apps()
+}
+
+/// Load the full PNG of an image item on demand (base64), instead of shipping it with the history
+#[tauri::command]
+fn get_image_data(state: State<AppState>, id: String) -> Result<String, String> {
+    state.clipboard_manager.lock().get_image_base64(&id)
+}
+
+/// Load the list thumbnail of an image item (base64 PNG, at most 256px on its longest side)
+#[tauri::command]
+fn get_thumbnail(state: State<AppState>, id: String) -> Result<String, String> {
+    state.clipboard_manager.lock().get_thumbnail_base64(&id)
+}
+
+/// Why the stored history could not be unlocked at startup, if it could not
+#[tauri::command]
+fn get_encryption_error(state: State<AppState>) -> Option<String> {
+    state
+        .clipboard_manager
+        .lock()
+        .encryption_error()
+        .map(str::to_string)
+}
+
+#[tauri::command]
+fn get_capture_state(state: State<AppState>) -> CaptureState {
+    state.clipboard_manager.lock().capture_state()
+}
+
+/// Pauses recording (resuming by itself after `minutes`, if given) or resumes it
+#[tauri::command]
+fn set_capture_paused(state: State<AppState>, paused: bool, minutes: Option<u32>) -> CaptureState {
+    let request = if paused {
+        PauseRequest::Pause { minutes }
+    } else {
+        PauseRequest::Resume
+    };
+    request.apply(&state.clipboard_manager);
+    state.clipboard_manager.lock().capture_state()
+}
+
 #[tauri::command]
 fn get_recent_emojis(state: State<AppState>) -> Vec<EmojiUsage> {
     state.emoji_manager.lock().get_recent()
@@ -91,8 +215,16 @@ fn get_user_settings() -> Result<UserSettings, String> {
 fn set_user_settings(
     app: AppHandle,
     state: State<AppState>,
-    new_settings: UserSettings,
+    mut new_settings: UserSettings,
 ) -> Result<(), String> {
+    new_settings.validate();
+
+    // Apply encryption first so a failed migration (e.g. locked keyring) is not saved as enabled
+    state
+        .clipboard_manager
+        .lock()
+        .configure_encryption(&new_settings.encryption_config())?;
+
     let manager = UserSettingsManager::new();
     manager.save(&new_settings)?;
 
@@ -102,6 +234,20 @@ fn set_user_settings(
         if clipboard_manager.get_max_history_size() != new_settings.max_history_size {
             clipboard_manager.set_max_history_size(new_settings.max_history_size);
         }
+        clipboard_manager.set_concealed_expiry(new_settings.concealed_expiry_secs);
+        clipboard_manager
+            .set_lock_clear_action(LockClearAction::from_setting(&new_settings.clear_on_lock));
+        clipboard_manager.set_excluded_apps(new_settings.excluded_apps.clone());
+        clipboard_manager.set_sensitive_paste(new_settings.sensitive_paste_policy());
+        clipboard_manager.set_transient_paste(new_settings.transient_paste);
+        clipboard_manager.set_persist_clipboard(new_settings.persist_clipboard);
+        clipboard_manager.set_image_policy(new_settings.image_policy());
+        clipboard_manager.set_retention(new_settings.retention_policy());
+        clipboard_manager.set_budget(new_settings.history_budget());
+        clipboard_manager.set_primary_selection(
+            new_settings.capture_primary_selection,
+            SelectionSync::from_setting(&new_settings.selection_sync),
+        );
     }
 
     // Emit event to notify all windows that settings have changed
@@ -153,18 +299,23 @@ async fn paste_item(app: AppHandle, state: State<'_, AppState>, id: String) -> R
             WindowController::hide(&app);
             PasteHelper::prepare_target_window().await?;
 
-            // 3. Perform Paste
+            // 3. Save the clipboard if the paste is given back (outside the lock)
+            let save = state.clipboard_manager.lock().begin_paste(Some(&item));
+            let previous = save.run();
+
+            // 4. Perform Paste
             let mut manager = state.clipboard_manager.lock();
-            manager.paste_item(&item).map_err(|e| e.to_string())?;
+            manager
+                .paste_item(&item, previous)
+                .map_err(|e| e.to_string())?;
         }
         None => {
             eprintln!(
                 "[paste_item] Item with id '{}' not found in history. Syncing frontend...",
                 id
             );
-            // Emit event to trigger frontend refresh
-            let history = state.clipboard_manager.lock().get_history();
-            let _ = app.emit("history-sync", &history);
+            // Tell the frontend to drop its stale copy
+            emit_history_event(&app, HistoryEvent::Removed { id: id.clone() });
             return Err(format!("Item '{}' not found. History has been synced.", id));
         }
     }
@@ -189,7 +340,11 @@ async fn paste_text(
     WindowController::hide(&app);
     PasteHelper::prepare_target_window().await?;
 
-    // 2. Set Clipboard & Mark
+    // 2. Save the clipboard for a transient paste (outside the lock)
+    let save = state.clipboard_manager.lock().begin_paste(None);
+    let previous = save.run();
+
+    // 3. Set Clipboard & Mark
     {
         let mut manager = state.clipboard_manager.lock();
         manager.mark_text_as_pasted(&text);
@@ -201,9 +356,16 @@ async fn paste_text(
             .map_err(|e| e.to_string())?;
     }
 
-    // 3. Simulate Paste
+    // 4. Simulate Paste
     simulate_paste_keystroke().map_err(|e| e.to_string())?;
 
+    // 5. Give the clipboard back in transient mode
+    let pasted = ClipboardItem::new_text(text);
+    state
+        .clipboard_manager
+        .lock()
+        .end_transient_paste(previous, Some(&pasted));
+
     Ok(())
 }
 
@@ -213,6 +375,10 @@ async fn paste_gif_from_url(
     state: State<'_, AppState>,
     url: String,
 ) -> Result<(), String> {
+    // 0. Save the clipboard for a transient paste before the GIF replaces it (outside the lock)
+    let save = state.clipboard_manager.lock().begin_paste(None);
+    let previous = save.run();
+
     // 1. Download (Blocking) - Window stays open to show loading if UI supports it
     let url_clone = url.clone();
     let file_uri = tokio::task::spawn_blocking(move || {
@@ -238,6 +404,12 @@ async fn paste_gif_from_url(
     // The clipboard is already set by paste_gif_to_clipboard_with_uri, we just need to paste
     simulate_paste_keystroke().map_err(|e| e.to_string())?;
 
+    // 4. Give the clipboard back in transient mode
+    state
+        .clipboard_manager
+        .lock()
+        .end_transient_paste(previous, None);
+
     Ok(())
 }
 
@@ -564,6 +736,132 @@ impl SettingsController {
     }
 }
 
+// --- Capture Pause ---
+
+/// Pause or resume requested from the tray menu or the command line
+#[derive(Debug, Clone, Copy)]
+enum PauseRequest {
+    /// Pause for `minutes`, or until resumed
+    Pause {
+        minutes: Option<u32>,
+    },
+    Resume,
+}
+
+impl PauseRequest {
+    /// Parses `--pause`, `--pause=MINUTES` or `--resume`
+    fn from_args(args: &[String]) -> Option<Self> {
+        args.iter().find_map(|arg| match arg.as_str() {
+            "--resume" => Some(Self::Resume),
+            "--pause" => Some(Self::Pause { minutes: None }),
+            _ => {
+                let minutes = arg.strip_prefix("--pause=")?;
+                // An invalid timer still pauses; resuming early would defeat the purpose
+                let minutes = minutes.parse().ok().filter(|minutes| *minutes > 0);
+                if minutes.is_none() {
+                    eprintln!("[Pause] Ignoring invalid timer in '{}'", arg);
+                }
+                Some(Self::Pause { minutes })
+            }
+        })
+    }
+
+    /// Parses a tray menu id ("resume", "pause-forever" or "pause-MINUTES")
+    fn from_menu_id(id: &str) -> Option<Self> {
+        match id {
+            "resume" => Some(Self::Resume),
+            "pause-forever" => Some(Self::Pause { minutes: None }),
+            _ => {
+                let minutes = id.strip_prefix("pause-")?.parse().ok()?;
+                Some(Self::Pause {
+                    minutes: Some(minutes),
+                })
+            }
+        }
+    }
+
+    fn apply(self, manager: &Mutex<ClipboardManager>) {
+        let mut manager = manager.lock();
+        match self {
+            Self::Pause { minutes } => manager
+                .pause_capture(minutes.map(|minutes| chrono::Duration::minutes(minutes.into()))),
+            Self::Resume => manager.resume_capture(),
+        }
+    }
+}
+
+// --- Tray ---
+
+const TRAY_ID: &str = "main";
+const TRAY_TOOLTIP: &str = "Clipboard History";
+const TRAY_ICON_PNG: &[u8] = include_bytes!("../icons/icon.png");
+
+/// Timers offered in the tray's pause submenu, in minutes
+const PAUSE_MENU_MINUTES: [u32; 3] = [5, 15, 60];
+
+/// Tray menu entries that depend on the capture state
+struct TrayMenu {
+    pause: Submenu<tauri::Wry>,
+    resume: MenuItem<tauri::Wry>,
+}
+
+fn tray_icon() -> Image<'static> {
+    Image::from_bytes(TRAY_ICON_PNG).unwrap()
+}
+
+/// The tray icon greyed out and faded, shown while capture is paused
+fn paused_tray_icon() -> Image<'static> {
+    let mut icon = match image::load_from_memory(TRAY_ICON_PNG) {
+        Ok(icon) => icon.grayscale().to_rgba8(),
+        Err(e) => {
+            eprintln!("[Tray] Failed to decode icon: {}", e);
+            return tray_icon();
+        }
+    };
+    for pixel in icon.pixels_mut() {
+        pixel[3] /= 2;
+    }
+    let (width, height) = icon.dimensions();
+    Image::new_owned(icon.into_raw(), width, height)
+}
+
+/// Shows the capture state in the tray icon, tooltip and menu. Must run on the main thread.
+fn update_tray(app: &AppHandle, capture: CaptureState) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let (icon, tooltip) = match capture {
+            CaptureState::Active => (tray_icon(), TRAY_TOOLTIP.to_string()),
+            CaptureState::Paused { until: Some(until) } => (
+                paused_tray_icon(),
+                format!(
+                    "{} (paused until {})",
+                    TRAY_TOOLTIP,
+                    until.with_timezone(&chrono::Local).format("%H:%M")
+                ),
+            ),
+            CaptureState::Paused { until: None } => {
+                (paused_tray_icon(), format!("{} (paused)", TRAY_TOOLTIP))
+            }
+        };
+        if let Err(e) = tray.set_icon(Some(icon)) {
+            eprintln!("[Tray] Failed to set icon: {}", e);
+        }
+        if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+            eprintln!("[Tray] Failed to set tooltip: {}", e);
+        }
+    }
+
+    if let Some(menu) = app.try_state::<TrayMenu>() {
+        let paused = capture.is_paused();
+        if let Err(e) = menu
+            .pause
+            .set_enabled(!paused)
+            .and_then(|_| menu.resume.set_enabled(paused))
+        {
+            eprintln!("[Tray] Failed to update menu: {}", e);
+        }
+    }
+}
+
 // --- Window Event Helper ---
 
 fn handle_window_moved_for_wayland(
@@ -588,47 +886,19 @@ fn handle_window_moved_for_wayland(
 
 // --- Background Listeners ---
 
-fn start_clipboard_watcher(app: AppHandle, clipboard_manager: Arc<Mutex<ClipboardManager>>) {
-    std::thread::spawn(move || {
-        let mut last_text_hash: Option<u64> = None;
-        let mut last_image_hash: Option<u64> = None;
-
-        loop {
-            std::thread::sleep(Duration::from_millis(500));
-            let mut manager = clipboard_manager.lock();
-
-            // Text
-            if let Ok(text) = manager.get_current_text() {
-                if !text.is_empty() {
-                    let text_hash =
-                        win11_clipboard_history_lib::clipboard_manager::calculate_hash(&text);
-
-                    if Some(text_hash) != last_text_hash {
-                        last_text_hash = Some(text_hash);
-                        last_image_hash = None;
-
-                        // Try to get HTML content for rich text support
-                        let html = manager.get_current_html();
-
-                        if let Some(item) = manager.add_text(text, html) {
-                            let _ = app.emit("clipboard-changed", &item);
-                        }
-                    }
-                }
-            }
-
-            // Image
-            if let Ok(Some((image_data, hash))) = manager.get_current_image() {
-                if Some(hash) != last_image_hash {
-                    last_image_hash = Some(hash);
-                    last_text_hash = None;
-                    if let Some(item) = manager.add_image(image_data, hash) {
-                        let _ = app.emit("clipboard-changed", &item);
-                    }
-                }
-            }
+/// Forwards a history change to the frontend
+fn emit_history_event(app: &AppHandle, event: HistoryEvent) {
+    if let HistoryEvent::CaptureChanged(capture) = event {
+        // Queued rather than run here: the manager is locked while it reports changes,
+        // and waiting on the main thread could deadlock with a command it runs
+        let app_for_tray = app.clone();
+        if let Err(e) = app.run_on_main_thread(move || update_tray(&app_for_tray, capture)) {
+            eprintln!("[Tray] Failed to schedule update: {}", e);
         }
-    });
+    }
+    if let Err(e) = app.emit(event.name(), &event) {
+        eprintln!("[History] Failed to emit {}: {}", event.name(), e);
+    }
 }
 
 // --- Main ---
@@ -657,6 +927,9 @@ fn main() {
         println!("        --background Start minimized to system tray (for autostart)");
         println!("        --settings   Open settings window on startup");
         println!("        --emoji      Open with emoji picker tab selected");
+        println!("        --pause[=MINUTES]");
+        println!("                     Stop recording copies, optionally resuming after MINUTES");
+        println!("        --resume     Resume recording copies");
         println!();
         println!("SHORTCUTS:");
         println!("    Super+V          Open clipboard history");
@@ -678,6 +951,9 @@ fn main() {
     // Check if --emoji flag is present (open with emoji tab)
     let open_emoji_on_start = args.iter().any(|arg| arg == "--emoji");
 
+    // Check for --pause / --resume (recording state at startup)
+    let pause_on_start = PauseRequest::from_args(&args);
+
     // Clone for use in setup closure
     let start_in_background_clone = start_in_background;
     let open_emoji_on_start_clone = open_emoji_on_start;
@@ -694,14 +970,33 @@ fn main() {
         eprintln!("Failed to create base directory: {}", e);
     }
 
-    let history_path = base_dir.join("history.json");
-
-    // Load user settings to get max_history_size
+    // Load user settings to get max_history_size and encryption preferences
     let user_settings = UserSettingsManager::new().load();
     let clipboard_manager = Arc::new(Mutex::new(ClipboardManager::new(
-        history_path,
+        &base_dir,
         user_settings.max_history_size,
+        &user_settings.encryption_config(),
     )));
+    {
+        let mut manager = clipboard_manager.lock();
+        manager.set_concealed_expiry(user_settings.concealed_expiry_secs);
+        manager.set_lock_clear_action(LockClearAction::from_setting(&user_settings.clear_on_lock));
+        manager.set_excluded_apps(user_settings.excluded_apps.clone());
+        manager.set_sensitive_paste(user_settings.sensitive_paste_policy());
+        manager.set_transient_paste(user_settings.transient_paste);
+        manager.set_persist_clipboard(user_settings.persist_clipboard);
+        manager.set_image_policy(user_settings.image_policy());
+        manager.set_retention(user_settings.retention_policy());
+        manager.set_budget(user_settings.history_budget());
+        manager.set_primary_selection(
+            user_settings.capture_primary_selection,
+            SelectionSync::from_setting(&user_settings.selection_sync),
+        );
+    }
+    if let Some(request) = pause_on_start {
+        request.apply(&clipboard_manager);
+    }
+    let restore_on_startup = StartupRestore::from_setting(&user_settings.restore_on_startup);
 
     let emoji_manager = Arc::new(Mutex::new(EmojiManager::new(base_dir.clone())));
 
@@ -714,8 +1009,11 @@ fn main() {
         // Single Instance Plugin: When user triggers shortcut and app is already running,
         // the OS launches a new instance which signals the existing one to toggle
         .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
-            // Check if --settings flag is present
-            if argv.iter().any(|arg| arg == "--settings") {
+            // --pause / --resume only change the recording state of this instance
+            if let Some(request) = PauseRequest::from_args(&argv) {
+                println!("[SingleInstance] Secondary instance with {:?}", request);
+                request.apply(&app.state::<AppState>().clipboard_manager);
+            } else if argv.iter().any(|arg| arg == "--settings") {
                 println!(
                     "[SingleInstance] Secondary instance with --settings flag, opening settings..."
                 );
@@ -759,24 +1057,47 @@ fn main() {
             let show = MenuItem::with_id(app, "show", "Show Clipboard", true, None::<&str>)?;
             let settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
             let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&show, &settings, &quit])?;
 
-            let icon = Image::from_bytes(include_bytes!("../icons/icon.png")).unwrap();
+            // Capture pause: timers in a submenu, and a separate entry to resume early
+            let pause = Submenu::with_id(app, "pause", "Pause Capture", true)?;
+            for minutes in PAUSE_MENU_MINUTES {
+                let label = format!("For {} Minutes", minutes);
+                let id = format!("pause-{}", minutes);
+                pause.append(&MenuItem::with_id(app, id, label, true, None::<&str>)?)?;
+            }
+            pause.append(&MenuItem::with_id(
+                app,
+                "pause-forever",
+                "Until Resumed",
+                true,
+                None::<&str>,
+            )?)?;
+            let resume = MenuItem::with_id(app, "resume", "Resume Capture", false, None::<&str>)?;
+
+            let menu = Menu::with_items(app, &[&show, &pause, &resume, &settings, &quit])?;
+            app.manage(TrayMenu {
+                pause: pause.clone(),
+                resume: resume.clone(),
+            });
 
             // Get temp directory for tray icon (avoids permission issues with XDG_RUNTIME_DIR)
             let temp_dir = std::env::temp_dir().join("win11-clipboard-history");
             std::fs::create_dir_all(&temp_dir).ok();
 
-            let _tray = TrayIconBuilder::new()
-                .icon(icon)
-                .tooltip("Clipboard History")
+            let _tray = TrayIconBuilder::with_id(TRAY_ID)
+                .icon(tray_icon())
+                .tooltip(TRAY_TOOLTIP)
                 .temp_dir_path(temp_dir)
                 .menu(&menu)
                 .on_menu_event(move |app, event| match event.id.as_ref() {
                     "quit" => app.exit(0),
                     "show" => WindowController::toggle(app),
                     "settings" => SettingsController::show(app),
-                    _ => {}
+                    id => {
+                        if let Some(request) = PauseRequest::from_menu_id(id) {
+                            request.apply(&app.state::<AppState>().clipboard_manager);
+                        }
+                    }
                 })
                 .on_tray_icon_event(|tray, event| {
                     if let TrayIconEvent::Click {
@@ -789,6 +1110,10 @@ fn main() {
                 })
                 .build(app)?;
 
+            // Capture may already be paused by --pause
+            let capture = clipboard_manager.lock().capture_state();
+            update_tray(&app_handle, capture);
+
             // Verify that settings window was created from config
             if app.get_webview_window("settings").is_none() {
                 eprintln!("[Setup] FATAL: Settings window missing from config");
@@ -845,7 +1170,23 @@ fn main() {
                 _ => {}
             });
 
-            start_clipboard_watcher(app_handle.clone(), clipboard_manager.clone());
+            // History changes reach the frontend as fine-grained events
+            let app_handle_for_events = app_handle.clone();
+            clipboard_manager
+                .lock()
+                .set_event_sink(Box::new(move |event| {
+                    emit_history_event(&app_handle_for_events, event)
+                }));
+
+            // Not done earlier in main(): secondary instances run that part too
+            if let Some(error) = clipboard_manager.lock().encryption_error() {
+                desktop_notification::notify(
+                    "Clipboard history is locked",
+                    &format!("{}. History will not be saved until this is fixed.", error),
+                );
+            }
+            clipboard_manager.lock().restore_clipboard(restore_on_startup);
+            clipboard_monitor::start(clipboard_manager.clone());
 
             // Start theme change listener (D-Bus event-based, more efficient than polling)
             #[cfg(target_os = "linux")]
@@ -860,6 +1201,20 @@ fn main() {
                 });
             }
 
+            // Clear history on screen lock, suspend and shutdown (logind signals)
+            #[cfg(target_os = "linux")]
+            {
+                let manager_for_logind = clipboard_manager.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) =
+                        win11_clipboard_history_lib::logind_listener::listen(manager_for_logind)
+                            .await
+                    {
+                        eprintln!("[LogindListener] Failed to listen: {}", e);
+                    }
+                });
+            }
+
             // Register global shortcut (Super+V) with the desktop environment
             // This runs in a background thread to avoid blocking startup
             #[cfg(target_os = "linux")]
@@ -922,10 +1277,21 @@ fn main() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
-            get_history,
+            get_history_page,
+            get_item,
             clear_history,
             delete_item,
             toggle_pin,
+            toggle_sensitive,
+            get_image_data,
+            get_thumbnail,
+            get_history_stats,
+            get_encryption_error,
+            get_capture_state,
+            set_capture_paused,
+            get_history_by_app,
+            search_history,
+            get_source_apps,
             paste_item,
             paste_text,
             get_recent_emojis,
@@ -0,0 +1,397 @@
+//! Selection Owner Module
+//! Puts content on the clipboard in several formats at once.
+//!
+//! arboard can only offer text, HTML or an image, but some content has to be offered
+//! as a set of targets (copied files need `text/uri-list` and `x-special/gnome-copied-files`
+//! together). Here the app owns the selection itself: on X11 through a hidden window that
+//! answers `SelectionRequest` events, on Wayland through a data-control source. A background
+//! thread serves requests until another client takes the selection over.
+
+use crate::clipboard_manager::SelectionKind;
+
+// --- Constants ---
+
+/// Canonical text target; the legacy text names below are served with the same bytes
+pub const UTF8_TEXT_MIME: &str = "text/plain;charset=utf-8";
+const TEXT_ALIASES: &[&str] = &["UTF8_STRING", "text/plain", "STRING", "TEXT"];
+
+/// One representation of the content: MIME type (or X11 target name) and its bytes
+pub type SelectionTarget = (String, Vec<u8>);
+
+// --- Public API ---
+
+/// Takes ownership of `selection` and serves `targets` until another client claims it.
+pub fn offer_selection(
+    selection: SelectionKind,
+    targets: Vec<SelectionTarget>,
+) -> Result<(), String> {
+    if targets.is_empty() {
+        return Err("Nothing to offer".to_string());
+    }
+    let targets = with_text_aliases(targets);
+
+    #[cfg(target_os = "linux")]
+    if crate::session::is_wayland() {
+        match crate::wayland_data_control::offer_selection(selection, targets.clone()) {
+            Ok(()) => return Ok(()),
+            // XWayland forwards its selection to Wayland clients
+            Err(e) => eprintln!(
+                "[SelectionOwner] Wayland data-control unavailable ({}), using X11",
+                e
+            ),
+        }
+    }
+
+    x11::offer_selection(selection, targets)
+}
+
+/// Adds the legacy text target names when UTF-8 text is offered
+fn with_text_aliases(mut targets: Vec<SelectionTarget>) -> Vec<SelectionTarget> {
+    let Some(text) = targets
+        .iter()
+        .find(|(mime, _)| mime == UTF8_TEXT_MIME)
+        .map(|(_, bytes)| bytes.clone())
+    else {
+        return targets;
+    };
+    for alias in TEXT_ALIASES {
+        if !targets.iter().any(|(mime, _)| mime == alias) {
+            targets.push((alias.to_string(), text.clone()));
+        }
+    }
+    targets
+}
+
+// --- X11 ---
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::SelectionTarget;
+    use crate::clipboard_manager::SelectionKind;
+    use crate::clipboard_watcher::poll_readable;
+    use std::os::unix::io::AsRawFd;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux, EventMask,
+        PropMode, Property, PropertyNotifyEvent, SelectionNotifyEvent, SelectionRequestEvent,
+        Window, WindowClass, SELECTION_NOTIFY_EVENT,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+
+    /// Room for the ChangeProperty request header
+    const REQUEST_OVERHEAD: usize = 64;
+    /// Time a requestor gets to take each chunk of an INCR transfer before it is dropped
+    const INCR_TIMEOUT: Duration = Duration::from_secs(5);
+
+    struct Owner {
+        conn: RustConnection,
+        window: Window,
+        selection: Atom,
+        targets_atom: Atom,
+        incr_atom: Atom,
+        targets: Vec<(Atom, Vec<u8>)>,
+        /// Largest property written in one request; bigger targets are sent with INCR
+        chunk_size: usize,
+        transfers: Vec<IncrTransfer>,
+    }
+
+    /// A target too large for one request, sent in chunks (ICCCM INCR protocol)
+    struct IncrTransfer {
+        requestor: Window,
+        property: Atom,
+        /// Index into `Owner::targets`
+        target: usize,
+        /// Bytes sent so far
+        offset: usize,
+        updated: Instant,
+    }
+
+    pub fn offer_selection(
+        selection: SelectionKind,
+        targets: Vec<SelectionTarget>,
+    ) -> Result<(), String> {
+        let (conn, screen_num) =
+            x11rb::connect(None).map_err(|e| format!("X11 connect failed: {}", e))?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let intern = |name: &str| -> Result<Atom, String> {
+            conn.intern_atom(false, name.as_bytes())
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map(|reply| reply.atom)
+                .map_err(|e| format!("Failed to intern {}: {}", name, e))
+        };
+        let selection = match selection {
+            SelectionKind::Clipboard => intern("CLIPBOARD")?,
+            SelectionKind::Primary => AtomEnum::PRIMARY.into(),
+        };
+        let targets_atom = intern("TARGETS")?;
+        let incr_atom = intern("INCR")?;
+        let targets = targets
+            .into_iter()
+            .map(|(name, bytes)| Ok((intern(&name)?, bytes)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let window = conn.generate_id().map_err(|e| e.to_string())?;
+        conn.create_window(
+            0,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new(),
+        )
+        .map_err(|e| format!("Failed to create window: {}", e))?;
+        conn.set_selection_owner(window, selection, x11rb::CURRENT_TIME)
+            .map_err(|e| format!("Failed to set selection owner: {}", e))?;
+
+        let owner = conn
+            .get_selection_owner(selection)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| format!("Failed to query selection owner: {}", e))?
+            .owner;
+        if owner != window {
+            return Err("Another client kept the selection".to_string());
+        }
+
+        let chunk_size = RequestConnection::maximum_request_bytes(&conn) - REQUEST_OVERHEAD;
+        let owner = Owner {
+            conn,
+            window,
+            selection,
+            targets_atom,
+            incr_atom,
+            targets,
+            chunk_size,
+            transfers: Vec::new(),
+        };
+        std::thread::spawn(move || owner.serve());
+        Ok(())
+    }
+
+    impl Owner {
+        /// Answers requests until the selection is taken by someone else,
+        /// then finishes the transfers still running
+        fn serve(mut self) {
+            let fd = self.conn.stream().as_raw_fd();
+            let mut cleared = false;
+            while !(cleared && self.transfers.is_empty()) {
+                let event = match self.conn.poll_for_event() {
+                    Ok(Some(event)) => event,
+                    Ok(None) if self.transfers.is_empty() => match self.conn.wait_for_event() {
+                        Ok(event) => event,
+                        Err(e) => {
+                            eprintln!("[SelectionOwner] X11 connection lost: {}", e);
+                            return;
+                        }
+                    },
+                    Ok(None) => {
+                        // Requestors that stopped taking chunks are given up on
+                        self.transfers
+                            .retain(|transfer| transfer.updated.elapsed() < INCR_TIMEOUT);
+                        if let Err(e) = poll_readable(fd, INCR_TIMEOUT) {
+                            eprintln!("[SelectionOwner] {}", e);
+                            return;
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        eprintln!("[SelectionOwner] X11 connection lost: {}", e);
+                        return;
+                    }
+                };
+
+                match event {
+                    Event::SelectionRequest(request) if !cleared => {
+                        if let Err(e) = self.answer(&request) {
+                            eprintln!("[SelectionOwner] Failed to answer request: {}", e);
+                        }
+                    }
+                    Event::SelectionClear(event) if event.selection == self.selection => {
+                        cleared = true
+                    }
+                    Event::PropertyNotify(event) if event.state == Property::DELETE => {
+                        if let Err(e) = self.send_next_chunk(&event) {
+                            eprintln!("[SelectionOwner] Failed to send chunk: {}", e);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let _ = self.conn.destroy_window(self.window);
+            let _ = self.conn.flush();
+        }
+
+        fn answer(&mut self, request: &SelectionRequestEvent) -> Result<(), String> {
+            // Obsolete clients pass no property and expect the target name to be used
+            let property = if request.property == u32::from(AtomEnum::NONE) {
+                request.target
+            } else {
+                request.property
+            };
+
+            let stored = if request.target == self.targets_atom {
+                let atoms: Vec<Atom> = self
+                    .targets
+                    .iter()
+                    .map(|(atom, _)| *atom)
+                    .chain(std::iter::once(self.targets_atom))
+                    .collect();
+                self.conn
+                    .change_property32(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        property,
+                        AtomEnum::ATOM,
+                        &atoms,
+                    )
+                    .map_err(|e| e.to_string())?;
+                true
+            } else {
+                match self
+                    .targets
+                    .iter()
+                    .position(|(atom, _)| *atom == request.target)
+                {
+                    Some(index) if self.targets[index].1.len() > self.chunk_size => {
+                        self.start_transfer(request.requestor, property, index)?;
+                        true
+                    }
+                    Some(index) => {
+                        let (atom, bytes) = &self.targets[index];
+                        self.conn
+                            .change_property8(
+                                PropMode::REPLACE,
+                                request.requestor,
+                                property,
+                                *atom,
+                                bytes,
+                            )
+                            .map_err(|e| e.to_string())?;
+                        true
+                    }
+                    None => false,
+                }
+            };
+
+            let notify = SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property: if stored {
+                    property
+                } else {
+                    AtomEnum::NONE.into()
+                },
+            };
+            self.conn
+                .send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+                .map_err(|e| e.to_string())?;
+            self.conn.flush().map_err(|e| e.to_string())
+        }
+
+        /// Announces a large target with an INCR property holding its size. The requestor
+        /// deleting that property asks for the first chunk.
+        fn start_transfer(
+            &mut self,
+            requestor: Window,
+            property: Atom,
+            target: usize,
+        ) -> Result<(), String> {
+            // Deletions on the requestor's window are what drive the transfer
+            self.conn
+                .change_window_attributes(
+                    requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )
+                .map_err(|e| e.to_string())?;
+            let size = u32::try_from(self.targets[target].1.len()).unwrap_or(u32::MAX);
+            self.conn
+                .change_property32(
+                    PropMode::REPLACE,
+                    requestor,
+                    property,
+                    self.incr_atom,
+                    &[size],
+                )
+                .map_err(|e| e.to_string())?;
+            self.transfers.push(IncrTransfer {
+                requestor,
+                property,
+                target,
+                offset: 0,
+                updated: Instant::now(),
+            });
+            Ok(())
+        }
+
+        /// Writes the next chunk once the requestor deleted the previous one.
+        /// The transfer ends with an empty chunk.
+        fn send_next_chunk(&mut self, event: &PropertyNotifyEvent) -> Result<(), String> {
+            let Some(index) = self.transfers.iter().position(|transfer| {
+                transfer.requestor == event.window && transfer.property == event.atom
+            }) else {
+                return Ok(());
+            };
+            let transfer = &mut self.transfers[index];
+            let (atom, bytes) = &self.targets[transfer.target];
+            let end = (transfer.offset + self.chunk_size).min(bytes.len());
+            self.conn
+                .change_property8(
+                    PropMode::REPLACE,
+                    transfer.requestor,
+                    transfer.property,
+                    *atom,
+                    &bytes[transfer.offset..end],
+                )
+                .map_err(|e| e.to_string())?;
+
+            if transfer.offset == bytes.len() {
+                self.transfers.remove(index);
+            } else {
+                transfer.offset = end;
+                transfer.updated = Instant::now();
+            }
+            self.conn.flush().map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod x11 {
+    use super::SelectionTarget;
+    use crate::clipboard_manager::SelectionKind;
+
+    pub fn offer_selection(_: SelectionKind, _: Vec<SelectionTarget>) -> Result<(), String> {
+        Err("Selection ownership is only supported on Linux".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_aliases_added_for_utf8_text() {
+        let targets = with_text_aliases(vec![(UTF8_TEXT_MIME.to_string(), b"hi".to_vec())]);
+        assert_eq!(targets.len(), 1 + TEXT_ALIASES.len());
+        assert!(targets
+            .iter()
+            .any(|(mime, bytes)| mime == "UTF8_STRING" && bytes == b"hi"));
+
+        let targets = with_text_aliases(vec![("text/uri-list".to_string(), Vec::new())]);
+        assert_eq!(targets.len(), 1);
+    }
+}
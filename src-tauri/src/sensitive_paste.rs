@@ -0,0 +1,272 @@
+//! Sensitive Paste Module
+//! Takes pasted content off the system clipboard again after a delay.
+//!
+//! Pasting puts content on the system clipboard, where it would stay until something
+//! else is copied. For sensitive items (password-manager secrets, items marked by hand,
+//! or items copied from the configured apps) the clipboard is cleared, or given back the
+//! content it had before the paste, once the delay has passed. In transient paste mode
+//! every paste gives the clipboard back right after the target application read it.
+//! The clipboard monitor checks for a due revert on every wakeup through
+//! `ClipboardManager::take_due_revert` and `revert`.
+//!
+//! The manager only decides what to save and when to revert; reading and writing the
+//! clipboard happens here, without holding the manager lock.
+
+use crate::clipboard_manager::{calculate_hash, ClipboardItem, ClipboardManager, SelectionKind};
+use crate::clipboard_targets;
+use crate::copied_files::CopiedFiles;
+use crate::image_policy;
+use crate::selection_owner::{self, SelectionTarget};
+use arboard::Clipboard;
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+
+/// MIME type the plain text of a clipboard snapshot is stored under
+pub const SNAPSHOT_TEXT_MIME: &str = "text/plain;charset=utf-8";
+
+/// Time the target application gets to read a transient paste before the
+/// previous clipboard comes back
+pub const TRANSIENT_RESTORE_DELAY: Duration = Duration::milliseconds(500);
+
+/// What happens to the clipboard after a sensitive item was pasted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SensitivePasteAction {
+    #[default]
+    Off,
+    /// Empty the clipboard
+    Clear,
+    /// Put back what was on the clipboard before the paste
+    Restore,
+}
+
+impl SensitivePasteAction {
+    /// Parses the `sensitive_paste_action` user setting ("off", "clear", "restore")
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "clear" => SensitivePasteAction::Clear,
+            "restore" => SensitivePasteAction::Restore,
+            _ => SensitivePasteAction::Off,
+        }
+    }
+}
+
+/// Which pasted items are taken off the clipboard again, and when
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensitivePastePolicy {
+    pub action: SensitivePasteAction,
+    /// Time the item stays on the clipboard after the paste
+    pub delay: Duration,
+    /// Applications whose copies are always treated as sensitive
+    pub apps: Vec<String>,
+}
+
+impl Default for SensitivePastePolicy {
+    fn default() -> Self {
+        Self {
+            action: SensitivePasteAction::Off,
+            delay: Duration::seconds(30),
+            apps: Vec::new(),
+        }
+    }
+}
+
+impl SensitivePastePolicy {
+    /// Whether pasting `item` has to be reverted
+    pub fn applies_to(&self, item: &ClipboardItem) -> bool {
+        self.action != SensitivePasteAction::Off
+            && (item.is_sensitive() || self.apps.iter().any(|app| item.is_from_app(app)))
+    }
+}
+
+/// Content a paste put on the clipboard, to tell later whether it is still there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PastedContent {
+    Text(String),
+    /// Stored image bytes
+    Image(Vec<u8>),
+    Files(CopiedFiles),
+}
+
+impl PastedContent {
+    /// Whether the system clipboard still holds this content
+    pub fn is_on_clipboard(&self) -> bool {
+        match self {
+            PastedContent::Text(text) => Clipboard::new()
+                .and_then(|mut clipboard| clipboard.get_text())
+                .is_ok_and(|current| current == *text),
+            // Compare the stored bytes, the pixels may have been re-encoded on capture
+            PastedContent::Image(bytes) => {
+                clipboard_targets::read_clipboard_target(image_policy::mime_type(bytes))
+                    .is_some_and(|current| current == *bytes)
+            }
+            PastedContent::Files(files) => clipboard_targets::get_clipboard_targets()
+                .and_then(|targets| {
+                    CopiedFiles::from_offer(&targets, clipboard_targets::read_clipboard_target)
+                })
+                .is_some_and(|current| current == *files),
+        }
+    }
+}
+
+/// Pasted content waiting to be taken off the clipboard
+#[derive(Debug, Clone)]
+pub struct PendingRevert {
+    /// What was pasted, to check it is still on the clipboard (`None` skips the check)
+    pub pasted: Option<PastedContent>,
+    pub due: DateTime<Utc>,
+    /// Clipboard content from before the paste; empty means clear instead
+    pub previous: Vec<SelectionTarget>,
+}
+
+impl PendingRevert {
+    /// Whether the clipboard still holds the pasted content
+    pub fn is_still_pasted(&self) -> bool {
+        self.pasted
+            .as_ref()
+            .map_or(true, PastedContent::is_on_clipboard)
+    }
+}
+
+/// Whether the clipboard has to be saved before a paste overwrites it, as decided by
+/// `ClipboardManager::begin_paste`
+#[derive(Debug)]
+pub enum ClipboardSave {
+    /// The paste is not given back
+    NotNeeded,
+    /// The clipboard is put back after the paste. While it still holds an earlier
+    /// paste waiting to be reverted, that paste's own previous content is used.
+    Needed { earlier: Option<PendingRevert> },
+}
+
+impl ClipboardSave {
+    /// Saves the clipboard if needed. `None` when not needed, or when the clipboard
+    /// could not be read completely and so cannot be put back as it was.
+    pub fn run(self) -> Option<Vec<SelectionTarget>> {
+        match self {
+            ClipboardSave::NotNeeded => None,
+            ClipboardSave::Needed {
+                earlier: Some(pending),
+            } if pending.is_still_pasted() => Some(pending.previous),
+            ClipboardSave::Needed { .. } => snapshot_clipboard(),
+        }
+    }
+}
+
+/// Reads everything on the clipboard that can be offered again later. `None` if an
+/// offered format could not be read (e.g. one beyond `clipboard_targets::MAX_FORMATS_BYTES`)..
+pub fn snapshot_clipboard() -> Option<Vec<SelectionTarget>> {
+    let mut snapshot = Vec::new();
+    if let Some(text) = Clipboard::new().ok().and_then(|mut c| c.get_text().ok()) {
+        snapshot.push((SNAPSHOT_TEXT_MIME.to_string(), text.into_bytes()));
+    }
+    let targets = clipboard_targets::get_clipboard_targets().unwrap_or_default();
+    let formats = clipboard_targets::read_clipboard_formats(&targets, |_| false);
+    if let Some(missing) = missing_format(&targets, &formats) {
+        eprintln!(
+            "[SensitivePaste] Could not save clipboard format {}, the clipboard will not be restored",
+            missing
+        );
+        return None;
+    }
+    snapshot.extend(formats);
+    Some(snapshot)
+}
+
+/// A format among `targets` that reading them into `formats` left out
+fn missing_format<'a>(targets: &'a [String], formats: &[SelectionTarget]) -> Option<&'a String> {
+    targets.iter().find(|target| {
+        clipboard_targets::is_format_target(target)
+            && !formats.iter().any(|(mime, _)| mime == *target)
+    })
+}
+
+/// Takes a due paste off the clipboard, unless something else was copied in the meantime
+pub fn revert(manager: &Mutex<ClipboardManager>, pending: PendingRevert) {
+    if !pending.is_still_pasted() {
+        return;
+    }
+    manager.lock().note_revert(&pending.previous, Utc::now());
+
+    let result = if pending.previous.is_empty() {
+        eprintln!("[SensitivePaste] Clearing pasted content from the clipboard");
+        clear_clipboard()
+    } else {
+        eprintln!("[SensitivePaste] Restoring the clipboard after a paste");
+        selection_owner::offer_selection(SelectionKind::Clipboard, pending.previous)
+    };
+    if let Err(e) = result {
+        eprintln!("[SensitivePaste] {}", e);
+    }
+}
+
+/// Plain text of a snapshot, if it has any
+pub fn snapshot_text(snapshot: &[SelectionTarget]) -> Option<String> {
+    snapshot
+        .iter()
+        .find(|(mime, _)| mime == SNAPSHOT_TEXT_MIME)
+        .and_then(|(_, bytes)| String::from_utf8(bytes.clone()).ok())
+        .filter(|text| !text.is_empty())
+}
+
+/// Pixel hash of the image in a snapshot, as the clipboard monitor computes it
+pub fn snapshot_image_hash(snapshot: &[SelectionTarget]) -> Option<u64> {
+    let (_, bytes) = snapshot
+        .iter()
+        .find(|(mime, _)| mime.starts_with("image/"))?;
+    let pixels = image::load_from_memory(bytes).ok()?.to_rgba8().into_raw();
+    Some(calculate_hash(&pixels))
+}
+
+/// Empties the clipboard
+pub fn clear_clipboard() -> Result<(), String> {
+    Clipboard::new()
+        .map_err(|e| e.to_string())?
+        .clear()
+        .map_err(|e| format!("Failed to clear clipboard: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_matches_sensitive_items() {
+        let mut policy = SensitivePastePolicy {
+            action: SensitivePasteAction::Clear,
+            apps: vec!["keepassxc".to_string()],
+            ..Default::default()
+        };
+        let mut item = ClipboardItem::new_text("hunter2".to_string());
+        assert!(!policy.applies_to(&item));
+
+        item.source_app = Some("KeePassXC".to_string());
+        assert!(policy.applies_to(&item));
+        item.source_app = Some("org.keepassxc.KeePassXC".to_string());
+        assert!(policy.applies_to(&item));
+        item.source_app = Some("keepassxc-proxy".to_string());
+        assert!(!policy.applies_to(&item));
+
+        item.source_app = None;
+        item.sensitive = true;
+        assert!(policy.applies_to(&item));
+
+        policy.action = SensitivePasteAction::Off;
+        assert!(!policy.applies_to(&item));
+    }
+
+    #[test]
+    fn test_snapshot_missing_format() {
+        let targets: Vec<String> = ["TARGETS", "UTF8_STRING", "text/html", "image/png"]
+            .iter()
+            .map(|target| target.to_string())
+            .collect();
+        let mut formats = vec![("text/html".to_string(), b"<b>hi</b>".to_vec())];
+        assert_eq!(
+            missing_format(&targets, &formats).map(String::as_str),
+            Some("image/png")
+        );
+
+        formats.push(("image/png".to_string(), vec![0x89, b'P', b'N', b'G']));
+        assert_eq!(missing_format(&targets, &formats), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Test Support Module
+//! Fixtures shared by the unit tests of several modules.
+
+use crate::blob_store::BlobStore;
+use crate::clipboard_manager::{ClipboardManager, DEFAULT_MAX_HISTORY_SIZE};
+use crate::history_store::MemoryHistoryStore;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+/// Empty directory in the system temp dir, removed with its content when dropped
+pub struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Creates a new empty directory in the system temp dir, named after `purpose`
+pub fn temp_dir(purpose: &str) -> TempDir {
+    let dir = std::env::temp_dir().join(format!(
+        "win11-clipboard-history-{}-{}",
+        purpose,
+        uuid::Uuid::new_v4()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
+
+/// Manager that keeps its blob directory until it is dropped
+pub struct TestManager {
+    manager: ClipboardManager,
+    _blobs: TempDir,
+}
+
+impl Deref for TestManager {
+    type Target = ClipboardManager;
+
+    fn deref(&self) -> &ClipboardManager {
+        &self.manager
+    }
+}
+
+impl DerefMut for TestManager {
+    fn deref_mut(&mut self) -> &mut ClipboardManager {
+        &mut self.manager
+    }
+}
+
+/// Manager with an in-memory store, writing blobs to a temp dir
+pub fn test_manager() -> TestManager {
+    let blobs = temp_dir("blobs");
+    TestManager {
+        manager: ClipboardManager::with_store(
+            Box::new(MemoryHistoryStore::new()),
+            BlobStore::new(blobs.to_path_buf()),
+            DEFAULT_MAX_HISTORY_SIZE,
+        ),
+        _blobs: blobs,
+    }
+}
@@ -1,11 +1,18 @@
 //! User Settings Module
 //! Handles persistence of user preferences (theme mode, background opacity) in a separate JSON file.
 
+use crate::clipboard_manager::HistoryBudget;
+use crate::history_crypto::EncryptionConfig;
+use crate::history_retention::RetentionPolicy;
+use crate::image_policy::{ImagePolicy, OversizedImageAction, StoredImageFormat};
+use crate::sensitive_paste::{SensitivePasteAction, SensitivePastePolicy};
+use chrono::NaiveTime;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
 const USER_SETTINGS_FILE: &str = "user_settings.json";
+const PURGE_TIME_FORMAT: &str = "%H:%M";
 
 /// User-configurable settings for the application
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +40,108 @@ pub struct UserSettings {
     #[serde(default = "default_max_history_size")]
     pub max_history_size: usize,
 
+    /// Storage limit of the whole history in bytes (0 = no limit)
+    #[serde(default)]
+    pub max_history_bytes: u64,
+
+    /// Storage limit of text, rich text and file items in bytes (0 = no limit)
+    #[serde(default)]
+    pub max_text_history_bytes: u64,
+
+    /// Storage limit of images in bytes (0 = no limit)
+    #[serde(default)]
+    pub max_image_history_bytes: u64,
+
+    /// Days after which unpinned items are deleted (0 = keep them)
+    #[serde(default)]
+    pub retention_days: u32,
+
+    /// Days after which unpinned images are deleted (0 = same as other items)
+    #[serde(default)]
+    pub image_retention_days: u32,
+
+    /// Local time ("HH:MM") at which all unpinned items are deleted every day
+    #[serde(default)]
+    pub daily_purge_time: Option<String>,
+
+    /// Seconds to keep text flagged as secret by a password manager.
+    /// 0 (default) never stores it; otherwise it is kept in memory only and then dropped.
+    #[serde(default)]
+    pub concealed_expiry_secs: u64,
+
+    /// What is deleted when the session locks or the machine suspends or shuts down:
+    /// "off", "sensitive" (secrets from password managers), "unpinned" or "all"
+    #[serde(default = "default_clear_on_lock")]
+    pub clear_on_lock: String,
+
+    /// What happens to the clipboard after a sensitive item was pasted:
+    /// "off", "clear" or "restore" (put back the content from before the paste)
+    #[serde(default = "default_sensitive_paste_action")]
+    pub sensitive_paste_action: String,
+
+    /// Seconds a pasted sensitive item stays on the clipboard
+    #[serde(default = "default_sensitive_paste_delay_secs")]
+    pub sensitive_paste_delay_secs: u64,
+
+    /// Put the previous clipboard content back after pasting from history,
+    /// so a paste does not replace what the user had copied
+    #[serde(default)]
+    pub transient_paste: bool,
+
+    /// Keep offering the last item when the application that copied it exits (Linux),
+    /// so closing it does not empty the clipboard
+    #[serde(default)]
+    pub persist_clipboard: bool,
+
+    /// Item put back on the clipboard at startup when it is empty (e.g. after a reboot):
+    /// "off", "latest" or "pinned" (the newest pinned item)
+    #[serde(default = "default_restore_on_startup")]
+    pub restore_on_startup: String,
+
+    /// Applications whose copies are treated as sensitive when pasted, in addition to
+    /// password-manager secrets and items marked by hand
+    #[serde(default)]
+    pub sensitive_apps: Vec<String>,
+
+    /// Applications whose copies are never recorded, matched case-insensitively
+    /// against the window class (WM_CLASS / app_id), instance or process name
+    #[serde(default = "default_excluded_apps")]
+    pub excluded_apps: Vec<String>,
+
+    /// Also record the PRIMARY selection (selected text), tagged separately in history
+    #[serde(default)]
+    pub capture_primary_selection: bool,
+
+    /// Mirroring between selections: "off", "clipboard_to_primary" or "primary_to_clipboard"
+    #[serde(default = "default_selection_sync")]
+    pub selection_sync: String,
+
+    /// Encrypt stored history and images at rest
+    #[serde(default)]
+    pub encrypt_history: bool,
+
+    /// Optional file whose contents are used as the encryption passphrase.
+    /// When unset, the key is kept in the desktop keyring (Secret Service).
+    #[serde(default)]
+    pub encryption_passphrase_file: Option<String>,
+
+    // --- Image Settings ---
+    /// Largest image stored as is, in megapixels (0 = no limit)
+    #[serde(default)]
+    pub max_image_megapixels: u32,
+
+    /// Largest encoded image stored as is, in megabytes (0 = no limit)
+    #[serde(default)]
+    pub max_image_megabytes: u32,
+
+    /// What to do with images over the limits: "downscale" or "skip"
+    #[serde(default = "default_oversized_image_action")]
+    pub oversized_image_action: String,
+
+    /// Encoding of stored images: "png" (lossless) or "jpeg" (lossy, smaller)
+    #[serde(default = "default_image_format")]
+    pub image_format: String,
+
     // --- Custom Data ---
     /// User-defined Kaomojis
     #[serde(default)]
@@ -60,6 +169,41 @@ fn default_max_history_size() -> usize {
     crate::clipboard_manager::DEFAULT_MAX_HISTORY_SIZE
 }
 
+fn default_excluded_apps() -> Vec<String> {
+    ["keepassxc", "bitwarden", "1password"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+fn default_clear_on_lock() -> String {
+    "off".to_string()
+}
+
+fn default_restore_on_startup() -> String {
+    "off".to_string()
+}
+
+fn default_sensitive_paste_action() -> String {
+    "off".to_string()
+}
+
+fn default_sensitive_paste_delay_secs() -> u64 {
+    30
+}
+
+fn default_selection_sync() -> String {
+    "off".to_string()
+}
+
+fn default_oversized_image_action() -> String {
+    "downscale".to_string()
+}
+
+fn default_image_format() -> String {
+    "png".to_string()
+}
+
 fn default_ui_scale() -> f32 {
     1.0
 }
@@ -73,6 +217,29 @@ impl Default for UserSettings {
             enable_smart_actions: true,
             enable_ui_polish: true,
             max_history_size: default_max_history_size(),
+            max_history_bytes: 0,
+            max_text_history_bytes: 0,
+            max_image_history_bytes: 0,
+            retention_days: 0,
+            image_retention_days: 0,
+            daily_purge_time: None,
+            concealed_expiry_secs: 0,
+            clear_on_lock: default_clear_on_lock(),
+            sensitive_paste_action: default_sensitive_paste_action(),
+            sensitive_paste_delay_secs: default_sensitive_paste_delay_secs(),
+            sensitive_apps: Vec::new(),
+            transient_paste: false,
+            persist_clipboard: false,
+            restore_on_startup: default_restore_on_startup(),
+            excluded_apps: default_excluded_apps(),
+            capture_primary_selection: false,
+            selection_sync: default_selection_sync(),
+            encrypt_history: false,
+            encryption_passphrase_file: None,
+            max_image_megapixels: 0,
+            max_image_megabytes: 0,
+            oversized_image_action: default_oversized_image_action(),
+            image_format: default_image_format(),
             custom_kaomojis: Vec::new(),
             ui_scale: default_ui_scale(),
         }
@@ -95,7 +262,120 @@ impl UserSettings {
 
         // Validate ui_scale (0.5 to 2.0)
         self.ui_scale = self.ui_scale.clamp(0.5, 2.0);
+
+        // Validate retention (at most ten years) and the purge time
+        self.retention_days = self.retention_days.min(3650);
+        self.image_retention_days = self.image_retention_days.min(3650);
+        self.daily_purge_time = self
+            .daily_purge_time
+            .take()
+            .filter(|time| NaiveTime::parse_from_str(time, PURGE_TIME_FORMAT).is_ok());
+
+        // Validate concealed_expiry_secs (at most one day)
+        self.concealed_expiry_secs = self.concealed_expiry_secs.min(86_400);
+
+        // Validate clear_on_lock
+        if !["off", "sensitive", "unpinned", "all"].contains(&self.clear_on_lock.as_str()) {
+            self.clear_on_lock = default_clear_on_lock();
+        }
+
+        // Validate restore_on_startup
+        if !["off", "latest", "pinned"].contains(&self.restore_on_startup.as_str()) {
+            self.restore_on_startup = default_restore_on_startup();
+        }
+
+        // Validate the sensitive paste action and delay (1 second to 1 hour)
+        if !["off", "clear", "restore"].contains(&self.sensitive_paste_action.as_str()) {
+            self.sensitive_paste_action = default_sensitive_paste_action();
+        }
+        self.sensitive_paste_delay_secs = self.sensitive_paste_delay_secs.clamp(1, 3600);
+
+        // Normalize excluded_apps and sensitive_apps (trimmed, no blanks or duplicates)
+        self.excluded_apps = normalize_apps(std::mem::take(&mut self.excluded_apps));
+        self.sensitive_apps = normalize_apps(std::mem::take(&mut self.sensitive_apps));
+
+        // Validate selection_sync
+        if !["off", "clipboard_to_primary", "primary_to_clipboard"]
+            .contains(&self.selection_sync.as_str())
+        {
+            self.selection_sync = default_selection_sync();
+        }
+
+        // Validate image limits (at most 1000 MP / 1000 MB) and choices
+        self.max_image_megapixels = self.max_image_megapixels.min(1000);
+        self.max_image_megabytes = self.max_image_megabytes.min(1000);
+        if !["downscale", "skip"].contains(&self.oversized_image_action.as_str()) {
+            self.oversized_image_action = default_oversized_image_action();
+        }
+        if !["png", "jpeg"].contains(&self.image_format.as_str()) {
+            self.image_format = default_image_format();
+        }
+
+        // Treat a blank passphrase file path as unset
+        self.encryption_passphrase_file = self
+            .encryption_passphrase_file
+            .take()
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty());
     }
+
+    /// Sensitive paste settings in the form used by the clipboard manager
+    pub fn sensitive_paste_policy(&self) -> SensitivePastePolicy {
+        SensitivePastePolicy {
+            action: SensitivePasteAction::from_setting(&self.sensitive_paste_action),
+            delay: chrono::Duration::seconds(self.sensitive_paste_delay_secs as i64),
+            apps: self.sensitive_apps.clone(),
+        }
+    }
+
+    /// Storage limits in the form used by the clipboard manager
+    pub fn history_budget(&self) -> HistoryBudget {
+        HistoryBudget {
+            max_bytes: self.max_history_bytes,
+            max_text_bytes: self.max_text_history_bytes,
+            max_image_bytes: self.max_image_history_bytes,
+        }
+    }
+
+    /// Retention settings in the form used by the clipboard manager
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        let days = |days: u32| (days > 0).then(|| chrono::Duration::days(i64::from(days)));
+        RetentionPolicy {
+            max_age: days(self.retention_days),
+            image_max_age: days(self.image_retention_days),
+            daily_purge: self
+                .daily_purge_time
+                .as_deref()
+                .and_then(|time| NaiveTime::parse_from_str(time, PURGE_TIME_FORMAT).ok()),
+        }
+    }
+
+    /// Image settings in the form used by the clipboard manager
+    pub fn image_policy(&self) -> ImagePolicy {
+        ImagePolicy {
+            max_pixels: u64::from(self.max_image_megapixels) * 1_000_000,
+            max_bytes: u64::from(self.max_image_megabytes) * 1024 * 1024,
+            oversized: OversizedImageAction::from_setting(&self.oversized_image_action),
+            format: StoredImageFormat::from_setting(&self.image_format),
+        }
+    }
+
+    /// Encryption settings in the form used by the history store
+    pub fn encryption_config(&self) -> EncryptionConfig {
+        EncryptionConfig {
+            enabled: self.encrypt_history,
+            passphrase_file: self.encryption_passphrase_file.as_ref().map(PathBuf::from),
+        }
+    }
+}
+
+/// Trims application names and drops blanks and case-insensitive duplicates
+fn normalize_apps(apps: Vec<String>) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    apps.into_iter()
+        .map(|app| app.trim().to_string())
+        .filter(|app| !app.is_empty() && seen.insert(app.to_lowercase()))
+        .collect()
 }
 
 /// Manages loading and saving of user settings
@@ -0,0 +1,666 @@
+//! Wayland Data Control Module
+//! Clipboard watcher and selection source for Wayland built on the data-control protocols.
+//!
+//! Compositors advertise either the standard `ext-data-control-v1` or the older
+//! `wlr-data-control-unstable-v1` (same semantics). Both send a `selection` event,
+//! together with every offered MIME type, as soon as the clipboard changes, so the
+//! content is read straight from the source instead of being polled. The same
+//! protocols let us own the selection and offer content in several formats.
+
+use crate::clipboard_manager::{ClipboardContent, SelectionKind};
+use crate::clipboard_targets;
+use crate::clipboard_watcher::{poll_readable, ClipboardSnapshot, ClipboardWatcher, WatchEvent};
+use crate::copied_files::CopiedFiles;
+use crate::selection_owner::SelectionTarget;
+use arboard::ImageData;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wayland_client::backend::WaylandError;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat::WlSeat};
+use wayland_client::{
+    delegate_noop, event_created_child, Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::ext::data_control::v1::client::{
+    ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+    ext_data_control_manager_v1::ExtDataControlManagerV1,
+    ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+    ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+// --- Constants ---
+
+/// How long a source may take to deliver one representation
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Upper bound for a single representation, guarding against runaway sources
+const MAX_PAYLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// Text targets in order of preference
+const TEXT_MIME_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+const HTML_MIME_TYPE: &str = "text/html";
+const PNG_MIME_TYPE: &str = "image/png";
+
+// --- Protocol State ---
+
+/// MIME types announced for an offer (filled by its `offer` events)
+type OfferMimeTypes = Mutex<Vec<String>>;
+
+/// A selection offer from either protocol
+enum DataOffer {
+    Ext(ExtDataControlOfferV1),
+    Wlr(ZwlrDataControlOfferV1),
+}
+
+impl DataOffer {
+    fn mime_types(&self) -> Vec<String> {
+        let data = match self {
+            DataOffer::Ext(offer) => offer.data::<OfferMimeTypes>(),
+            DataOffer::Wlr(offer) => offer.data::<OfferMimeTypes>(),
+        };
+        data.and_then(|types| types.lock().ok().map(|types| types.clone()))
+            .unwrap_or_default()
+    }
+
+    fn receive(&self, mime_type: &str, fd: BorrowedFd<'_>) {
+        match self {
+            DataOffer::Ext(offer) => offer.receive(mime_type.to_string(), fd),
+            DataOffer::Wlr(offer) => offer.receive(mime_type.to_string(), fd),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            DataOffer::Ext(offer) => offer.destroy(),
+            DataOffer::Wlr(offer) => offer.destroy(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Latest clipboard offer not yet handed to the caller
+    pending: Option<DataOffer>,
+    /// Latest PRIMARY selection offer not yet handed to the caller
+    pending_primary: Option<DataOffer>,
+    /// Set when the clipboard was cleared to no selection (its owner went away)
+    clipboard_cleared: bool,
+    /// Whether PRIMARY offers are kept at all
+    watch_primary: bool,
+    /// Set when the compositor invalidated the data device
+    finished: bool,
+}
+
+impl State {
+    fn set_selection(&mut self, kind: SelectionKind, offer: Option<DataOffer>) {
+        let slot = match kind {
+            SelectionKind::Clipboard => {
+                self.clipboard_cleared = offer.is_none();
+                &mut self.pending
+            }
+            SelectionKind::Primary => &mut self.pending_primary,
+        };
+        // An unread older offer is superseded; only the current content matters
+        if let Some(previous) = slot.take() {
+            previous.destroy();
+        }
+        *slot = offer;
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(State: ignore WlSeat);
+delegate_noop!(State: ExtDataControlManagerV1);
+delegate_noop!(State: ZwlrDataControlManagerV1);
+
+/// Both protocol families have identical events, so their handlers are generated
+macro_rules! impl_data_control_dispatch {
+    ($device:ty, $device_mod:ident, $offer:ty, $offer_mod:ident, $variant:ident) => {
+        impl Dispatch<$device, ()> for State {
+            fn event(
+                state: &mut Self,
+                _: &$device,
+                event: $device_mod::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $device_mod::Event::Selection { id } => {
+                        state.set_selection(SelectionKind::Clipboard, id.map(DataOffer::$variant))
+                    }
+                    $device_mod::Event::PrimarySelection { id } if state.watch_primary => {
+                        state.set_selection(SelectionKind::Primary, id.map(DataOffer::$variant))
+                    }
+                    $device_mod::Event::PrimarySelection { id: Some(offer) } => offer.destroy(),
+                    $device_mod::Event::Finished => state.finished = true,
+                    _ => {}
+                }
+            }
+
+            event_created_child!(State, $device, [
+                $device_mod::EVT_DATA_OFFER_OPCODE => ($offer, OfferMimeTypes::default()),
+            ]);
+        }
+
+        impl Dispatch<$offer, OfferMimeTypes> for State {
+            fn event(
+                _: &mut Self,
+                _: &$offer,
+                event: $offer_mod::Event,
+                mime_types: &OfferMimeTypes,
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                if let $offer_mod::Event::Offer { mime_type } = event {
+                    if let Ok(mut mime_types) = mime_types.lock() {
+                        mime_types.push(mime_type);
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl_data_control_dispatch!(
+    ExtDataControlDeviceV1,
+    ext_data_control_device_v1,
+    ExtDataControlOfferV1,
+    ext_data_control_offer_v1,
+    Ext
+);
+impl_data_control_dispatch!(
+    ZwlrDataControlDeviceV1,
+    zwlr_data_control_device_v1,
+    ZwlrDataControlOfferV1,
+    zwlr_data_control_offer_v1,
+    Wlr
+);
+
+// --- Watcher ---
+
+/// Event-driven watcher for compositors implementing a data-control protocol
+pub struct DataControlWatcher {
+    conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+}
+
+impl DataControlWatcher {
+    /// Connects to the compositor and binds the first data-control protocol available.
+    pub fn new() -> Result<Self, String> {
+        let (watcher, protocol) = Self::connect()?;
+        eprintln!("[WaylandDataControl] Bound {}", protocol);
+        Ok(watcher)
+    }
+
+    /// Binds a data device, which is sent the current selections right away
+    fn connect() -> Result<(Self, &'static str), String> {
+        let conn =
+            Connection::connect_to_env().map_err(|e| format!("Wayland connect failed: {}", e))?;
+        let (globals, mut queue) = registry_queue_init::<State>(&conn)
+            .map_err(|e| format!("Failed to list Wayland globals: {}", e))?;
+        let qh = queue.handle();
+
+        let seat: WlSeat = globals
+            .bind(&qh, 1..=1, ())
+            .map_err(|e| format!("No wl_seat available: {}", e))?;
+
+        let protocol = if let Ok(manager) =
+            globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ())
+        {
+            manager.get_data_device(&seat, &qh, ());
+            "ext-data-control-v1"
+        } else if let Ok(manager) = globals.bind::<ZwlrDataControlManagerV1, _, _>(&qh, 1..=2, ()) {
+            manager.get_data_device(&seat, &qh, ());
+            "wlr-data-control-unstable-v1"
+        } else {
+            return Err("Compositor advertises no data-control protocol".to_string());
+        };
+
+        let mut state = State::default();
+        queue
+            .roundtrip(&mut state)
+            .map_err(|e| format!("Wayland roundtrip failed: {}", e))?;
+
+        Ok((Self { conn, queue, state }, protocol))
+    }
+
+    /// Reads the representations we record (text, HTML, image) from an offer.
+    fn read_offer(&self, offer: &DataOffer) -> ClipboardSnapshot {
+        let mime_types = offer.mime_types();
+        let offered = |mime: &str| mime_types.iter().any(|m| m == mime);
+
+        let text = self.read_text(offer, &mime_types);
+
+        let html = offered(HTML_MIME_TYPE)
+            .then(|| self.receive(offer, HTML_MIME_TYPE))
+            .flatten()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+
+        // Prefer PNG, otherwise take any image format the decoder may understand
+        let image_mime = if offered(PNG_MIME_TYPE) {
+            Some(PNG_MIME_TYPE)
+        } else {
+            mime_types
+                .iter()
+                .map(String::as_str)
+                .find(|mime| mime.starts_with("image/"))
+        };
+        let image = image_mime
+            .and_then(|mime| self.receive(offer, mime))
+            .and_then(|bytes| decode_image(&bytes));
+
+        let files = CopiedFiles::from_offer(&mime_types, |mime| self.receive(offer, mime));
+        let concealed =
+            clipboard_targets::is_concealed(&mime_types, |mime| self.receive(offer, mime));
+
+        // Other formats are only kept with text or an image, and never with a secret
+        let main_type = if files.is_some() || concealed {
+            None
+        } else if text.as_deref().is_some_and(|text| !text.is_empty()) {
+            Some(if html.is_some() { "rich_text" } else { "text" })
+        } else if image.is_some() {
+            Some("image")
+        } else {
+            None
+        };
+        let formats = match main_type {
+            Some(main_type) => clipboard_targets::read_formats(
+                &mime_types,
+                |mime| ClipboardContent::type_represents(main_type, mime),
+                |mime| self.receive(offer, mime),
+            ),
+            None => Vec::new(),
+        };
+
+        ClipboardSnapshot {
+            mime_types,
+            text,
+            html,
+            image,
+            files,
+            formats,
+            concealed,
+        }
+    }
+
+    fn read_text(&self, offer: &DataOffer, mime_types: &[String]) -> Option<String> {
+        TEXT_MIME_TYPES
+            .iter()
+            .find(|mime| mime_types.iter().any(|m| m == *mime))
+            .and_then(|mime| self.receive(offer, mime))
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Reads only the text of a PRIMARY offer; selections change too often for more
+    fn read_primary_offer(&self, offer: &DataOffer) -> ClipboardSnapshot {
+        let mime_types = offer.mime_types();
+        ClipboardSnapshot {
+            text: self.read_text(offer, &mime_types),
+            mime_types,
+            ..Default::default()
+        }
+    }
+
+    /// Asks the source to write one representation into a pipe and reads it back.
+    fn receive(&self, offer: &DataOffer, mime_type: &str) -> Option<Vec<u8>> {
+        let (read_end, write_end) = create_pipe()
+            .map_err(|e| eprintln!("[WaylandDataControl] Failed to create pipe: {}", e))
+            .ok()?;
+        offer.receive(mime_type, write_end.as_fd());
+        if let Err(e) = self.conn.flush() {
+            eprintln!("[WaylandDataControl] Flush failed: {}", e);
+            return None;
+        }
+        // Our copy of the write end must be closed so that EOF is seen once the source is done
+        drop(write_end);
+
+        match read_pipe(read_end) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                eprintln!(
+                    "[WaylandDataControl] Failed to read '{}' from source: {}",
+                    mime_type, e
+                );
+                None
+            }
+        }
+    }
+}
+
+impl ClipboardWatcher for DataControlWatcher {
+    fn name(&self) -> &'static str {
+        "wayland-data-control"
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<WatchEvent, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.queue
+                .dispatch_pending(&mut self.state)
+                .map_err(|e| format!("Wayland dispatch failed: {}", e))?;
+            if self.state.finished {
+                return Err("data device was invalidated by the compositor".to_string());
+            }
+
+            if let Some(offer) = self.state.pending.take() {
+                let snapshot = self.read_offer(&offer);
+                offer.destroy();
+                return Ok(WatchEvent::Offer(SelectionKind::Clipboard, snapshot));
+            }
+            if std::mem::take(&mut self.state.clipboard_cleared) {
+                return Ok(WatchEvent::OwnerGone(SelectionKind::Clipboard));
+            }
+            if let Some(offer) = self.state.pending_primary.take() {
+                let snapshot = self.read_primary_offer(&offer);
+                offer.destroy();
+                return Ok(WatchEvent::Offer(SelectionKind::Primary, snapshot));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(WatchEvent::Idle);
+            }
+
+            self.conn
+                .flush()
+                .map_err(|e| format!("Wayland flush failed: {}", e))?;
+            // None means events are already queued; dispatch them first
+            let Some(guard) = self.queue.prepare_read() else {
+                continue;
+            };
+            if poll_readable(guard.connection_fd().as_raw_fd(), deadline - now)? {
+                match guard.read() {
+                    Ok(_) => {}
+                    Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(format!("Wayland read failed: {}", e)),
+                }
+            }
+        }
+    }
+
+    fn set_watch_primary(&mut self, enabled: bool) {
+        self.state.watch_primary = enabled;
+        if !enabled {
+            self.state.set_selection(SelectionKind::Primary, None);
+        }
+    }
+}
+
+// --- Reader ---
+
+/// The current clipboard offer, on a connection of its own. Used to read the
+/// clipboard outside the watcher (snapshots before a paste, checks after it).
+pub struct ClipboardReader {
+    watcher: DataControlWatcher,
+    offer: Option<DataOffer>,
+}
+
+impl ClipboardReader {
+    pub fn new() -> Result<Self, String> {
+        let (mut watcher, _) = DataControlWatcher::connect()?;
+        let offer = watcher.state.pending.take();
+        Ok(Self { watcher, offer })
+    }
+
+    /// MIME types offered by the clipboard, empty when it holds nothing
+    pub fn mime_types(&self) -> Vec<String> {
+        self.offer
+            .as_ref()
+            .map(DataOffer::mime_types)
+            .unwrap_or_default()
+    }
+
+    /// Reads one MIME type from the clipboard owner
+    pub fn read(&self, mime_type: &str) -> Option<Vec<u8>> {
+        self.watcher.receive(self.offer.as_ref()?, mime_type)
+    }
+}
+
+impl Drop for ClipboardReader {
+    fn drop(&mut self) {
+        if let Some(offer) = self.offer.take() {
+            offer.destroy();
+        }
+    }
+}
+
+// --- Source ---
+
+/// State of a connection that owns the selection
+struct SourceState {
+    targets: Vec<SelectionTarget>,
+    /// Set once another client took the selection (or the device went away)
+    cancelled: bool,
+}
+
+impl SourceState {
+    fn send(&self, mime_type: &str, fd: OwnedFd) {
+        let Some((_, bytes)) = self.targets.iter().find(|(mime, _)| mime == mime_type) else {
+            return;
+        };
+        if let Err(e) = File::from(fd).write_all(bytes) {
+            eprintln!("[WaylandDataControl] Failed to send '{}': {}", mime_type, e);
+        }
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for SourceState {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(SourceState: ignore WlSeat);
+delegate_noop!(SourceState: ExtDataControlManagerV1);
+delegate_noop!(SourceState: ZwlrDataControlManagerV1);
+delegate_noop!(SourceState: ignore ExtDataControlOfferV1);
+delegate_noop!(SourceState: ignore ZwlrDataControlOfferV1);
+
+macro_rules! impl_data_control_source_dispatch {
+    ($device:ty, $device_mod:ident, $offer:ty, $source:ty, $source_mod:ident) => {
+        impl Dispatch<$device, ()> for SourceState {
+            fn event(
+                state: &mut Self,
+                _: &$device,
+                event: $device_mod::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                // Offers, including our own, are not read on this connection
+                match event {
+                    $device_mod::Event::Selection { id: Some(offer) }
+                    | $device_mod::Event::PrimarySelection { id: Some(offer) } => offer.destroy(),
+                    $device_mod::Event::Finished => state.cancelled = true,
+                    _ => {}
+                }
+            }
+
+            event_created_child!(SourceState, $device, [
+                $device_mod::EVT_DATA_OFFER_OPCODE => ($offer, ()),
+            ]);
+        }
+
+        impl Dispatch<$source, ()> for SourceState {
+            fn event(
+                state: &mut Self,
+                _: &$source,
+                event: $source_mod::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $source_mod::Event::Send { mime_type, fd } => state.send(&mime_type, fd),
+                    $source_mod::Event::Cancelled => state.cancelled = true,
+                    _ => {}
+                }
+            }
+        }
+    };
+}
+
+impl_data_control_source_dispatch!(
+    ExtDataControlDeviceV1,
+    ext_data_control_device_v1,
+    ExtDataControlOfferV1,
+    ExtDataControlSourceV1,
+    ext_data_control_source_v1
+);
+impl_data_control_source_dispatch!(
+    ZwlrDataControlDeviceV1,
+    zwlr_data_control_device_v1,
+    ZwlrDataControlOfferV1,
+    ZwlrDataControlSourceV1,
+    zwlr_data_control_source_v1
+);
+
+/// Takes ownership of `selection` and serves `targets` from a background thread
+/// until another client replaces the selection.
+pub fn offer_selection(
+    selection: SelectionKind,
+    targets: Vec<SelectionTarget>,
+) -> Result<(), String> {
+    let conn =
+        Connection::connect_to_env().map_err(|e| format!("Wayland connect failed: {}", e))?;
+    let (globals, mut queue) = registry_queue_init::<SourceState>(&conn)
+        .map_err(|e| format!("Failed to list Wayland globals: {}", e))?;
+    let qh = queue.handle();
+
+    let seat: WlSeat = globals
+        .bind(&qh, 1..=1, ())
+        .map_err(|e| format!("No wl_seat available: {}", e))?;
+
+    if let Ok(manager) = globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ()) {
+        let source = manager.create_data_source(&qh, ());
+        for (mime_type, _) in &targets {
+            source.offer(mime_type.clone());
+        }
+        let device = manager.get_data_device(&seat, &qh, ());
+        match selection {
+            SelectionKind::Clipboard => device.set_selection(Some(&source)),
+            SelectionKind::Primary => device.set_primary_selection(Some(&source)),
+        }
+    } else if let Ok(manager) = globals.bind::<ZwlrDataControlManagerV1, _, _>(&qh, 1..=2, ()) {
+        // The primary selection was added in version 2
+        if selection == SelectionKind::Primary && manager.version() < 2 {
+            return Err("Compositor does not support the primary selection".to_string());
+        }
+        let source = manager.create_data_source(&qh, ());
+        for (mime_type, _) in &targets {
+            source.offer(mime_type.clone());
+        }
+        let device = manager.get_data_device(&seat, &qh, ());
+        match selection {
+            SelectionKind::Clipboard => device.set_selection(Some(&source)),
+            SelectionKind::Primary => device.set_primary_selection(Some(&source)),
+        }
+    } else {
+        return Err("Compositor advertises no data-control protocol".to_string());
+    }
+
+    let mut state = SourceState {
+        targets,
+        cancelled: false,
+    };
+    queue
+        .roundtrip(&mut state)
+        .map_err(|e| format!("Wayland roundtrip failed: {}", e))?;
+
+    std::thread::spawn(move || {
+        // The connection (and with it the source) lives as long as this thread
+        let _conn = conn;
+        while !state.cancelled {
+            if let Err(e) = queue.blocking_dispatch(&mut state) {
+                eprintln!("[WaylandDataControl] Selection source failed: {}", e);
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+// --- Helpers ---
+
+fn create_pipe() -> std::io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two descriptors written by pipe2
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: pipe2 succeeded, so both descriptors are open and owned by us
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Reads a non-blocking pipe until EOF, giving up after `RECEIVE_TIMEOUT`
+fn read_pipe(fd: OwnedFd) -> Result<Vec<u8>, String> {
+    let deadline = Instant::now() + RECEIVE_TIMEOUT;
+    let mut file = File::from(fd);
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => return Ok(bytes),
+            Ok(n) => {
+                bytes.extend_from_slice(&chunk[..n]);
+                if bytes.len() > MAX_PAYLOAD_BYTES {
+                    return Err("payload too large".to_string());
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err("timed out".to_string());
+                }
+                poll_readable(file.as_raw_fd(), deadline - now)?;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+/// Decodes an encoded image into the RGBA form used by arboard (and by the image hash)
+fn decode_image(bytes: &[u8]) -> Option<ImageData<'static>> {
+    let rgba = image::load_from_memory(bytes).ok()?.to_rgba8();
+    Some(ImageData {
+        width: rgba.width() as usize,
+        height: rgba.height() as usize,
+        bytes: Cow::Owned(rgba.into_raw()),
+    })
+}
@@ -95,8 +95,18 @@ function ClipboardApp() {
   const secondaryOpacity = calculateSecondaryOpacity(opacity)
   const tertiaryOpacity = calculateTertiaryOpacity(opacity)
 
-  const { history, isLoading, clearHistory, deleteItem, togglePin, pasteItem } =
-    useClipboardHistory()
+  const {
+    history,
+    total,
+    hasMore,
+    isLoading,
+    loadMore,
+    clearHistory,
+    deleteItem,
+    togglePin,
+    toggleSensitive,
+    pasteItem,
+  } = useClipboardHistory()
 
   // Refs for focus management
   const tabBarRef = useRef<TabBarRef>(null)
@@ -214,6 +224,9 @@ function ClipboardApp() {
         return (
           <ClipboardTab
             history={history}
+            total={total}
+            hasMore={hasMore}
+            loadMore={loadMore}
             isLoading={isLoading}
             isDark={isDark}
             tertiaryOpacity={tertiaryOpacity}
@@ -221,6 +234,7 @@ function ClipboardApp() {
             clearHistory={clearHistory}
             deleteItem={deleteItem}
             togglePin={togglePin}
+            toggleSensitive={toggleSensitive}
             onPaste={pasteItem}
             settings={settings}
             tabBarRef={tabBarRef}
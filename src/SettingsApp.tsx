@@ -5,12 +5,35 @@ import { listen } from '@tauri-apps/api/event'
 import { emit } from '@tauri-apps/api/event'
 import { clsx } from 'clsx'
 
-import type { UserSettings, CustomKaomoji, BooleanSettingKey } from './types/clipboard'
+import type {
+  UserSettings,
+  HistoryStats,
+  CustomKaomoji,
+  BooleanSettingKey,
+  SelectionSync,
+  ClearOnLock,
+  RestoreOnStartup,
+  SensitivePasteAction,
+  OversizedImageAction,
+  ImageFormat,
+} from './types/clipboard'
 import { FeaturesSection } from './components/FeaturesSection'
 import { useSystemThemePreference } from './utils/systemTheme'
 
 const MIN_HISTORY_SIZE = 1
 const MAX_HISTORY_SIZE = 100_000
+const MAX_CONCEALED_EXPIRY_SECS = 86_400
+const MAX_IMAGE_LIMIT = 1000
+const MAX_RETENTION_DAYS = 3650
+const MAX_STORAGE_MB = 100_000
+const MAX_SENSITIVE_PASTE_DELAY_SECS = 3600
+const BYTES_PER_MB = 1024 * 1024
+
+/** Formats a byte count as KB / MB for the storage summary */
+function formatBytes(bytes: number): string {
+  if (bytes < BYTES_PER_MB) return `${Math.ceil(bytes / 1024)} KB`
+  return `${(bytes / BYTES_PER_MB).toFixed(1)} MB`
+}
 
 const DEFAULT_SETTINGS: UserSettings = {
   theme_mode: 'system',
@@ -19,6 +42,29 @@ const DEFAULT_SETTINGS: UserSettings = {
   enable_smart_actions: true,
   enable_ui_polish: true,
   max_history_size: 50,
+  max_history_bytes: 0,
+  max_text_history_bytes: 0,
+  max_image_history_bytes: 0,
+  retention_days: 0,
+  image_retention_days: 0,
+  daily_purge_time: null,
+  concealed_expiry_secs: 0,
+  clear_on_lock: 'off',
+  sensitive_paste_action: 'off',
+  sensitive_paste_delay_secs: 30,
+  sensitive_apps: [],
+  transient_paste: false,
+  persist_clipboard: false,
+  restore_on_startup: 'off',
+  excluded_apps: ['keepassxc', 'bitwarden', '1password'],
+  capture_primary_selection: false,
+  selection_sync: 'off',
+  encrypt_history: false,
+  encryption_passphrase_file: null,
+  max_image_megapixels: 0,
+  max_image_megabytes: 0,
+  oversized_image_action: 'downscale',
+  image_format: 'png',
   custom_kaomojis: [],
   ui_scale: 1,
 }
@@ -121,6 +167,17 @@ function SettingsApp() {
   // Custom Kaomoji State
   const [newKaomoji, setNewKaomoji] = useState('')
 
+  // Current history size, refreshed after each save (limits may evict items)
+  const [stats, setStats] = useState<HistoryStats | null>(null)
+  const refreshStats = useCallback(() => {
+    invoke<HistoryStats>('get_history_stats')
+      .then(setStats)
+      .catch((err) => console.error('Failed to load history stats:', err))
+  }, [])
+
+  // Set when the saved history could not be unlocked at startup
+  const [encryptionError, setEncryptionError] = useState<string | null>(null)
+
   // Apply theme to settings window itself
   const isDark = useThemeMode(settings.theme_mode)
 
@@ -143,6 +200,10 @@ function SettingsApp() {
         console.error('Failed to load settings:', err)
         setIsLoading(false)
       })
+    refreshStats()
+    invoke<string | null>('get_encryption_error')
+      .then(setEncryptionError)
+      .catch((err) => console.error('Failed to load encryption status:', err))
 
     // Show the main clipboard window for live preview
     const mainWindow = new Window('main')
@@ -166,7 +227,7 @@ function SettingsApp() {
       unlistenClosePromise.then((unlisten) => unlisten())
       unlistenSettingsPromise.then((unlisten) => unlisten())
     }
-  }, [])
+  }, [refreshStats])
 
   // Save settings with debounce-like behavior
   const saveSettings = useCallback(async (newSettings: UserSettings) => {
@@ -177,13 +238,14 @@ function SettingsApp() {
       await invoke('set_user_settings', { newSettings })
       setSaveMessage('Saved')
       setTimeout(() => setSaveMessage(null), 2000)
+      refreshStats()
     } catch (err) {
       console.error('Failed to save settings:', err)
       setSaveMessage('Error saving')
     } finally {
       setIsSaving(false)
     }
-  }, [])
+  }, [refreshStats])
 
   // Centralized settings update helper
   const updateSettings = useCallback(
@@ -544,6 +606,13 @@ function SettingsApp() {
             <p className={clsx('text-xs', isDark ? 'text-gray-400' : 'text-gray-500')}>
               Configure clipboard history behavior
             </p>
+            {stats && (
+              <p className={clsx('text-xs mt-1', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                {stats.total.count} items using {formatBytes(stats.total.bytes)}
+                {stats.by_type.image &&
+                  ` (images: ${stats.by_type.image.count}, ${formatBytes(stats.by_type.image.bytes)})`}
+              </p>
+            )}
           </div>
 
           <div className="p-6 space-y-4">
@@ -581,6 +650,544 @@ function SettingsApp() {
                 )}
               />
             </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="max-history-bytes" className="text-sm font-medium">
+                  History Storage Limit (MB)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Oldest unpinned items are removed beyond this size. 0 means no limit.
+                </p>
+              </div>
+              <input
+                id="max-history-bytes"
+                type="number"
+                min={0}
+                max={MAX_STORAGE_MB}
+                value={Math.round(settings.max_history_bytes / BYTES_PER_MB)}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  if (Number.isNaN(parsed)) return
+                  const value = Math.max(0, Math.min(MAX_STORAGE_MB, parsed))
+                  updateSettings({ max_history_bytes: value * BYTES_PER_MB })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="max-text-history-bytes" className="text-sm font-medium">
+                  Text Storage Limit (MB)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Separate limit for text and file items. 0 means no limit.
+                </p>
+              </div>
+              <input
+                id="max-text-history-bytes"
+                type="number"
+                min={0}
+                max={MAX_STORAGE_MB}
+                value={Math.round(settings.max_text_history_bytes / BYTES_PER_MB)}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  if (Number.isNaN(parsed)) return
+                  const value = Math.max(0, Math.min(MAX_STORAGE_MB, parsed))
+                  updateSettings({ max_text_history_bytes: value * BYTES_PER_MB })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="max-image-history-bytes" className="text-sm font-medium">
+                  Image Storage Limit (MB)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Separate limit for images. 0 means no limit.
+                </p>
+              </div>
+              <input
+                id="max-image-history-bytes"
+                type="number"
+                min={0}
+                max={MAX_STORAGE_MB}
+                value={Math.round(settings.max_image_history_bytes / BYTES_PER_MB)}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  if (Number.isNaN(parsed)) return
+                  const value = Math.max(0, Math.min(MAX_STORAGE_MB, parsed))
+                  updateSettings({ max_image_history_bytes: value * BYTES_PER_MB })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="retention-days" className="text-sm font-medium">
+                  Delete Items After (days)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Unpinned items older than this are removed. 0 keeps them.
+                </p>
+              </div>
+              <input
+                id="retention-days"
+                type="number"
+                min={0}
+                max={MAX_RETENTION_DAYS}
+                value={settings.retention_days}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  const safe = Number.isNaN(parsed) ? settings.retention_days : parsed
+                  const value = Math.max(0, Math.min(MAX_RETENTION_DAYS, safe))
+                  updateSettings({ retention_days: value })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="image-retention-days" className="text-sm font-medium">
+                  Delete Images After (days)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Separate limit for images. 0 uses the one above.
+                </p>
+              </div>
+              <input
+                id="image-retention-days"
+                type="number"
+                min={0}
+                max={MAX_RETENTION_DAYS}
+                value={settings.image_retention_days}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  const safe = Number.isNaN(parsed) ? settings.image_retention_days : parsed
+                  const value = Math.max(0, Math.min(MAX_RETENTION_DAYS, safe))
+                  updateSettings({ image_retention_days: value })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="daily-purge-time" className="text-sm font-medium">
+                  Daily Purge
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Every day at this time all unpinned items are removed. Leave empty to disable.
+                </p>
+              </div>
+              <input
+                id="daily-purge-time"
+                type="time"
+                value={settings.daily_purge_time ?? ''}
+                onChange={(e) => updateSettings({ daily_purge_time: e.target.value || null })}
+                className={clsx(
+                  'px-2 py-1.5 text-sm font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="concealed-expiry" className="text-sm font-medium">
+                  Keep Passwords For (seconds)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Copies marked secret by password managers. 0 never stores them.
+                </p>
+              </div>
+              <input
+                id="concealed-expiry"
+                type="number"
+                min={0}
+                max={MAX_CONCEALED_EXPIRY_SECS}
+                value={settings.concealed_expiry_secs}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  const safe = Number.isNaN(parsed) ? settings.concealed_expiry_secs : parsed
+                  const value = Math.max(0, Math.min(MAX_CONCEALED_EXPIRY_SECS, safe))
+                  updateSettings({ concealed_expiry_secs: value })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="restore-on-startup" className="text-sm font-medium">
+                  Restore on Startup
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Put an item back on the clipboard when it is empty at startup
+                </p>
+              </div>
+              <select
+                id="restore-on-startup"
+                value={settings.restore_on_startup}
+                onChange={(e) =>
+                  updateSettings({ restore_on_startup: e.target.value as RestoreOnStartup })
+                }
+                className={clsx(
+                  'px-2 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              >
+                <option value="off">Off</option>
+                <option value="latest">Latest item</option>
+                <option value="pinned">Latest pinned item</option>
+              </select>
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="clear-on-lock" className="text-sm font-medium">
+                  Clear on Lock
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Delete items when the screen locks or the computer suspends or shuts down
+                </p>
+              </div>
+              <select
+                id="clear-on-lock"
+                value={settings.clear_on_lock}
+                onChange={(e) => updateSettings({ clear_on_lock: e.target.value as ClearOnLock })}
+                className={clsx(
+                  'px-2 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              >
+                <option value="off">Off</option>
+                <option value="sensitive">Passwords only</option>
+                <option value="unpinned">Unpinned items</option>
+                <option value="all">Everything</option>
+              </select>
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="sensitive-paste-action" className="text-sm font-medium">
+                  After Pasting Sensitive Items
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Take passwords and items marked sensitive off the clipboard again
+                </p>
+              </div>
+              <select
+                id="sensitive-paste-action"
+                value={settings.sensitive_paste_action}
+                onChange={(e) =>
+                  updateSettings({
+                    sensitive_paste_action: e.target.value as SensitivePasteAction,
+                  })
+                }
+                className={clsx(
+                  'px-2 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              >
+                <option value="off">Keep on clipboard</option>
+                <option value="clear">Clear clipboard</option>
+                <option value="restore">Restore previous content</option>
+              </select>
+            </div>
+
+            {settings.sensitive_paste_action !== 'off' && (
+              <div className="flex justify-between items-center">
+                <div>
+                  <label htmlFor="sensitive-paste-delay" className="text-sm font-medium">
+                    Sensitive Paste Delay (seconds)
+                  </label>
+                  <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                    How long a pasted sensitive item stays on the clipboard
+                  </p>
+                </div>
+                <input
+                  id="sensitive-paste-delay"
+                  type="number"
+                  min={1}
+                  max={MAX_SENSITIVE_PASTE_DELAY_SECS}
+                  value={settings.sensitive_paste_delay_secs}
+                  onChange={(e) => {
+                    const parsed = Number.parseInt(e.target.value, 10)
+                    if (Number.isNaN(parsed)) return
+                    const value = Math.max(1, Math.min(MAX_SENSITIVE_PASTE_DELAY_SECS, parsed))
+                    updateSettings({ sensitive_paste_delay_secs: value })
+                  }}
+                  className={clsx(
+                    'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                    'input-number-compact no-number-spinner',
+                    isDark
+                      ? 'bg-white/5 border-white/10 text-white'
+                      : 'bg-gray-50 border-gray-200 text-gray-900'
+                  )}
+                />
+              </div>
+            )}
+
+            {settings.sensitive_paste_action !== 'off' && (
+              <div className="space-y-2">
+                <div>
+                  <label htmlFor="sensitive-apps" className="text-sm font-medium">
+                    Sensitive Apps
+                  </label>
+                  <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                    Everything copied from these apps counts as sensitive (window class or
+                    process name, * as wildcard, comma separated)
+                  </p>
+                </div>
+                <input
+                  id="sensitive-apps"
+                  type="text"
+                  key={settings.sensitive_apps.join(',')}
+                  defaultValue={settings.sensitive_apps.join(', ')}
+                  onBlur={(e) => {
+                    const apps = e.target.value
+                      .split(',')
+                      .map((app) => app.trim())
+                      .filter(Boolean)
+                    if (apps.join(',') !== settings.sensitive_apps.join(',')) {
+                      updateSettings({ sensitive_apps: apps })
+                    }
+                  }}
+                  placeholder="1password, bitwarden"
+                  className={clsx(
+                    'w-full px-3 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                    isDark
+                      ? 'bg-white/5 border-white/10 text-white'
+                      : 'bg-gray-50 border-gray-200 text-gray-900'
+                  )}
+                />
+              </div>
+            )}
+
+            <div className="space-y-2">
+              <div>
+                <label htmlFor="excluded-apps" className="text-sm font-medium">
+                  Excluded Apps
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Copies from these apps are never saved (window class or process name, * as
+                  wildcard, comma separated)
+                </p>
+              </div>
+              <input
+                id="excluded-apps"
+                type="text"
+                // Re-mount when settings change elsewhere so the uncontrolled value stays in sync
+                key={settings.excluded_apps.join(',')}
+                defaultValue={settings.excluded_apps.join(', ')}
+                onBlur={(e) => {
+                  const apps = e.target.value
+                    .split(',')
+                    .map((app) => app.trim())
+                    .filter(Boolean)
+                  if (apps.join(',') !== settings.excluded_apps.join(',')) {
+                    updateSettings({ excluded_apps: apps })
+                  }
+                }}
+                placeholder="keepassxc, bitwarden"
+                className={clsx(
+                  'w-full px-3 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="selection-sync" className="text-sm font-medium">
+                  Sync Selections
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Copy between the clipboard and selected text (middle-click paste)
+                </p>
+              </div>
+              <select
+                id="selection-sync"
+                value={settings.selection_sync}
+                onChange={(e) =>
+                  updateSettings({ selection_sync: e.target.value as SelectionSync })
+                }
+                className={clsx(
+                  'px-2 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              >
+                <option value="off">Off</option>
+                <option value="clipboard_to_primary">Clipboard to selection</option>
+                <option value="primary_to_clipboard">Selection to clipboard</option>
+              </select>
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="max-image-megapixels" className="text-sm font-medium">
+                  Max Image Size (megapixels)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Larger copied images are downscaled or skipped. 0 means no limit.
+                </p>
+              </div>
+              <input
+                id="max-image-megapixels"
+                type="number"
+                min={0}
+                max={MAX_IMAGE_LIMIT}
+                value={settings.max_image_megapixels}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  const safe = Number.isNaN(parsed) ? settings.max_image_megapixels : parsed
+                  const value = Math.max(0, Math.min(MAX_IMAGE_LIMIT, safe))
+                  updateSettings({ max_image_megapixels: value })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="max-image-megabytes" className="text-sm font-medium">
+                  Max Image File Size (MB)
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  Limit for the stored file of each image. 0 means no limit.
+                </p>
+              </div>
+              <input
+                id="max-image-megabytes"
+                type="number"
+                min={0}
+                max={MAX_IMAGE_LIMIT}
+                value={settings.max_image_megabytes}
+                onChange={(e) => {
+                  const parsed = Number.parseInt(e.target.value, 10)
+                  const safe = Number.isNaN(parsed) ? settings.max_image_megabytes : parsed
+                  const value = Math.max(0, Math.min(MAX_IMAGE_LIMIT, safe))
+                  updateSettings({ max_image_megabytes: value })
+                }}
+                className={clsx(
+                  'w-28 text-right font-mono border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  'input-number-compact no-number-spinner',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              />
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="oversized-image-action" className="text-sm font-medium">
+                  Oversized Images
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  What happens to images over the limits
+                </p>
+              </div>
+              <select
+                id="oversized-image-action"
+                value={settings.oversized_image_action}
+                onChange={(e) => updateSettings({ oversized_image_action: e.target.value as OversizedImageAction })}
+                className={clsx(
+                  'px-2 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              >
+                <option value="downscale">Downscale</option>
+                <option value="skip">Skip and notify</option>
+              </select>
+            </div>
+
+            <div className="flex justify-between items-center">
+              <div>
+                <label htmlFor="image-format" className="text-sm font-medium">
+                  Image Format
+                </label>
+                <p className={clsx('text-xs mt-0.5', isDark ? 'text-gray-400' : 'text-gray-500')}>
+                  PNG keeps every pixel, JPEG is much smaller
+                </p>
+              </div>
+              <select
+                id="image-format"
+                value={settings.image_format}
+                onChange={(e) => updateSettings({ image_format: e.target.value as ImageFormat })}
+                className={clsx(
+                  'px-2 py-1.5 text-sm border rounded-md transition-all focus:outline-none focus:ring-2 focus:ring-win11-bg-accent/50',
+                  isDark
+                    ? 'bg-white/5 border-white/10 text-white'
+                    : 'bg-gray-50 border-gray-200 text-gray-900'
+                )}
+              >
+                <option value="png">PNG (lossless)</option>
+                <option value="jpeg">JPEG (lossy)</option>
+              </select>
+            </div>
           </div>
         </section>
 
@@ -676,7 +1283,12 @@ function SettingsApp() {
         </section>
 
         {/* Features Section */}
-        <FeaturesSection settings={settings} isDark={isDark} onToggle={handleToggle} />
+        <FeaturesSection
+          settings={settings}
+          isDark={isDark}
+          onToggle={handleToggle}
+          encryptionError={encryptionError}
+        />
 
         {/* Reset Section */}
         <div className="flex justify-end pt-2">
@@ -1,8 +1,9 @@
-import { useState, useMemo, useRef, useEffect, useCallback } from 'react'
+import { useState, useRef, useEffect, useCallback } from 'react'
+import { invoke } from '@tauri-apps/api/core'
 import { listen } from '@tauri-apps/api/event'
 import { clsx } from 'clsx'
 
-import type { ClipboardItem, UserSettings } from '../types/clipboard'
+import type { ClipboardItemSummary, SearchResults, UserSettings } from '../types/clipboard'
 import type { TabBarRef } from './TabBar'
 import { Header } from './Header'
 import { SearchBar } from './common/SearchBar'
@@ -10,8 +11,17 @@ import { EmptyState } from './EmptyState'
 import { HistoryItem } from './HistoryItem'
 import { useHistoryKeyboardNavigation } from '../hooks/useHistoryKeyboardNavigation'
 
+/** Delay after the last keystroke before the backend is queried */
+const SEARCH_DEBOUNCE_MS = 150
+/** Number of search results shown */
+const SEARCH_RESULT_LIMIT = 200
+
 export function ClipboardTab(props: {
-  history: ClipboardItem[]
+  history: ClipboardItemSummary[]
+  /** Number of items in the full history (only some pages may be loaded) */
+  total: number
+  hasMore: boolean
+  loadMore: () => void
   isLoading: boolean
   isDark: boolean
   tertiaryOpacity: number
@@ -19,12 +29,16 @@ export function ClipboardTab(props: {
   clearHistory: () => void
   deleteItem: (id: string) => void
   togglePin: (id: string) => void
+  toggleSensitive: (id: string) => void
   onPaste: (id: string) => void
   settings: UserSettings
   tabBarRef: React.RefObject<TabBarRef | null>
 }) {
   const {
     history,
+    total,
+    hasMore,
+    loadMore,
     isLoading,
     isDark,
     tertiaryOpacity,
@@ -32,6 +46,7 @@ export function ClipboardTab(props: {
     clearHistory,
     deleteItem,
     togglePin,
+    toggleSensitive,
     onPaste,
     settings,
     tabBarRef,
@@ -107,39 +122,52 @@ export function ClipboardTab(props: {
     }
   }, [])
 
-  // Filter history
-  const filteredHistory = useMemo(() => {
-    if (!searchQuery) return history
-
-    let regex: RegExp | null = null
-    if (isRegexMode) {
-      try {
-        regex = new RegExp(searchQuery, 'i')
-      } catch (err) {
-        console.error('Invalid regex pattern in clipboard search query:', searchQuery, err)
-        return []
-      }
+  // Search on the backend; re-run when the history changes so results stay current.
+  // Supports filters like type:image, pinned:true, app:firefox, before:/after:2024-05-01
+  const [searchResults, setSearchResults] = useState<SearchResults | null>(null)
+  useEffect(() => {
+    if (!searchQuery) {
+      setSearchResults(null)
+      return
     }
 
-    return history.filter((item) => {
-      let searchableText = ''
-      if (item.content.type === 'Text') {
-        searchableText = item.content.data
-      } else if (item.content.type === 'RichText') {
-        searchableText = item.content.data.plain
-      } else {
-        return false
-      }
+    let cancelled = false
+    const timer = setTimeout(() => {
+      invoke<SearchResults>('search_history', {
+        query: searchQuery,
+        regex: isRegexMode,
+        limit: SEARCH_RESULT_LIMIT,
+      })
+        .then((results) => {
+          if (!cancelled) setSearchResults(results)
+        })
+        .catch((err) => {
+          console.error('Clipboard search failed:', searchQuery, err)
+          if (!cancelled) setSearchResults({ items: [], total: 0, offset: 0 })
+        })
+    }, SEARCH_DEBOUNCE_MS)
 
-      if (isRegexMode && regex) {
-        return regex.test(searchableText)
-      } else if (!isRegexMode) {
-        return searchableText.toLowerCase().includes(searchQuery.toLowerCase())
-      }
-      return false
-    })
+    return () => {
+      cancelled = true
+      clearTimeout(timer)
+    }
   }, [history, searchQuery, isRegexMode])
 
+  const filteredHistory = searchQuery && searchResults ? searchResults.items : history
+
+  // Load the next page when the end of the list scrolls into view
+  const loadMoreRef = useRef<HTMLDivElement>(null)
+  const isShowingSearch = Boolean(searchQuery && searchResults)
+  useEffect(() => {
+    const sentinel = loadMoreRef.current
+    if (!sentinel || !hasMore || isShowingSearch) return
+    const observer = new IntersectionObserver((entries) => {
+      if (entries.some((entry) => entry.isIntersecting)) loadMore()
+    })
+    observer.observe(sentinel)
+    return () => observer.disconnect()
+  }, [hasMore, isShowingSearch, loadMore, history.length])
+
   // Keyboard navigation
   useHistoryKeyboardNavigation({
     activeTab: 'clipboard', // Always 'clipboard' when this component is mounted
@@ -187,7 +215,7 @@ export function ClipboardTab(props: {
     <>
       <Header
         onClearHistory={clearHistory}
-        itemCount={filteredHistory.length}
+        itemCount={searchQuery && searchResults ? searchResults.total : total}
         isDark={isDark}
         tertiaryOpacity={tertiaryOpacity}
         isCompact={isCompact}
@@ -202,7 +230,7 @@ export function ClipboardTab(props: {
             onChange={setSearchQuery}
             isDark={isDark}
             opacity={secondaryOpacity}
-            placeholder="Search history..."
+            placeholder="Search history... (type:image, app:firefox)"
             isRegex={isRegexMode}
             onToggleRegex={() => setIsRegexMode(!isRegexMode)}
             onClear={() => {
@@ -238,6 +266,7 @@ export function ClipboardTab(props: {
               onPaste={onPaste}
               onDelete={deleteItem}
               onTogglePin={togglePin}
+              onToggleSensitive={toggleSensitive}
               onFocus={() => setFocusedIndex(index)}
               isDark={isDark}
               secondaryOpacity={secondaryOpacity}
@@ -246,6 +275,7 @@ export function ClipboardTab(props: {
               enableUiPolish={settings.enable_ui_polish}
             />
           ))}
+          {hasMore && !isShowingSearch && <div ref={loadMoreRef} className="h-px" />}
         </div>
       )}
     </>
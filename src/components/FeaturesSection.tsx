@@ -13,16 +13,39 @@ const FEATURES = [
     label: 'UI Polish',
     desc: 'Enable animations and compact mode support.',
   },
+  {
+    key: 'capture_primary_selection',
+    label: 'Capture Selected Text',
+    desc: 'Also save text you select (middle-click paste), marked separately in history.',
+  },
+  {
+    key: 'transient_paste',
+    label: 'Keep Clipboard After Paste',
+    desc: 'Put back what you had copied after pasting an item from history.',
+  },
+  {
+    key: 'persist_clipboard',
+    label: 'Keep Clipboard When Apps Close',
+    desc: 'Keep the last copied item available after the app it came from exits.',
+  },
+  {
+    key: 'encrypt_history',
+    label: 'Encrypt History',
+    desc: 'Encrypt saved clipboard history using a key stored in your keyring.',
+  },
 ] as const
 
 export function FeaturesSection({
   settings,
   isDark,
   onToggle,
+  encryptionError,
 }: {
   settings: UserSettings
   isDark: boolean
   onToggle: (key: BooleanSettingKey) => void
+  /** Why the saved history could not be unlocked at startup */
+  encryptionError: string | null
 }) {
   return (
     <section
@@ -45,6 +68,12 @@ export function FeaturesSection({
               <div className={clsx('text-xs', isDark ? 'text-gray-400' : 'text-gray-500')}>
                 {feature.desc}
               </div>
+              {feature.key === 'encrypt_history' && encryptionError && (
+                <div className="text-xs text-red-500 mt-1">
+                  Saved history could not be unlocked: {encryptionError}. History is not being
+                  saved until the key is available again and the app is restarted.
+                </div>
+              )}
             </div>
             <Switch
               checked={settings[feature.key]}
@@ -1,18 +1,20 @@
+import { useEffect, useState } from 'react'
+import { invoke } from '@tauri-apps/api/core'
 import { clsx } from 'clsx'
-import type { ClipboardItem } from '../../types/clipboard'
+import type { ClipboardItemSummary } from '../../types/clipboard'
 
 export function TextContent({
   item,
   isDark,
   effectiveCompact,
 }: {
-  item: ClipboardItem
+  item: ClipboardItemSummary
   isDark: boolean
   effectiveCompact: boolean
 }) {
-  if (item.content.type !== 'Text' && item.content.type !== 'RichText') return null
+  if (item.kind !== 'text' && item.kind !== 'rich_text') return null
 
-  const textToDisplay = item.content.type === 'Text' ? item.content.data : item.content.data.plain
+  const textToDisplay = item.text ?? item.preview
 
   return (
     <p
@@ -27,17 +29,56 @@ export function TextContent({
   )
 }
 
+export function FilesContent({
+  item,
+  isDark,
+  effectiveCompact,
+}: {
+  item: ClipboardItemSummary
+  isDark: boolean
+  effectiveCompact: boolean
+}) {
+  if (item.kind !== 'files') return null
+  const count = item.file_count ?? 0
+
+  return (
+    <div className="min-w-0">
+      <p
+        className={clsx(
+          'text-sm truncate',
+          isDark ? 'text-win11-text-primary' : 'text-win11Light-text-primary'
+        )}
+        title={item.text}
+      >
+        {item.preview}
+      </p>
+      {!effectiveCompact && (
+        <span
+          className={clsx(
+            'text-xs',
+            isDark ? 'text-win11-text-tertiary' : 'text-win11Light-text-secondary'
+          )}
+        >
+          {count === 1 ? '1 file' : `${count} files`}
+          {item.operation === 'cut' ? ' · cut' : ''}
+        </span>
+      )}
+    </div>
+  )
+}
+
 export function ImageContent({
   item,
   isDark,
   effectiveCompact,
 }: {
-  item: ClipboardItem
+  item: ClipboardItemSummary
   isDark: boolean
   effectiveCompact: boolean
 }) {
-  if (item.content.type !== 'Image') return null
-  const { width, height, base64 } = item.content.data
+  if (item.kind !== 'image') return null
+  const width = item.width ?? 0
+  const height = item.height ?? 0
 
   if (effectiveCompact) {
     return (
@@ -52,13 +93,36 @@ export function ImageContent({
     )
   }
 
+  return <ImagePreview id={item.id} width={width} height={height} />
+}
+
+function ImagePreview({ id, width, height }: { id: string; width: number; height: number }) {
+  const [src, setSrc] = useState<string | null>(null)
+
+  // The list shows a small thumbnail; the full image is only read on paste
+  useEffect(() => {
+    let cancelled = false
+    invoke<string>('get_thumbnail', { id })
+      .then((base64) => {
+        if (!cancelled) setSrc(`data:image/png;base64,${base64}`)
+      })
+      .catch((err) => console.warn('[ImageContent] Failed to load image', err))
+    return () => {
+      cancelled = true
+    }
+  }, [id])
+
   return (
     <div className="relative">
-      <img
-        src={`data:image/png;base64,${base64}`}
-        alt="Clipboard image"
-        className="max-w-full max-h-24 rounded object-contain bg-black/10"
-      />
+      {src ? (
+        <img
+          src={src}
+          alt="Clipboard image"
+          className="max-w-full max-h-24 rounded object-contain bg-black/10"
+        />
+      ) : (
+        <div className="w-full h-24 rounded bg-black/10" />
+      )}
       <span className="absolute bottom-1 right-1 text-xs px-1.5 py-0.5 rounded bg-black/60 text-white">
         {width}×{height}
       </span>
@@ -1,18 +1,19 @@
 import { useCallback, forwardRef, useRef, useEffect } from 'react'
 import { clsx } from 'clsx'
-import { Pin, X, Image as ImageIcon, Type } from 'lucide-react'
-import type { ClipboardItem } from '../../types/clipboard'
+import { Pin, X, Image as ImageIcon, Type, Files, KeyRound } from 'lucide-react'
+import type { ClipboardItemSummary } from '../../types/clipboard'
 import { getCardBackgroundStyle, getTertiaryBackgroundStyle } from '../../utils/themeUtils'
 import { useSmartActions } from '../../hooks/useSmartActions'
 import { HistorySmartActions } from '../HistorySmartActions'
-import { TextContent, ImageContent, Timestamp } from './_HistoryItemContent'
+import { TextContent, ImageContent, FilesContent, Timestamp } from './_HistoryItemContent'
 import { getIconSize, getIconContainerClasses } from './_HistoryItemUtils'
 
 interface HistoryItemProps {
-  item: ClipboardItem
+  item: ClipboardItemSummary
   onPaste: (id: string) => void
   onDelete: (id: string) => void
   onTogglePin: (id: string) => void
+  onToggleSensitive: (id: string) => void
   onFocus?: () => void
   index: number
   isFocused?: boolean
@@ -30,6 +31,7 @@ export const HistoryItem = forwardRef<HTMLDivElement, HistoryItemProps>(function
     onPaste,
     onDelete,
     onTogglePin,
+    onToggleSensitive,
     onFocus,
     index,
     isFocused = false,
@@ -60,7 +62,7 @@ export const HistoryItem = forwardRef<HTMLDivElement, HistoryItemProps>(function
     },
     [ref]
   )
-  const isText = item.content.type === 'Text' || item.content.type === 'RichText'
+  const isText = item.kind === 'text' || item.kind === 'rich_text'
 
   // Use compact mode only if enabled by flag
   const effectiveCompact = enableUiPolish ? isCompact : false
@@ -100,6 +102,15 @@ export const HistoryItem = forwardRef<HTMLDivElement, HistoryItemProps>(function
     [item.id, onTogglePin]
   )
 
+  // Handle sensitive toggle with stopPropagation
+  const handleToggleSensitive = useCallback(
+    (e: React.MouseEvent) => {
+      e.stopPropagation()
+      onToggleSensitive(item.id)
+    },
+    [item.id, onToggleSensitive]
+  )
+
   // Prevent buttons from taking focus on pointer down (covers mouse/touch/pen)
   const handlePointerDownPreventDefault = useCallback((e: React.PointerEvent) => {
     e.preventDefault()
@@ -187,6 +198,13 @@ export const HistoryItem = forwardRef<HTMLDivElement, HistoryItemProps>(function
                 isDark ? 'text-win11-text-secondary' : 'text-win11Light-text-secondary'
               )}
             />
+          ) : item.kind === 'files' ? (
+            <Files
+              className={clsx(
+                iconSize,
+                isDark ? 'text-win11-text-secondary' : 'text-win11Light-text-secondary'
+              )}
+            />
           ) : (
             <ImageIcon
               className={clsx(
@@ -201,6 +219,7 @@ export const HistoryItem = forwardRef<HTMLDivElement, HistoryItemProps>(function
         <div className="flex-1 min-w-0">
           <TextContent item={item} isDark={isDark} effectiveCompact={effectiveCompact} />
           <ImageContent item={item} isDark={isDark} effectiveCompact={effectiveCompact} />
+          <FilesContent item={item} isDark={isDark} effectiveCompact={effectiveCompact} />
           <Timestamp show={!effectiveCompact} isDark={isDark} timestamp={item.timestamp} />
         </div>
 
@@ -238,6 +257,25 @@ export const HistoryItem = forwardRef<HTMLDivElement, HistoryItemProps>(function
             <Pin className="w-4 h-4" fill={item.pinned ? 'currentColor' : 'none'} />
           </button>
 
+          {/* Sensitive button */}
+          <button
+            onPointerDown={handlePointerDownPreventDefault}
+            onClick={handleToggleSensitive}
+            className={clsx(
+              'p-1.5 rounded-md transition-colors',
+              isDark ? 'hover:bg-win11-bg-tertiary' : 'hover:bg-win11Light-bg-tertiary',
+              item.sensitive
+                ? 'text-win11-bg-accent'
+                : isDark
+                  ? 'text-win11-text-tertiary'
+                  : 'text-win11Light-text-secondary'
+            )}
+            title={item.sensitive ? 'Unmark as sensitive' : 'Mark as sensitive'}
+            tabIndex={-1}
+          >
+            <KeyRound className="w-4 h-4" />
+          </button>
+
           {/* Delete button */}
           <button
             onPointerDown={handlePointerDownPreventDefault}
@@ -1,75 +1,101 @@
-import { useState, useEffect, useCallback } from 'react'
+import { useState, useEffect, useCallback, useRef } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { listen, UnlistenFn } from '@tauri-apps/api/event'
-import type { ClipboardItem } from '../types/clipboard'
+import type { ClipboardItemSummary, HistoryItemEvent, HistoryPage } from '../types/clipboard'
+
+/** Items requested per page */
+const PAGE_SIZE = 50
+
+/** Places an item at its position in the full history, if that is within the loaded pages */
+function placeItem(
+  items: ClipboardItemSummary[],
+  item: ClipboardItemSummary,
+  index: number
+): ClipboardItemSummary[] {
+  const others = items.filter((i) => i.id !== item.id)
+  if (index > others.length) return others
+  return [...others.slice(0, index), item, ...others.slice(index)]
+}
 
 /**
- * Hook for managing clipboard history
+ * Hook for managing clipboard history.
+ * Loads summaries page by page and applies the backend's change events in place.
  */
 export function useClipboardHistory() {
-  const [history, setHistory] = useState<ClipboardItem[]>([])
+  const [history, setHistory] = useState<ClipboardItemSummary[]>([])
+  const [total, setTotal] = useState(0)
   const [isLoading, setIsLoading] = useState(true)
   const [error, setError] = useState<string | null>(null)
+  const nextCursorRef = useRef<string | null>(null)
+  const [hasMore, setHasMore] = useState(false)
+  const isLoadingMoreRef = useRef(false)
+
+  const applyPage = useCallback((page: HistoryPage, append: boolean) => {
+    setHistory((prev) => {
+      if (!append) return page.items
+      const known = new Set(prev.map((item) => item.id))
+      return [...prev, ...page.items.filter((item) => !known.has(item.id))]
+    })
+    setTotal(page.total)
+    nextCursorRef.current = page.next_cursor
+    setHasMore(page.next_cursor !== null)
+  }, [])
 
-  // Fetch initial history
+  // Fetch the first page (also used to recover from a stale cursor)
   const fetchHistory = useCallback(async () => {
     try {
       setIsLoading(true)
-      const items = await invoke<ClipboardItem[]>('get_history')
-      setHistory(items)
+      const page = await invoke<HistoryPage>('get_history_page', { limit: PAGE_SIZE })
+      applyPage(page, false)
       setError(null)
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Failed to fetch history')
     } finally {
       setIsLoading(false)
     }
-  }, [])
+  }, [applyPage])
+
+  // Fetch the page after the loaded ones
+  const loadMore = useCallback(async () => {
+    const cursor = nextCursorRef.current
+    if (cursor === null || isLoadingMoreRef.current) return
+    isLoadingMoreRef.current = true
+    try {
+      const page = await invoke<HistoryPage>('get_history_page', { cursor, limit: PAGE_SIZE })
+      applyPage(page, true)
+    } catch (err) {
+      // The cursor item was removed meanwhile: start over
+      console.warn('[useClipboardHistory] Failed to load more history, reloading', err)
+      await fetchHistory()
+    } finally {
+      isLoadingMoreRef.current = false
+    }
+  }, [applyPage, fetchHistory])
 
-  // Clear all history
+  // Clear all history (the backend confirms with a history-cleared event)
   const clearHistory = useCallback(async () => {
     try {
       await invoke('clear_history')
-      setHistory((prev) => prev.filter((item) => item.pinned))
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Failed to clear history')
     }
   }, [])
 
-  // Delete a specific item
+  // Delete a specific item (removed locally when item-removed arrives)
   const deleteItem = useCallback(async (id: string) => {
     try {
       await invoke('delete_item', { id })
-      setHistory((prev) => prev.filter((item) => item.id !== id))
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Failed to delete item')
     }
   }, [])
 
-  // Toggle pin status
+  // Toggle pin status (repositioned locally when item-updated arrives)
   const togglePin = useCallback(
     async (id: string) => {
       try {
-        const updatedItem = await invoke<ClipboardItem>('toggle_pin', { id })
-        if (updatedItem) {
-          setHistory((prev) => {
-            // Remove the item from its current position
-            const otherItems = prev.filter((item) => item.id !== id)
-            const pinnedItems = otherItems.filter((item) => item.pinned)
-            const unpinnedItems = otherItems.filter((item) => !item.pinned)
-
-            if (updatedItem.pinned) {
-              // Item was pinned - add to the end of pinned items (top of list)
-              return [...pinnedItems, updatedItem, ...unpinnedItems]
-            } else {
-              // Item was unpinned - insert in correct position by timestamp
-              const allUnpinned = [updatedItem, ...unpinnedItems]
-              allUnpinned.sort(
-                (a, b) => new Date(b.timestamp).getTime() - new Date(a.timestamp).getTime()
-              )
-              return [...pinnedItems, ...allUnpinned]
-            }
-          })
-        } else {
+        const updatedItem = await invoke<ClipboardItemSummary | null>('toggle_pin', { id })
+        if (!updatedItem) {
           // Item not found - refresh history
           console.warn('[useClipboardHistory] Toggle pin returned null, refreshing history')
           await fetchHistory()
@@ -83,90 +109,73 @@ export function useClipboardHistory() {
     [fetchHistory]
   )
 
+  // Toggle the sensitive flag (updated locally when item-updated arrives)
+  const toggleSensitive = useCallback(async (id: string) => {
+    try {
+      await invoke('toggle_sensitive', { id })
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to mark item as sensitive')
+    }
+  }, [])
+
   // Paste an item
-  const pasteItem = useCallback(
-    async (id: string) => {
-      try {
-        await invoke('paste_item', { id })
-      } catch (err) {
-        const errorMessage = err instanceof Error ? err.message : String(err)
-        console.warn('[useClipboardHistory] Paste failed, refreshing history:', errorMessage)
-        // If paste failed due to item not found, refresh history
-        // The backend already emits history-sync event, but we fetch as backup
-        await fetchHistory()
-        setError(errorMessage)
-      }
-    },
-    [fetchHistory]
-  )
+  const pasteItem = useCallback(async (id: string) => {
+    try {
+      await invoke('paste_item', { id })
+    } catch (err) {
+      // A missing item is dropped through the item-removed event the backend emits
+      const errorMessage = err instanceof Error ? err.message : String(err)
+      console.warn('[useClipboardHistory] Paste failed:', errorMessage)
+      setError(errorMessage)
+    }
+  }, [])
 
-  // Listen for clipboard changes
+  // Apply fine-grained history events
   useEffect(() => {
     fetchHistory()
 
-    let unlistenChanged: UnlistenFn | undefined
-    let unlistenCleared: UnlistenFn | undefined
-    let unlistenSync: UnlistenFn | undefined
-
-    const setupListeners = async () => {
-      unlistenChanged = await listen<ClipboardItem>('clipboard-changed', async () => {
-        // Backend emits the event and already enforces trimming. Fetch full history
-        // to keep frontend in sync with backend limits and ordering.
-        try {
-          await fetchHistory()
-        } catch (e) {
-          console.warn('[useClipboardHistory] Failed to refresh history on clipboard-changed', e)
-        }
-      })
-
-      unlistenCleared = await listen('history-cleared', async () => {
-        unlistenChanged = await listen<ClipboardItem>('clipboard-changed', (event) => {
-          console.log('[useClipboardHistory] clipboard-changed event received')
-          const newItem = event.payload
-
-          if (!newItem) {
-            // Fallback: if backend did not send a payload, refresh full history.
-            fetchHistory().catch((e) => {
-              console.warn(
-                '[useClipboardHistory] Failed to refresh history on clipboard-changed (no payload)',
-                e
-              )
-            })
-            return
-          }
-
-          // Apply delta update locally to avoid fetching entire history for large limits.
-          setHistory((prev) => {
-            // If the item already exists, ignore the event (duplicate)
-            if (prev.some((i) => i.id === newItem.id)) return prev
-
-            const pinnedItems = prev.filter((i) => i.pinned)
-            const unpinnedItems = prev.filter((i) => !i.pinned)
-
-            // Insert new item at the top of unpinned items (after pins)
-            return [...pinnedItems, newItem, ...unpinnedItems]
-          })
-        })
-      })
+    const removeIds = (ids: string[]) => {
+      const removed = new Set(ids)
+      setHistory((prev) => prev.filter((item) => !removed.has(item.id)))
+      setTotal((prev) => Math.max(0, prev - ids.length))
     }
 
-    setupListeners()
+    const listeners: Promise<UnlistenFn>[] = [
+      listen<HistoryItemEvent>('item-added', (event) => {
+        const { item, index } = event.payload
+        setHistory((prev) => placeItem(prev, item, index))
+        setTotal((prev) => prev + 1)
+      }),
+      listen<HistoryItemEvent>('item-updated', (event) => {
+        const { item, index } = event.payload
+        setHistory((prev) => placeItem(prev, item, index))
+      }),
+      listen<{ id: string }>('item-removed', (event) => removeIds([event.payload.id])),
+      listen<{ ids: string[] }>('items-trimmed', (event) => removeIds(event.payload.ids)),
+      listen('history-cleared', () => {
+        fetchHistory().catch((e) => {
+          console.warn('[useClipboardHistory] Failed to refresh history after clearing', e)
+        })
+      }),
+    ]
 
     return () => {
-      unlistenChanged?.()
-      unlistenCleared?.()
-      unlistenSync?.()
+      listeners.forEach((unlisten) => unlisten.then((u) => u()))
     }
   }, [fetchHistory])
 
   return {
     history,
+    total,
+    hasMore,
     isLoading,
     error,
     fetchHistory,
+    loadMore,
     clearHistory,
     deleteItem,
     togglePin,
+    toggleSensitive,
     pasteItem,
   }
 }
@@ -1,14 +1,15 @@
 import { useMemo, useCallback } from 'react'
 import type { MouseEvent } from 'react'
 import { smartActionService } from '../services/smartActionService'
-import type { ClipboardItem } from '../types/clipboard'
+import type { ClipboardItemSummary } from '../types/clipboard'
 import type { SmartAction } from '../services/smartActionService'
 
-export function useSmartActions(item: ClipboardItem, enableSmartActions: boolean) {
+export function useSmartActions(item: ClipboardItemSummary, enableSmartActions: boolean) {
   const smartActions = useMemo(() => {
     if (!enableSmartActions) return []
-    if (item.content.type === 'Text') {
-      return smartActionService.detectActions(item.content.data)
+    // Links, addresses and colors are short, so a truncated text never is one
+    if (item.kind === 'text' && item.text && !item.truncated) {
+      return smartActionService.detectActions(item.text)
     }
     return []
   }, [item, enableSmartActions])
@@ -16,18 +16,36 @@ export interface RichTextContent {
   }
 }
 
-/** Image content */
+/** Image content (the PNG is loaded on demand via `get_image_data`, the list uses `get_thumbnail`) */
 export interface ImageContent {
   type: 'Image'
   data: {
-    base64: string
+    blob: string
     width: number
     height: number
+    /** Blob key of the list thumbnail, if the image was scaled down for it */
+    thumbnail?: string
   }
 }
 
+/** Files and folders copied in a file manager */
+export interface FilesContent {
+  type: 'Files'
+  data: {
+    uris: string[]
+    operation: 'copy' | 'cut'
+  }
+}
+
+/** Another representation stored with an item (e.g. RTF), re-offered on paste */
+export interface ClipboardFormat {
+  mime_type: string
+  blob: string
+  size: number
+}
+
 /** Union of all content types */
-export type ClipboardContent = TextContent | RichTextContent | ImageContent
+export type ClipboardContent = TextContent | RichTextContent | ImageContent | FilesContent
 
 /** A single clipboard history item */
 export interface ClipboardItem {
@@ -36,11 +54,97 @@ export interface ClipboardItem {
   timestamp: string
   pinned: boolean
   preview: string
+  /** Set for secrets kept temporarily (never written to disk) */
+  expires_at?: string
+  /** Application the content was copied from, when known */
+  source_app?: string
+  source_window_title?: string
+  source_pid?: number
+  /** Selection the item came from; absent for the regular clipboard */
+  selection?: 'clipboard' | 'primary'
+  /** Other formats the source offered */
+  formats?: ClipboardFormat[]
+}
+
+/** Lightweight list entry; the full item is loaded with `get_item` when needed */
+export interface ClipboardItemSummary {
+  id: string
+  kind: 'text' | 'rich_text' | 'image' | 'files'
+  timestamp: string
+  pinned: boolean
+  /** Marked sensitive by the user (taken off the clipboard again after a paste) */
+  sensitive?: boolean
+  preview: string
+  /** Start of the plain text (or the paths of copied files) */
+  text?: string
+  /** Whether `text` was cut short */
+  truncated?: boolean
+  width?: number
+  height?: number
+  file_count?: number
+  operation?: 'copy' | 'cut'
+  expires_at?: string
+  source_app?: string
+  selection?: 'clipboard' | 'primary'
+}
+
+/** One page of `get_history_page` (or `get_history_by_app`) results */
+export interface HistoryPage {
+  items: ClipboardItemSummary[]
+  /** Cursor for the next page; null on the last page */
+  next_cursor: string | null
+  total: number
+}
+
+/** Number and storage size of items */
+export interface ItemStats {
+  count: number
+  bytes: number
+}
+
+/** Result of `get_history_stats`, keyed by content type in `by_type` */
+export interface HistoryStats {
+  total: ItemStats
+  pinned: ItemStats
+  by_type: Partial<Record<ClipboardItemSummary['kind'], ItemStats>>
+}
+
+/** Payload of the `item-added` and `item-updated` events */
+export interface HistoryItemEvent {
+  item: ClipboardItemSummary
+  /** Position of the item in the full history */
+  index: number
+}
+
+/** One page of `search_history` results */
+export interface SearchResults {
+  items: ClipboardItemSummary[]
+  /** Number of matches across all pages */
+  total: number
+  offset: number
 }
 
 /** Active tab in the UI */
 export type ActiveTab = 'clipboard' | 'gifs' | 'emoji' | 'kaomoji' | 'symbols'
 
+/** Mirroring between the CLIPBOARD and PRIMARY selections */
+export type SelectionSync = 'off' | 'clipboard_to_primary' | 'primary_to_clipboard'
+
+/** What is deleted when the session locks or the machine suspends */
+export type ClearOnLock = 'off' | 'sensitive' | 'unpinned' | 'all'
+
+/** Which item is put back on the empty clipboard at startup */
+export type RestoreOnStartup = 'off' | 'latest' | 'pinned'
+
+/** What happens to the clipboard after a sensitive item was pasted */
+export type SensitivePasteAction = 'off' | 'clear' | 'restore'
+
+/** Handling of copied images over the size limits */
+export type OversizedImageAction = 'downscale' | 'skip'
+
+/** Encoding of stored images */
+export type ImageFormat = 'png' | 'jpeg'
+
 /** Theme mode */
 export type ThemeMode = 'light' | 'dark' | 'system'
 
@@ -70,6 +174,30 @@ export interface UserSettings {
   enable_smart_actions: boolean
   enable_ui_polish: boolean
   max_history_size: number
+  max_history_bytes: number
+  max_text_history_bytes: number
+  max_image_history_bytes: number
+  retention_days: number
+  image_retention_days: number
+  /** Local time "HH:MM" of the daily purge, or null */
+  daily_purge_time: string | null
+  concealed_expiry_secs: number
+  clear_on_lock: ClearOnLock
+  sensitive_paste_action: SensitivePasteAction
+  sensitive_paste_delay_secs: number
+  sensitive_apps: string[]
+  transient_paste: boolean
+  persist_clipboard: boolean
+  restore_on_startup: RestoreOnStartup
+  excluded_apps: string[]
+  capture_primary_selection: boolean
+  selection_sync: SelectionSync
+  encrypt_history: boolean
+  encryption_passphrase_file: string | null
+  max_image_megapixels: number
+  max_image_megabytes: number
+  oversized_image_action: OversizedImageAction
+  image_format: ImageFormat
   custom_kaomojis: CustomKaomoji[]
   ui_scale: number
 }